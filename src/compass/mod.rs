@@ -0,0 +1,52 @@
+use bracket_lib::prelude::*;
+
+use crate::types::CellKind;
+
+/// Features marked at the edge of the canvas by default while they're off screen
+pub const TRACKED: [CellKind; 3] = [
+    CellKind::StairsDown,
+    CellKind::StairsUp,
+    CellKind::Shopkeeper,
+];
+
+/// Return the tile on the edge of `canvas` where a line from `from` toward `target` leaves it,
+/// with an arrow for the edge it's on, or nothing if `target` is on the canvas.
+///
+/// Both points are in screen coordinates, and `from` should be on the canvas.
+pub fn marker(canvas: Rect, from: Point, target: Point) -> Option<(Point, char)> {
+    if canvas.point_in_rect(target) {
+        return None;
+    }
+
+    let (dx, dy) = (target.x - from.x, target.y - from.y);
+    // How far along the line it is to the last column or row it crosses
+    let along = |d: i32, low: i32, high: i32, start: i32| match d {
+        0 => f32::INFINITY,
+        d if d > 0 => (high - 1 - start) as f32 / d as f32,
+        d => (low - start) as f32 / d as f32,
+    };
+    let tx = along(dx, canvas.x1, canvas.x2, from.x);
+    let ty = along(dy, canvas.y1, canvas.y2, from.y);
+    let t = tx.min(ty);
+
+    let point = Point::new(
+        (from.x + (dx as f32 * t).round() as i32)
+            .max(canvas.x1)
+            .min(canvas.x2 - 1),
+        (from.y + (dy as f32 * t).round() as i32)
+            .max(canvas.y1)
+            .min(canvas.y2 - 1),
+    );
+    let arrow = if tx <= ty {
+        if dx > 0 {
+            '>'
+        } else {
+            '<'
+        }
+    } else if dy > 0 {
+        'v'
+    } else {
+        '^'
+    };
+    Some((point, arrow))
+}
@@ -0,0 +1,49 @@
+use serde::{Deserialize, Serialize};
+
+/// What an enemy is up to
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum AiState {
+    /// Hasn't noticed the player yet and stays put
+    Asleep,
+    /// Knows where the player is, by sight or by scent
+    Chasing,
+    /// Lost track of the player and roams until it spots them again
+    Wandering,
+}
+
+/// Marks a cell that hunts the player after each of the player's turns
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct EnemyAI {
+    pub state: AiState,
+    /// How many turns in a row the enemy has gone without seeing the player
+    pub unseen: u32,
+    /// Id shared by every enemy in the same pack, if it runs with one
+    #[serde(default)]
+    pub pack: Option<u32>,
+}
+
+impl EnemyAI {
+    /// An enemy asleep until the player comes into view
+    pub fn new() -> Self {
+        Self {
+            state: AiState::Asleep,
+            unseen: 0,
+            pack: None,
+        }
+    }
+
+    /// An enemy already up and roaming, like one that has just wandered in
+    pub fn wandering() -> Self {
+        Self {
+            state: AiState::Wandering,
+            unseen: 0,
+            pack: None,
+        }
+    }
+
+    /// Return a copy of the enemy running with the pack `pack`
+    pub fn with_pack(mut self, pack: u32) -> Self {
+        self.pack = Some(pack);
+        self
+    }
+}
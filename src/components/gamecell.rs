@@ -1,14 +1,69 @@
 use bracket_lib::prelude::*;
 
-use crate::types::{CellAccess, CellKind};
+use serde::{Deserialize, Serialize};
 
-#[derive(Clone, Debug)]
+use crate::types::{CellAccess, CellKind, Dice, Effect};
+
+/// How many uses a weapon or piece of armor lasts before it breaks
+const DURABILITY: u32 = 40;
+/// How fast anything acts unless it's quicker or slower than usual
+pub const NORMAL_SPEED: u32 = 2;
+
+fn one() -> i32 {
+    1
+}
+
+fn one_hand() -> u8 {
+    1
+}
+
+fn normal_speed() -> u32 {
+    NORMAL_SPEED
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct GameCell {
     point: Point,
     kind: CellKind,
     name: String,
     color: RGB,
     access: CellAccess,
+    value: u32,
+    damage: Option<Dice>,
+    defense: i32,
+    health: Option<(u32, u32)>,
+    #[serde(default)]
+    hidden: bool,
+    #[serde(default)]
+    weight: u32,
+    #[serde(default)]
+    footprint: Vec<Point>,
+    /// How far the cell casts light while it has fuel
+    #[serde(default)]
+    light: i32,
+    #[serde(default)]
+    fuel: Option<(u32, u32)>,
+    /// Id shared by a locked door and the key that opens it
+    #[serde(default)]
+    lock: Option<u32>,
+    /// Uses left and uses when new, for equipment that wears out
+    #[serde(default)]
+    durability: Option<(u32, u32)>,
+    /// What's inside a chest until it's opened
+    #[serde(default)]
+    contents: Vec<GameCell>,
+    /// How many tiles away a weapon strikes in melee
+    #[serde(default = "one")]
+    reach: i32,
+    /// How many hands a weapon takes to wield
+    #[serde(default = "one_hand")]
+    hands: u8,
+    /// How fast an enemy acts, or the player wielding a weapon
+    #[serde(default = "normal_speed")]
+    speed: u32,
+    /// An effect a weapon leaves on whatever it hits, for that many turns
+    #[serde(default)]
+    on_hit_effect: Option<(Effect, u32)>,
 }
 
 impl GameCell {
@@ -19,28 +74,177 @@ impl GameCell {
             name: name.to_owned(),
             color,
             access,
+            value: 0,
+            damage: None,
+            defense: 0,
+            health: None,
+            hidden: false,
+            weight: 0,
+            footprint: Vec::new(),
+            light: 0,
+            fuel: None,
+            lock: None,
+            durability: None,
+            contents: Vec::new(),
+            reach: 1,
+            hands: 1,
+            speed: NORMAL_SPEED,
+            on_hit_effect: None,
         }
     }
 
+    /// Build a takeable weapon that hits for `damage`
+    pub fn weapon(kind: CellKind, name: &str, color: RGB, damage: Dice) -> Self {
+        Self::new(Point::new(0, 0), kind, name, color, CellAccess::Takeable)
+            .with_damage(damage)
+            .with_durability(DURABILITY)
+    }
+
+    /// Build a takeable piece of armor that blocks `defense` damage per hit
+    pub fn armor(kind: CellKind, name: &str, color: RGB, defense: i32) -> Self {
+        Self::new(Point::new(0, 0), kind, name, color, CellAccess::Takeable)
+            .with_defense(defense)
+            .with_durability(DURABILITY)
+    }
+
+    /// Return a copy of the cell that hits for `damage`
+    pub fn with_damage(mut self, damage: Dice) -> Self {
+        self.damage = Some(damage);
+        self
+    }
+
+    /// Return a copy of the cell that blocks `defense` damage per hit
+    pub fn with_defense(mut self, defense: i32) -> Self {
+        self.defense = defense;
+        self
+    }
+
+    /// Return a copy of the cell that can take `max` damage before it is destroyed
+    pub fn with_health(mut self, max: u32) -> Self {
+        self.health = Some((max, max));
+        self
+    }
+
+    /// Return a copy of the cell that isn't drawn until it's revealed
+    pub fn with_hidden(mut self) -> Self {
+        self.hidden = true;
+        self
+    }
+
+    /// Return a copy of the cell that weighs `weight` when carried
+    pub fn with_weight(mut self, weight: u32) -> Self {
+        self.weight = weight;
+        self
+    }
+
+    /// Return a copy of the cell that also covers the tiles at each of `offsets`
+    /// from its point
+    pub fn with_footprint(mut self, offsets: &[Point]) -> Self {
+        self.footprint = offsets.to_vec();
+        self
+    }
+
+    /// Return a copy of the cell that lights up `radius` tiles around it
+    /// for `fuel` turns of burning
+    pub fn with_light(mut self, radius: i32, fuel: u32) -> Self {
+        self.light = radius;
+        self.fuel = Some((fuel, fuel));
+        self
+    }
+
+    /// Return a copy of the cell that is a lock or key with the id `lock`
+    pub fn with_lock(mut self, lock: u32) -> Self {
+        self.lock = Some(lock);
+        self
+    }
+
+    /// Return a copy of the cell that breaks after `max` uses
+    pub fn with_durability(mut self, max: u32) -> Self {
+        self.durability = Some((max, max));
+        self
+    }
+
+    /// Return a copy of the cell that strikes enemies up to `reach` tiles away
+    pub fn with_reach(mut self, reach: i32) -> Self {
+        self.reach = reach;
+        self
+    }
+
+    /// Return a copy of the cell that takes `hands` hands to wield
+    pub fn with_hands(mut self, hands: u8) -> Self {
+        self.hands = hands;
+        self
+    }
+
+    /// Return a copy of the cell that acts at `speed`, or lets its wielder act at it
+    pub fn with_speed(mut self, speed: u32) -> Self {
+        self.speed = speed;
+        self
+    }
+
+    /// Return a copy of the weapon that leaves `effect` on what it hits for `turns` turns
+    pub fn with_on_hit_effect(mut self, effect: Effect, turns: u32) -> Self {
+        self.on_hit_effect = Some((effect, turns));
+        self
+    }
+
+    /// Return a copy of the cell holding `contents`, like a chest full of loot
+    pub fn with_contents(mut self, contents: Vec<GameCell>) -> Self {
+        self.contents = contents;
+        self
+    }
+
+    /// Return a copy of the cell worth `value` gold
+    pub fn with_value(mut self, value: u32) -> Self {
+        self.value = value;
+        self
+    }
+
+    /// Return a copy of the cell placed at `point`
+    pub fn with_point(mut self, point: Point) -> Self {
+        self.point = point;
+        self
+    }
+
+    pub fn move_pos(&mut self, a: i32, b: i32) {
+        self.point.x += a;
+        self.point.y += b;
+    }
+
     pub fn point(&self) -> Point {
         self.point
     }
+    /// Return every tile the cell covers, starting with its point
+    pub fn tiles(&self) -> Vec<Point> {
+        let mut tiles = vec![self.point];
+        tiles.extend(
+            self.footprint
+                .iter()
+                .map(|offset| Point::new(self.point.x + offset.x, self.point.y + offset.y)),
+        );
+        tiles
+    }
+    pub fn occupies(&self, point: Point) -> bool {
+        self.tiles().contains(&point)
+    }
     pub fn x(&self) -> i32 {
         self.point.x
     }
     pub fn y(&self) -> i32 {
         self.point.y
     }
-    pub fn symbol(&self) -> char {
-        self.kind.symbol()
+    pub fn kind(&self) -> CellKind {
+        self.kind
+    }
+    pub fn set_kind(&mut self, kind: CellKind) {
+        self.kind = kind;
     }
     /// Return the RGB color of the cell
     pub fn color(&self) -> RGB {
         self.color
     }
-    /// Return a brightened version of the cell's color
-    pub fn color_bright(&self) -> RGB {
-        RGB::from_f32(self.color.r * 1.5, self.color.g * 1.5, self.color.b * 1.5)
+    pub fn set_color(&mut self, color: RGB) {
+        self.color = color;
     }
     /// Return a black background for the cell
     pub fn bg_color(&self) -> RGB {
@@ -49,7 +253,180 @@ impl GameCell {
     pub fn name(&self) -> String {
         self.name.clone()
     }
+    pub fn set_name(&mut self, name: &str) {
+        self.name = name.to_owned();
+    }
+    /// Return the cell's name along with its stats, like "short sword (d6)"
+    pub fn label(&self) -> String {
+        match self.damage {
+            Some(damage) => format!("{} ({})", self.name, damage),
+            None if self.defense > 0 => format!("{} [{}]", self.name, self.defense),
+            None => self.name.clone(),
+        }
+    }
+    /// Return a few sentences on what the cell is, its stats and some flavor
+    pub fn description(&self) -> String {
+        let mut parts = vec![format!("A {}.", self.kind.name().replace('_', " "))];
+        if let Some(damage) = self.damage {
+            parts.push(format!("Damage {}.", damage));
+        }
+        if self.reach > 1 {
+            parts.push(format!("Reach {}.", self.reach));
+        }
+        if self.hands > 1 {
+            parts.push(String::from("Two-handed."));
+        }
+        if self.speed > NORMAL_SPEED {
+            parts.push(String::from("Quick."));
+        } else if self.speed < NORMAL_SPEED {
+            parts.push(String::from("Slow."));
+        }
+        if let Some((effect, _)) = self.on_hit_effect {
+            parts.push(format!("Causes {}.", effect.name()));
+        }
+        if self.defense > 0 {
+            parts.push(format!("Defense {}.", self.defense));
+        }
+        if let Some((cur, _)) = self.fuel {
+            parts.push(format!("Fuel for {} turns.", cur));
+        }
+        parts.push(format!("Weight {}.", self.weight));
+        parts.push(self.kind.flavor().to_owned());
+        parts.join(" ")
+    }
+    pub fn damage(&self) -> Option<Dice> {
+        self.damage
+    }
+    pub fn defense(&self) -> i32 {
+        self.defense
+    }
+    pub fn reach(&self) -> i32 {
+        self.reach
+    }
+    pub fn hands(&self) -> u8 {
+        self.hands
+    }
+    pub fn speed(&self) -> u32 {
+        self.speed
+    }
+    pub fn on_hit_effect(&self) -> Option<(Effect, u32)> {
+        self.on_hit_effect
+    }
+    /// Return what the cell holds
+    pub fn contents(&self) -> &[GameCell] {
+        &self.contents
+    }
+    /// Empty the cell out, returning what it held
+    pub fn take_contents(&mut self) -> Vec<GameCell> {
+        std::mem::take(&mut self.contents)
+    }
+    /// Put `contents` back in the cell, as when there was no room to take them out
+    pub fn set_contents(&mut self, contents: Vec<GameCell>) {
+        self.contents = contents;
+    }
+    /// Return the cell's current and max health, if it can be hurt
+    pub fn health(&self) -> Option<(u32, u32)> {
+        self.health
+    }
+    /// Regain up to `amount` health, never going over the max
+    pub fn heal(&mut self, amount: u32) {
+        if let Some((cur, max)) = self.health.as_mut() {
+            *cur = (*cur + amount).min(*max);
+        }
+    }
+    /// Raise the max health by `amount`, healing by as much
+    pub fn raise_max_health(&mut self, amount: u32) {
+        if let Some((cur, max)) = self.health.as_mut() {
+            *cur += amount;
+            *max += amount;
+        }
+    }
+    /// Lose `amount` health, returning whether that destroyed the cell
+    pub fn take_damage(&mut self, amount: u32) -> bool {
+        match self.health.as_mut() {
+            Some((cur, _)) => {
+                *cur = cur.saturating_sub(amount);
+                *cur == 0
+            }
+            None => false,
+        }
+    }
+    pub fn weight(&self) -> u32 {
+        self.weight
+    }
+    pub fn hidden(&self) -> bool {
+        self.hidden
+    }
+    pub fn reveal(&mut self) {
+        self.hidden = false;
+    }
+    /// Return how far the cell casts light, or 0 if it's out of fuel
+    pub fn light(&self) -> i32 {
+        match self.fuel {
+            Some((0, _)) => 0,
+            _ => self.light,
+        }
+    }
+    /// Burn a turn of fuel, returning whether that put the light out
+    pub fn burn(&mut self) -> bool {
+        match self.fuel.as_mut() {
+            Some((cur, _)) if *cur > 0 => {
+                *cur -= 1;
+                *cur == 0
+            }
+            _ => false,
+        }
+    }
+    /// Top the fuel back up, as when the next item in a stack is lit
+    pub fn refuel(&mut self) {
+        if let Some((cur, max)) = self.fuel.as_mut() {
+            *cur = *max;
+        }
+    }
+    /// Return the id of the lock the cell is or opens, if any
+    pub fn lock(&self) -> Option<u32> {
+        self.lock
+    }
+    /// Return whether the cell and `other` are items that pile up together,
+    /// sharing a name, kind and lock
+    pub fn stacks_with(&self, other: &GameCell) -> bool {
+        self.access == CellAccess::Takeable
+            && other.access == CellAccess::Takeable
+            && self.name == other.name
+            && self.kind == other.kind
+            && self.lock == other.lock
+    }
+    pub fn set_lock(&mut self, lock: u32) {
+        self.lock = Some(lock);
+    }
+    /// Return the cell's uses left and uses when new, if it wears out
+    pub fn durability(&self) -> Option<(u32, u32)> {
+        self.durability
+    }
+    /// Use the cell up a little, returning whether that broke it
+    pub fn wear(&mut self) -> bool {
+        match self.durability.as_mut() {
+            Some((cur, _)) if *cur > 0 => {
+                *cur -= 1;
+                *cur == 0
+            }
+            _ => false,
+        }
+    }
+    /// Bring the cell's durability back up to new, as when the next item in a stack is used
+    pub fn mend(&mut self) {
+        if let Some((cur, max)) = self.durability.as_mut() {
+            *cur = *max;
+        }
+    }
     pub fn access(&self) -> CellAccess {
         self.access
     }
+    pub fn set_access(&mut self, access: CellAccess) {
+        self.access = access;
+    }
+    /// Return the cell's worth in gold, or the size of a gold pile
+    pub fn value(&self) -> u32 {
+        self.value
+    }
 }
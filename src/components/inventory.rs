@@ -1,48 +1,513 @@
 use bracket_lib::prelude::*;
 
+use serde::{Deserialize, Serialize};
+
 use super::GameCell;
+use crate::types::{wrap, CellKind, Dice, Effect, EquipSlot, ItemCategory};
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Inventory {
     shown: bool,
-    cap: usize,
-    contents: Vec<GameCell>,
+    capacity: u32,
+    contents: Vec<(GameCell, usize)>,
+    weapon: Option<usize>,
+    armor: Option<usize>,
+    /// Index of the shield held in the hand a one-handed weapon leaves free
+    #[serde(default)]
+    off_hand: Option<usize>,
+    /// Index of the item highlighted while browsing the inventory
+    #[serde(skip)]
+    selected: usize,
+    /// Which items are listed while browsing
+    #[serde(skip)]
+    category: ItemCategory,
 }
 
 impl Inventory {
     pub fn new() -> Self {
         Self {
             shown: false,
-            cap: 10,
+            capacity: 50,
             contents: Vec::with_capacity(10),
+            weapon: None,
+            armor: None,
+            off_hand: None,
+            selected: 0,
+            category: ItemCategory::All,
         }
     }
 
-    pub fn print(&self, ctx: &mut BTerm, window_size: (u32, u32)) {
-        if self.shown {
+    /// Draw the inventory in the box `area` if it's shown or an item is being picked
+    pub fn print(&self, ctx: &mut BTerm, area: Rect, prompt: Option<&str>) {
+        if self.shown || prompt.is_some() {
             ctx.draw_box(
-                window_size.0 as i32 - window_size.0 as i32 / 4 - 2,
-                1,
-                window_size.0 as i32 / 4,
-                window_size.1 as i32 - 3,
+                area.x1,
+                area.y1,
+                area.width(),
+                area.height(),
                 RGB::from_u8(0, 170, 0),
                 RGB::from_u8(100, 100, 100),
             );
-            for (y, gc) in self.contents.iter().enumerate() {
-                ctx.print(
-                    window_size.0 as i32 - window_size.0 as i32 / 4 - 1,
-                    2 + y as i32,
-                    gc.name(),
-                );
+            ctx.print(
+                area.x1 + 1,
+                area.y2,
+                format!("Weight: {} / {}", self.total_weight(), self.capacity),
+            );
+            match prompt {
+                Some(prompt) => ctx.print(area.x1 + 1, area.y1, prompt),
+                None => ctx.print(area.x1 + 1, area.y1, self.category.name()),
+            }
+            let lines = self.list_filtered(self.category);
+            for (y, (i, line)) in self
+                .visible(self.category)
+                .into_iter()
+                .zip(lines)
+                .enumerate()
+            {
+                if prompt.is_none() && i == self.selected {
+                    ctx.print_color(
+                        area.x1 + 1,
+                        area.y1 + 1 + y as i32,
+                        RGB::named(BLACK),
+                        RGB::from_u8(0, 170, 0),
+                        &line,
+                    );
+                } else {
+                    ctx.print(area.x1 + 1, area.y1 + 1 + y as i32, &line);
+                }
+            }
+
+            let shown = self
+                .get(self.selected)
+                .filter(|item| prompt.is_none() && self.category.contains(item.kind()));
+            if let Some(item) = shown {
+                let lines = wrap(&item.description(), area.width() as usize - 1);
+                let top = area.y2 - 1 - lines.len() as i32;
+                for (y, line) in lines.iter().enumerate() {
+                    ctx.print_color(
+                        area.x1 + 1,
+                        top + y as i32,
+                        RGB::from_u8(150, 150, 150),
+                        RGB::new(),
+                        line,
+                    );
+                }
             }
         }
     }
 
+    /// Return a line per stack of items in `category`, labeled with the letter that
+    /// selects it and marked with a `*` and its durability if it's equipped.
+    /// Each keeps the letter it has when every item is listed.
+    pub fn list_filtered(&self, category: ItemCategory) -> Vec<String> {
+        self.visible(category)
+            .into_iter()
+            .map(|i| self.line(i))
+            .collect()
+    }
+
+    /// Return the index of every stack of items in `category`
+    fn visible(&self, category: ItemCategory) -> Vec<usize> {
+        self.contents
+            .iter()
+            .enumerate()
+            .filter(|(_, (item, _))| category.contains(item.kind()))
+            .map(|(i, _)| i)
+            .collect()
+    }
+
+    fn line(&self, i: usize) -> String {
+        let (gc, count) = &self.contents[i];
+        let equipped = match (self.slot_of(i), gc.durability()) {
+            (Some(_), Some((cur, max))) => format!(" * {}/{}", cur, max),
+            (Some(_), None) => String::from(" *"),
+            (None, _) => String::new(),
+        };
+        format!(
+            "{}) {}{}{}",
+            (b'a' + i as u8) as char,
+            gc.label(),
+            if *count > 1 {
+                format!(" x{}", count)
+            } else {
+                String::new()
+            },
+            equipped
+        )
+    }
+
     pub fn toggle(&mut self) {
         self.shown = !self.shown;
     }
 
-    pub fn take(&mut self, item: GameCell) {
-        self.contents.push(item);
+    /// Return whether the inventory is open for browsing
+    pub fn is_shown(&self) -> bool {
+        self.shown
+    }
+
+    /// Show the next category of items, moving the highlight onto it if it was left out
+    pub fn next_category(&mut self) {
+        self.category = self.category.next();
+        self.keep_selection_visible();
+    }
+
+    /// Move the highlight onto the first item shown if the one highlighted isn't
+    fn keep_selection_visible(&mut self) {
+        let visible = self.visible(self.category);
+        if !visible.contains(&self.selected) {
+            self.selected = visible.first().copied().unwrap_or(0);
+        }
+    }
+
+    /// Highlight the next item down, wrapping around to the top
+    pub fn select_next(&mut self) {
+        let visible = self.visible(self.category);
+        if let Some(&next) = visible
+            .iter()
+            .find(|&&i| i > self.selected)
+            .or_else(|| visible.first())
+        {
+            self.selected = next;
+        }
+    }
+
+    /// Highlight the next item up, wrapping around to the bottom
+    pub fn select_prev(&mut self) {
+        let visible = self.visible(self.category);
+        if let Some(&prev) = visible
+            .iter()
+            .rev()
+            .find(|&&i| i < self.selected)
+            .or_else(|| visible.last())
+        {
+            self.selected = prev;
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.contents.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.contents.is_empty()
+    }
+
+    /// Return how many items are being carried, counting every item in a stack
+    pub fn item_count(&self) -> usize {
+        self.contents.iter().map(|(_, count)| count).sum()
+    }
+
+    /// Return how much everything being carried weighs
+    pub fn total_weight(&self) -> u32 {
+        self.contents
+            .iter()
+            .map(|(item, count)| item.weight() * *count as u32)
+            .sum()
+    }
+
+    /// Return whether `item` can be added without going over capacity
+    pub fn can_carry(&self, item: &GameCell) -> bool {
+        self.total_weight() + item.weight() <= self.capacity
+    }
+
+    /// Add an item, stacking it onto an existing one with the same name, kind and lock.
+    /// Items that would go over capacity are turned away.
+    pub fn take(&mut self, item: GameCell) -> Result<(), String> {
+        if !self.can_carry(&item) {
+            return Err(String::from("You are carrying too much."));
+        }
+        match self.contents.iter_mut().find(|(gc, _)| {
+            gc.name() == item.name() && gc.kind() == item.kind() && gc.lock() == item.lock()
+        }) {
+            Some((_, count)) => *count += 1,
+            None => self.contents.push((item, 1)),
+        }
+        Ok(())
+    }
+
+    /// Take one item off the stack at `index`, unequipping it if it was the last one
+    pub fn remove(&mut self, index: usize) -> Option<GameCell> {
+        let (item, count) = self.contents.get_mut(index)?;
+        if *count > 1 {
+            *count -= 1;
+            return Some(item.clone());
+        }
+
+        let (item, _) = self.contents.remove(index);
+        self.weapon = reindex(self.weapon, index);
+        self.armor = reindex(self.armor, index);
+        self.off_hand = reindex(self.off_hand, index);
+        if self.selected > 0 && self.selected >= index {
+            self.selected -= 1;
+        }
+        self.keep_selection_visible();
+        Some(item)
+    }
+
+    /// Rename every item of `kind` drawn in `color`
+    pub fn rename(&mut self, kind: CellKind, color: RGB, name: &str) {
+        for (item, _) in self.contents.iter_mut() {
+            if item.kind() == kind && item.color() == color {
+                item.set_name(name);
+            }
+        }
+    }
+
+    pub fn get(&self, index: usize) -> Option<&GameCell> {
+        self.contents.get(index).map(|(gc, _)| gc)
+    }
+
+    /// Return how many items are in the stack at `index`
+    pub fn count(&self, index: usize) -> usize {
+        self.contents.get(index).map_or(0, |(_, count)| *count)
+    }
+
+    /// Put the item at `index` in the slot its kind belongs to,
+    /// or return a message explaining why it can't be equipped.
+    ///
+    /// Wielding a two-handed weapon takes the off-hand out of its slot,
+    /// and nothing goes in the off-hand while a two-handed weapon is wielded.
+    pub fn equip(&mut self, index: usize) -> Result<EquipSlot, String> {
+        let item = match self.get(index) {
+            Some(item) => item,
+            None => return Err(String::from("You don't have that item.")),
+        };
+        match item.kind().equip_slot() {
+            Some(EquipSlot::Weapon) => {
+                if item.hands() > 1 {
+                    self.off_hand = None;
+                }
+                self.weapon = Some(index);
+                Ok(EquipSlot::Weapon)
+            }
+            Some(EquipSlot::Armor) => {
+                self.armor = Some(index);
+                Ok(EquipSlot::Armor)
+            }
+            Some(EquipSlot::OffHand) => match self.equipped(EquipSlot::Weapon) {
+                Some(weapon) if weapon.hands() > 1 => {
+                    Err(format!("You need both hands for the {}.", weapon.name()))
+                }
+                _ => {
+                    self.off_hand = Some(index);
+                    Ok(EquipSlot::OffHand)
+                }
+            },
+            None => Err(format!("You can't equip the {}.", item.name())),
+        }
+    }
+
+    /// Empty `slot`, returning the item that was in it
+    pub fn unequip(&mut self, slot: EquipSlot) -> Option<&GameCell> {
+        let index = match slot {
+            EquipSlot::Weapon => self.weapon.take(),
+            EquipSlot::Armor => self.armor.take(),
+            EquipSlot::OffHand => self.off_hand.take(),
+        }?;
+        self.get(index)
+    }
+
+    pub fn equipped(&self, slot: EquipSlot) -> Option<&GameCell> {
+        match slot {
+            EquipSlot::Weapon => self.weapon,
+            EquipSlot::Armor => self.armor,
+            EquipSlot::OffHand => self.off_hand,
+        }
+        .and_then(|i| self.get(i))
+    }
+
+    /// Return the damage of the wielded weapon, if any
+    pub fn weapon_damage(&self) -> Option<Dice> {
+        self.equipped(EquipSlot::Weapon)
+            .and_then(|weapon| weapon.damage())
+    }
+
+    /// Return the effect the wielded weapon leaves on what it hits, and for how long
+    pub fn weapon_on_hit_effect(&self) -> Option<(Effect, u32)> {
+        self.equipped(EquipSlot::Weapon)
+            .and_then(|weapon| weapon.on_hit_effect())
+    }
+
+    /// Return the total defense of everything equipped
+    pub fn defense(&self) -> i32 {
+        [EquipSlot::Weapon, EquipSlot::Armor, EquipSlot::OffHand]
+            .iter()
+            .filter_map(|&slot| self.equipped(slot))
+            .map(|item| item.defense())
+            .sum()
+    }
+
+    /// Wear down whatever is equipped in `slot`.
+    /// One that breaks is used up and its name is returned. The next one in its stack
+    /// takes its place in the slot, and the slot is emptied once the stack runs out.
+    pub fn wear(&mut self, slot: EquipSlot) -> Option<String> {
+        let index = match slot {
+            EquipSlot::Weapon => self.weapon,
+            EquipSlot::Armor => self.armor,
+            EquipSlot::OffHand => self.off_hand,
+        }?;
+        let (item, count) = &mut self.contents[index];
+        if !item.wear() {
+            return None;
+        }
+
+        let name = item.name();
+        if *count > 1 {
+            *count -= 1;
+            item.mend();
+        } else {
+            self.remove(index);
+        }
+        Some(name)
+    }
+
+    /// Return the index of the key that opens the lock with the id `lock`, if carried
+    pub fn key_for(&self, lock: u32) -> Option<usize> {
+        self.contents
+            .iter()
+            .position(|(item, _)| item.kind() == CellKind::Key && item.lock() == Some(lock))
+    }
+
+    /// Return how much further a lit light source being carried lets the player see
+    pub fn light(&self) -> i32 {
+        self.contents
+            .iter()
+            .map(|(item, _)| item.light())
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Burn a turn of fuel from the first lit light source being carried.
+    /// One that runs out is used up, and its name is returned.
+    pub fn burn_light(&mut self) -> Option<String> {
+        let index = self
+            .contents
+            .iter()
+            .position(|(item, _)| item.light() > 0)?;
+        let (item, count) = &mut self.contents[index];
+        if !item.burn() {
+            return None;
+        }
+
+        let name = item.name();
+        if *count > 1 {
+            *count -= 1;
+            item.refuel();
+        } else {
+            self.remove(index);
+        }
+        Some(name)
+    }
+
+    /// Return the slot the item at `index` is equipped in, if any
+    pub fn slot_of(&self, index: usize) -> Option<EquipSlot> {
+        if self.weapon == Some(index) {
+            Some(EquipSlot::Weapon)
+        } else if self.armor == Some(index) {
+            Some(EquipSlot::Armor)
+        } else if self.off_hand == Some(index) {
+            Some(EquipSlot::OffHand)
+        } else {
+            None
+        }
+    }
+}
+
+/// Return where an equipped index ends up after the entry at `removed` is taken out
+fn reindex(slot: Option<usize>, removed: usize) -> Option<usize> {
+    match slot {
+        Some(i) if i == removed => None,
+        Some(i) if i > removed => Some(i - 1),
+        _ => slot,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::types::CellAccess;
+
+    fn item(kind: CellKind, name: &str) -> GameCell {
+        GameCell::new(
+            Point::new(0, 0),
+            kind,
+            name,
+            RGB::named(GRAY),
+            CellAccess::Takeable,
+        )
+    }
+
+    #[test]
+    fn wielding_a_two_hander_frees_the_off_hand() {
+        let mut inventory = Inventory::new();
+        inventory.take(item(CellKind::Shield, "shield")).unwrap();
+        inventory
+            .take(item(CellKind::EdgedWeapon, "greatsword").with_hands(2))
+            .unwrap();
+
+        assert_eq!(inventory.equip(0), Ok(EquipSlot::OffHand));
+        assert_eq!(inventory.equip(1), Ok(EquipSlot::Weapon));
+        assert!(inventory.equipped(EquipSlot::OffHand).is_none());
+        assert_eq!(
+            inventory.equip(0),
+            Err(String::from("You need both hands for the greatsword."))
+        );
+    }
+
+    #[test]
+    fn the_next_in_a_stack_replaces_a_broken_item() {
+        let mut inventory = Inventory::new();
+        let dagger = item(CellKind::EdgedWeapon, "dagger").with_durability(1);
+        inventory.take(dagger.clone()).unwrap();
+        inventory.take(dagger).unwrap();
+        inventory.equip(0).unwrap();
+
+        assert_eq!(
+            inventory.wear(EquipSlot::Weapon),
+            Some(String::from("dagger"))
+        );
+        assert_eq!(inventory.count(0), 1);
+        let next = inventory.equipped(EquipSlot::Weapon).unwrap();
+        assert_eq!(next.durability(), Some((1, 1)));
+
+        assert_eq!(
+            inventory.wear(EquipSlot::Weapon),
+            Some(String::from("dagger"))
+        );
+        assert!(inventory.is_empty());
+        assert!(inventory.equipped(EquipSlot::Weapon).is_none());
+    }
+
+    #[test]
+    fn turns_away_what_would_go_over_capacity() {
+        let mut inventory = Inventory::new();
+        let plate = item(CellKind::HardArmor, "plate armor").with_weight(30);
+        let mail = |weight| item(CellKind::HardArmor, "chain mail").with_weight(weight);
+
+        assert_eq!(inventory.take(plate.clone()), Ok(()));
+        assert_eq!(
+            inventory.take(plate),
+            Err(String::from("You are carrying too much."))
+        );
+        assert_eq!(inventory.total_weight(), 30);
+        assert!(inventory.can_carry(&mail(20)));
+        assert!(!inventory.can_carry(&mail(21)));
+
+        assert_eq!(inventory.take(mail(20)), Ok(()));
+        assert_eq!(inventory.total_weight(), 50);
+        assert_eq!(inventory.item_count(), 2);
+    }
+
+    #[test]
+    fn the_same_item_taken_twice_stacks() {
+        let mut inventory = Inventory::new();
+        inventory.take(item(CellKind::Food, "ration")).unwrap();
+        inventory.take(item(CellKind::Food, "ration")).unwrap();
+        inventory.take(item(CellKind::Torch, "torch")).unwrap();
+
+        assert_eq!(inventory.len(), 2);
+        assert_eq!(inventory.count(0), 2);
+        assert_eq!(inventory.item_count(), 3);
     }
 }
@@ -1,7 +1,9 @@
+mod enemy;
 mod gamecell;
 mod inventory;
 mod visible;
 
-pub use gamecell::GameCell;
+pub use enemy::{AiState, EnemyAI};
+pub use gamecell::{GameCell, NORMAL_SPEED};
 pub use inventory::Inventory;
 pub use visible::CellVisibility;
@@ -1,4 +1,11 @@
-#[derive(Clone, Copy, Debug, PartialEq)]
+use serde::{Deserialize, Serialize};
+
+/// What the player knows of a cell.
+///
+/// `Dark` cells have been seen before and are remembered, drawn dimmed,
+/// while `Unvisited` cells aren't drawn at all.
+/// Each level keeps its own cells, so what was explored is kept per level.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
 pub enum CellVisibility {
     Visible,
     Dark,
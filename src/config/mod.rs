@@ -0,0 +1,257 @@
+use std::{fs, io};
+
+use serde::{Deserialize, Serialize, Serializer};
+
+use crate::{
+    compass, floating,
+    types::{CellKind, SIGHT_RADIUS},
+};
+
+/// Narrowest field of view the settings screen goes down to
+const MIN_FOV_RADIUS: i32 = 2;
+/// Widest field of view the settings screen goes up to
+const MAX_FOV_RADIUS: i32 = 12;
+/// Turns a second the settings screen steps through for real-time mode, 0 being off
+const REALTIME_STEPS: [f32; 5] = [0.0, 1.0, 2.0, 4.0, 8.0];
+
+/// Game options that are kept between runs.
+///
+/// Anything left out of the file keeps its default.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// Whether walking onto items picks them up,
+    /// rather than leaving them lying until the pick up key is pressed
+    pub auto_pickup: bool,
+    /// Whether to ring the terminal bell on damage, pickups and death
+    pub bell: bool,
+    /// Turns a second the world takes on its own, or 0 to wait for the player
+    pub realtime: f32,
+    /// How many tiles away the player can see in the dark
+    pub fov_radius: i32,
+    /// How many frames damage numbers stay up for, or 0 for none
+    pub float_lifetime: u32,
+    /// Where damage numbers show, from the tile that was hit
+    pub float_offset: (i32, i32),
+    /// Whether enemies get a free attack on a player stepping away from them
+    pub opportunity_attacks: bool,
+    /// Kinds of feature pointed out at the edge of the map once found, while off screen,
+    /// written by the names they go by in the theme file, like `"stairs_down"`
+    #[serde(serialize_with = "kind_names", skip_deserializing)]
+    pub compass: Vec<CellKind>,
+}
+
+fn kind_names<S: Serializer>(kinds: &[CellKind], serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_seq(kinds.iter().map(|kind| kind.name()))
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            auto_pickup: true,
+            bell: false,
+            realtime: 0.0,
+            fov_radius: SIGHT_RADIUS,
+            float_lifetime: floating::LIFETIME,
+            float_offset: floating::OFFSET,
+            opportunity_attacks: false,
+            compass: compass::TRACKED.to_vec(),
+        }
+    }
+}
+
+impl Config {
+    /// Load the options from the TOML file at `path`, falling back to the defaults.
+    /// Problems with the file are returned as warnings rather than failing.
+    pub fn load(path: &str) -> (Self, Vec<String>) {
+        match fs::read_to_string(path) {
+            Ok(contents) => Self::parse(&contents, path),
+            Err(_) => (Self::default(), Vec::new()),
+        }
+    }
+
+    /// Read the options from `contents`, the TOML file at `path`.
+    /// A compass feature with a name no kind of cell goes by is skipped with a warning,
+    /// keeping the rest of the file.
+    fn parse(contents: &str, path: &str) -> (Self, Vec<String>) {
+        let mut warnings = Vec::new();
+        let mut value = match toml::from_str::<toml::Value>(contents) {
+            Ok(value) => value,
+            Err(e) => {
+                warnings.push(format!("Could not read {}: {}", path, e));
+                return (Self::default(), warnings);
+            }
+        };
+        let compass = value
+            .as_table_mut()
+            .and_then(|table| table.remove("compass"));
+        let mut config = match value.try_into::<Self>() {
+            Ok(config) => config,
+            Err(e) => {
+                warnings.push(format!("Could not read {}: {}", path, e));
+                return (Self::default(), warnings);
+            }
+        };
+        config.fov_radius = config.fov_radius.max(MIN_FOV_RADIUS).min(MAX_FOV_RADIUS);
+
+        match compass.as_ref().map(toml::Value::as_array) {
+            Some(Some(names)) => {
+                config.compass = names
+                    .iter()
+                    .filter_map(|name| {
+                        let kind = name
+                            .as_str()
+                            .and_then(CellKind::from_name)
+                            // Written before the compass went by the theme's names
+                            .or_else(|| name.clone().try_into::<CellKind>().ok());
+                        if kind.is_none() {
+                            warnings.push(format!("Unknown compass feature {} in {}.", name, path));
+                        }
+                        kind
+                    })
+                    .collect();
+            }
+            Some(None) => warnings.push(format!(
+                "The compass in {} should be a list of features.",
+                path
+            )),
+            None => (),
+        }
+        (config, warnings)
+    }
+
+    /// Write the options to the TOML file at `path`
+    pub fn save(&self, path: &str) -> io::Result<()> {
+        let contents =
+            toml::to_string(self).map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+        fs::write(path, contents)
+    }
+}
+
+/// A row on the settings screen
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Setting {
+    AutoPickup,
+    Bell,
+    Realtime,
+    FovRadius,
+    OpportunityAttacks,
+}
+
+impl Setting {
+    pub const ALL: [Setting; 5] = [
+        Setting::AutoPickup,
+        Setting::Bell,
+        Setting::Realtime,
+        Setting::FovRadius,
+        Setting::OpportunityAttacks,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Setting::AutoPickup => "Auto pickup",
+            Setting::Bell => "Bell",
+            Setting::Realtime => "Real-time turns a second",
+            Setting::FovRadius => "Field of view radius",
+            Setting::OpportunityAttacks => "Attacks on fleeing",
+        }
+    }
+
+    /// Return how the setting stands in `config`, as shown on the settings screen
+    pub fn value(self, config: &Config) -> String {
+        let on_off = |on: bool| String::from(if on { "on" } else { "off" });
+        match self {
+            Setting::AutoPickup => on_off(config.auto_pickup),
+            Setting::Bell => on_off(config.bell),
+            Setting::Realtime if config.realtime > 0.0 => format!("{}", config.realtime),
+            Setting::Realtime => String::from("off"),
+            Setting::FovRadius => config.fov_radius.to_string(),
+            Setting::OpportunityAttacks => on_off(config.opportunity_attacks),
+        }
+    }
+
+    /// Set the setting in `to` to how it stands in `from`
+    pub fn copy(self, from: &Config, to: &mut Config) {
+        match self {
+            Setting::AutoPickup => to.auto_pickup = from.auto_pickup,
+            Setting::Bell => to.bell = from.bell,
+            Setting::Realtime => to.realtime = from.realtime,
+            Setting::FovRadius => to.fov_radius = from.fov_radius,
+            Setting::OpportunityAttacks => to.opportunity_attacks = from.opportunity_attacks,
+        }
+    }
+
+    /// Flip the setting in `config`, or step it up or down if it's a number
+    pub fn change(self, config: &mut Config, up: bool) {
+        match self {
+            Setting::AutoPickup => config.auto_pickup = !config.auto_pickup,
+            Setting::Bell => config.bell = !config.bell,
+            Setting::OpportunityAttacks => config.opportunity_attacks = !config.opportunity_attacks,
+            Setting::Realtime => {
+                let current = REALTIME_STEPS
+                    .iter()
+                    .position(|&step| step >= config.realtime)
+                    .unwrap_or(0);
+                let next = if up {
+                    (current + 1) % REALTIME_STEPS.len()
+                } else {
+                    (current + REALTIME_STEPS.len() - 1) % REALTIME_STEPS.len()
+                };
+                config.realtime = REALTIME_STEPS[next];
+            }
+            Setting::FovRadius => {
+                let step = if up { 1 } else { -1 };
+                config.fov_radius = (config.fov_radius + step)
+                    .max(MIN_FOV_RADIUS)
+                    .min(MAX_FOV_RADIUS);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_compass_features_by_their_theme_names() {
+        let (config, warnings) = Config::parse(
+            "bell = true\ncompass = [\"stairs_down\", \"chest\"]",
+            "config.toml",
+        );
+        assert!(warnings.is_empty(), "{:?}", warnings);
+        assert!(config.bell);
+        assert_eq!(config.compass, vec![CellKind::StairsDown, CellKind::Chest]);
+    }
+
+    #[test]
+    fn skips_unknown_compass_features_and_keeps_the_rest() {
+        let (config, warnings) = Config::parse(
+            "bell = true\ncompass = [\"stairs_down\", \"dragon\", 3]",
+            "config.toml",
+        );
+        assert!(config.bell);
+        assert_eq!(config.compass, vec![CellKind::StairsDown]);
+        assert_eq!(
+            warnings,
+            vec![
+                "Unknown compass feature \"dragon\" in config.toml.",
+                "Unknown compass feature 3 in config.toml.",
+            ]
+        );
+    }
+
+    #[test]
+    fn saved_compass_features_read_back() {
+        let config = Config {
+            compass: vec![CellKind::Shopkeeper, CellKind::StairsUp],
+            ..Config::default()
+        };
+        let contents = toml::to_string(&config).unwrap();
+        assert!(contents.contains("\"shopkeeper\""));
+        assert_eq!(
+            Config::parse(&contents, "config.toml"),
+            (config, Vec::new())
+        );
+    }
+}
@@ -0,0 +1,84 @@
+use std::{collections::HashSet, io};
+
+use bracket_lib::prelude::*;
+
+use legion::*;
+
+use crate::{components::GameCell, theme::Theme};
+
+/// Draw every cell in `world` as text, a line per row, with the player as `@` at `player`.
+///
+/// Each tile shows what the game would draw on top there, using the symbols from `theme`,
+/// and nothing is hidden, not even traps or tiles the player hasn't seen.
+pub fn ascii(world: &World, theme: &Theme, player: Point) -> String {
+    let mut query = <Read<GameCell>>::query();
+    let mut cells = query.iter(world).collect::<Vec<_>>();
+    cells.sort_by_key(|cell| cell.kind().render_layer());
+    let walls = cells
+        .iter()
+        .filter(|cell| cell.kind().joins_walls())
+        .flat_map(|cell| cell.tiles())
+        .collect::<HashSet<_>>();
+
+    let tiles = cells
+        .iter()
+        .flat_map(|cell| cell.tiles())
+        .chain(Some(player))
+        .collect::<Vec<_>>();
+    let (x1, y1) = (
+        tiles.iter().map(|tile| tile.x).min().unwrap_or(0),
+        tiles.iter().map(|tile| tile.y).min().unwrap_or(0),
+    );
+    let (x2, y2) = (
+        tiles.iter().map(|tile| tile.x).max().unwrap_or(0),
+        tiles.iter().map(|tile| tile.y).max().unwrap_or(0),
+    );
+
+    let width = (x2 - x1 + 1) as usize;
+    let mut rows = vec![vec![' '; width]; (y2 - y1 + 1) as usize];
+    let mut put = |tile: Point, symbol: char| {
+        rows[(tile.y - y1) as usize][(tile.x - x1) as usize] = symbol;
+    };
+    for cell in cells {
+        for tile in cell.tiles() {
+            let symbol = match cell.kind() {
+                kind if kind.looks_like_wall() => theme.wall_symbol(tile, |p| walls.contains(&p)),
+                kind => theme.symbol(kind),
+            };
+            put(tile, symbol);
+        }
+    }
+    put(player, '@');
+
+    let mut text = String::with_capacity(rows.len() * (width + 1));
+    for row in rows {
+        text.push_str(row.iter().collect::<String>().trim_end());
+        text.push('\n');
+    }
+    text
+}
+
+/// Draw every cell in `world` as an image at `path`.
+/// This is only a placeholder for now and always fails.
+#[cfg(feature = "png")]
+pub fn png(_world: &World, _theme: &Theme, _player: Point, path: &str) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Other,
+        format!(
+            "Could not write {}: image export is not supported yet",
+            path
+        ),
+    ))
+}
+
+/// Stands in for the image export when built without the `png` feature
+#[cfg(not(feature = "png"))]
+pub fn png(_world: &World, _theme: &Theme, _player: Point, path: &str) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Other,
+        format!(
+            "Could not write {}: image export needs the png feature",
+            path
+        ),
+    ))
+}
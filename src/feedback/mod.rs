@@ -0,0 +1,11 @@
+use std::io::{self, Write};
+
+/// Ring the terminal bell.
+///
+/// The game is drawn in its own window rather than the terminal it was started from,
+/// so the bell byte on stdout never ends up in the middle of a frame.
+pub fn beep() {
+    let mut stdout = io::stdout();
+    let _ = stdout.write_all(b"\x07");
+    let _ = stdout.flush();
+}
@@ -0,0 +1,82 @@
+use bracket_lib::prelude::*;
+
+/// How many frames floating text stays up for by default
+pub const LIFETIME: u32 = 30;
+/// Where floating text shows by default, from the tile it's about
+pub const OFFSET: (i32, i32) = (0, -1);
+
+/// A bit of text hanging over a tile for a few frames
+struct FloatingText {
+    point: Point,
+    text: String,
+    color: RGB,
+    frames_left: u32,
+}
+
+/// Short-lived text drawn over the map, like the damage dealt to whatever was hit.
+///
+/// Each piece is drawn `offset` from the tile it's about, in world coordinates,
+/// and is dropped once it has been drawn for `lifetime` frames.
+pub struct FloatingTexts {
+    texts: Vec<FloatingText>,
+    lifetime: u32,
+    offset: (i32, i32),
+}
+
+impl Default for FloatingTexts {
+    fn default() -> Self {
+        Self {
+            texts: Vec::new(),
+            lifetime: LIFETIME,
+            offset: OFFSET,
+        }
+    }
+}
+
+impl FloatingTexts {
+    /// Keep each piece of text up for `frames` frames
+    pub fn set_lifetime(&mut self, frames: u32) {
+        self.lifetime = frames;
+    }
+
+    /// Draw each piece of text `offset` from the tile it's about
+    pub fn set_offset(&mut self, offset: (i32, i32)) {
+        self.offset = offset;
+    }
+
+    /// Float `text` over the tile at `point`
+    pub fn add(&mut self, point: Point, text: String, color: RGB) {
+        if self.lifetime > 0 {
+            self.texts.push(FloatingText {
+                point,
+                text,
+                color,
+                frames_left: self.lifetime,
+            });
+        }
+    }
+
+    /// Drop every piece of text, as when the player leaves the level
+    pub fn clear(&mut self) {
+        self.texts.clear();
+    }
+
+    /// Draw every piece of text whose tile is in `view`, shifted by the render `offset`,
+    /// and count a frame off each
+    pub fn print(&mut self, ctx: &mut BTerm, view: Rect, offset: (i32, i32)) {
+        for text in &mut self.texts {
+            let at = Point::new(text.point.x + self.offset.0, text.point.y + self.offset.1);
+            if view.point_in_rect(at) {
+                ctx.print_color(
+                    at.x + offset.0,
+                    at.y + offset.1,
+                    text.color,
+                    RGB::new(),
+                    &text.text,
+                );
+            }
+            text.frames_left -= 1;
+        }
+        self.texts.retain(|text| text.frames_left > 0);
+    }
+}
@@ -0,0 +1,162 @@
+use std::collections::HashSet;
+
+use bracket_lib::prelude::*;
+
+/// A slope of `num / den` with a positive denominator
+#[derive(Clone, Copy, Debug)]
+struct Slope {
+    num: i32,
+    den: i32,
+}
+
+impl Slope {
+    fn new(num: i32, den: i32) -> Self {
+        Self { num, den }
+    }
+
+    /// Slope through the left edge of the tile at `col` in the row at `depth`
+    fn at(depth: i32, col: i32) -> Self {
+        Self::new(2 * col - 1, 2 * depth)
+    }
+
+    /// `depth * slope` rounded to the nearest column, ties rounding up
+    fn round_up(self, depth: i32) -> i32 {
+        (2 * depth * self.num + self.den).div_euclid(2 * self.den)
+    }
+
+    /// `depth * slope` rounded to the nearest column, ties rounding down
+    fn round_down(self, depth: i32) -> i32 {
+        -(self.den - 2 * depth * self.num).div_euclid(2 * self.den)
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+enum Quadrant {
+    North,
+    South,
+    East,
+    West,
+}
+
+impl Quadrant {
+    fn transform(self, origin: Point, depth: i32, col: i32) -> Point {
+        match self {
+            Quadrant::North => Point::new(origin.x + col, origin.y - depth),
+            Quadrant::South => Point::new(origin.x + col, origin.y + depth),
+            Quadrant::East => Point::new(origin.x + depth, origin.y + col),
+            Quadrant::West => Point::new(origin.x - depth, origin.y + col),
+        }
+    }
+}
+
+struct Fov<'a, F: Fn(Point) -> bool> {
+    origin: Point,
+    radius: i32,
+    is_opaque: &'a F,
+    visible: HashSet<Point>,
+}
+
+impl<'a, F: Fn(Point) -> bool> Fov<'a, F> {
+    fn reveal(&mut self, point: Point) {
+        let dx = point.x - self.origin.x;
+        let dy = point.y - self.origin.y;
+        if dx * dx + dy * dy <= self.radius * self.radius + self.radius {
+            self.visible.insert(point);
+        }
+    }
+
+    fn scan(&mut self, quadrant: Quadrant, depth: i32, mut start: Slope, end: Slope) {
+        if depth > self.radius {
+            return;
+        }
+
+        let mut prev_opaque = None;
+        for col in start.round_up(depth)..=end.round_down(depth) {
+            let point = quadrant.transform(self.origin, depth, col);
+            let opaque = (self.is_opaque)(point);
+
+            let symmetric =
+                col * start.den >= depth * start.num && col * end.den <= depth * end.num;
+            if opaque || symmetric {
+                self.reveal(point);
+            }
+            if prev_opaque == Some(true) && !opaque {
+                start = Slope::at(depth, col);
+            }
+            if prev_opaque == Some(false) && opaque {
+                self.scan(quadrant, depth + 1, start, Slope::at(depth, col));
+            }
+            prev_opaque = Some(opaque);
+        }
+        if prev_opaque == Some(false) {
+            self.scan(quadrant, depth + 1, start, end);
+        }
+    }
+}
+
+/// Return every point visible from `origin` within `radius` tiles,
+/// using symmetric shadowcasting.
+///
+/// Opaque tiles are visible themselves but hide whatever is behind them.
+/// Points are in the same world coordinates as `GameCell`s.
+pub fn field_of_view<F: Fn(Point) -> bool>(
+    origin: Point,
+    radius: i32,
+    is_opaque: F,
+) -> HashSet<Point> {
+    let mut fov = Fov {
+        origin,
+        radius,
+        is_opaque: &is_opaque,
+        visible: HashSet::new(),
+    };
+    fov.visible.insert(origin);
+
+    for &quadrant in &[
+        Quadrant::North,
+        Quadrant::South,
+        Quadrant::East,
+        Quadrant::West,
+    ] {
+        fov.scan(quadrant, 1, Slope::new(-1, 1), Slope::new(1, 1));
+    }
+
+    fov.visible
+}
+
+/// Every point lit by light sources lying around a level.
+///
+/// Lighting only changes when a light source or an opaque tile does,
+/// so it's kept between turns and only recast when one of those moves.
+#[derive(Clone, Debug, Default)]
+pub struct LightMap {
+    sources: Vec<(Point, i32)>,
+    opaque: usize,
+    lit: HashSet<Point>,
+}
+
+impl LightMap {
+    /// Light the level from each of `sources` and how far it shines,
+    /// unless nothing has changed since last time
+    pub fn update(&mut self, sources: Vec<(Point, i32)>, opaque: &HashSet<Point>) {
+        if sources == self.sources && opaque.len() == self.opaque && !self.lit.is_empty() {
+            return;
+        }
+
+        self.lit.clear();
+        for &(origin, radius) in &sources {
+            self.lit
+                .extend(field_of_view(origin, radius, |p| opaque.contains(&p)));
+        }
+        self.sources = sources;
+        self.opaque = opaque.len();
+    }
+
+    pub fn is_lit(&self, point: Point) -> bool {
+        self.lit.contains(&point)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lit.is_empty()
+    }
+}
@@ -0,0 +1,368 @@
+use std::{collections::HashMap, fs};
+
+use bracket_lib::prelude::*;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Action {
+    MoveUp,
+    MoveDown,
+    MoveLeft,
+    MoveRight,
+    MoveUpLeft,
+    MoveUpRight,
+    MoveDownLeft,
+    MoveDownRight,
+    CloseDoor,
+    Descend,
+    Ascend,
+    Inventory,
+    Minimap,
+    Equip,
+    Drop,
+    DropAll,
+    PickUp,
+    PickUpAll,
+    Eat,
+    Quaff,
+    Throw,
+    Look,
+    Travel,
+    TravelToStairs,
+    Explore,
+    Fire,
+    Swipe,
+    Rest,
+    Search,
+    Wait,
+    Run,
+    Sneak,
+    Undo,
+    ScrollUp,
+    ScrollDown,
+    Save,
+    Help,
+    Settings,
+    Bestiary,
+    Quit,
+}
+
+impl Action {
+    pub const ALL: [Action; 40] = [
+        Action::MoveUp,
+        Action::MoveDown,
+        Action::MoveLeft,
+        Action::MoveRight,
+        Action::MoveUpLeft,
+        Action::MoveUpRight,
+        Action::MoveDownLeft,
+        Action::MoveDownRight,
+        Action::CloseDoor,
+        Action::Descend,
+        Action::Ascend,
+        Action::Inventory,
+        Action::Minimap,
+        Action::Equip,
+        Action::Drop,
+        Action::DropAll,
+        Action::PickUp,
+        Action::PickUpAll,
+        Action::Eat,
+        Action::Quaff,
+        Action::Throw,
+        Action::Look,
+        Action::Travel,
+        Action::TravelToStairs,
+        Action::Explore,
+        Action::Fire,
+        Action::Swipe,
+        Action::Rest,
+        Action::Search,
+        Action::Wait,
+        Action::Run,
+        Action::Sneak,
+        Action::Undo,
+        Action::ScrollUp,
+        Action::ScrollDown,
+        Action::Save,
+        Action::Help,
+        Action::Settings,
+        Action::Bestiary,
+        Action::Quit,
+    ];
+
+    /// Return the name the action goes by in the keybindings file
+    pub fn name(self) -> &'static str {
+        match self {
+            Action::MoveUp => "move_up",
+            Action::MoveDown => "move_down",
+            Action::MoveLeft => "move_left",
+            Action::MoveRight => "move_right",
+            Action::MoveUpLeft => "move_up_left",
+            Action::MoveUpRight => "move_up_right",
+            Action::MoveDownLeft => "move_down_left",
+            Action::MoveDownRight => "move_down_right",
+            Action::CloseDoor => "close_door",
+            Action::Descend => "descend",
+            Action::Ascend => "ascend",
+            Action::Inventory => "inventory",
+            Action::Minimap => "minimap",
+            Action::Equip => "equip",
+            Action::Drop => "drop",
+            Action::DropAll => "drop_all",
+            Action::PickUp => "pick_up",
+            Action::PickUpAll => "pick_up_all",
+            Action::Eat => "eat",
+            Action::Quaff => "quaff",
+            Action::Throw => "throw",
+            Action::Look => "look",
+            Action::Travel => "travel",
+            Action::TravelToStairs => "travel_to_stairs",
+            Action::Explore => "explore",
+            Action::Fire => "fire",
+            Action::Swipe => "swipe",
+            Action::Rest => "rest",
+            Action::Search => "search",
+            Action::Wait => "wait",
+            Action::Run => "run",
+            Action::Sneak => "sneak",
+            Action::Undo => "undo",
+            Action::ScrollUp => "scroll_up",
+            Action::ScrollDown => "scroll_down",
+            Action::Save => "save",
+            Action::Help => "help",
+            Action::Settings => "settings",
+            Action::Bestiary => "bestiary",
+            Action::Quit => "quit",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|action| action.name() == name)
+    }
+
+    /// Return the world offset a movement action steps the player by
+    pub fn direction(self) -> Option<(i32, i32)> {
+        match self {
+            Action::MoveUp => Some((0, -1)),
+            Action::MoveDown => Some((0, 1)),
+            Action::MoveLeft => Some((-1, 0)),
+            Action::MoveRight => Some((1, 0)),
+            Action::MoveUpLeft => Some((-1, -1)),
+            Action::MoveUpRight => Some((1, -1)),
+            Action::MoveDownLeft => Some((-1, 1)),
+            Action::MoveDownRight => Some((1, 1)),
+            _ => None,
+        }
+    }
+
+    fn default_keys(self) -> &'static [VirtualKeyCode] {
+        match self {
+            Action::MoveUp => &[VirtualKeyCode::Up, VirtualKeyCode::K],
+            Action::MoveDown => &[VirtualKeyCode::Down, VirtualKeyCode::J],
+            Action::MoveLeft => &[VirtualKeyCode::Left, VirtualKeyCode::H],
+            Action::MoveRight => &[VirtualKeyCode::Right, VirtualKeyCode::L],
+            Action::MoveUpLeft => &[VirtualKeyCode::Y],
+            Action::MoveUpRight => &[VirtualKeyCode::U],
+            Action::MoveDownLeft => &[VirtualKeyCode::B],
+            Action::MoveDownRight => &[VirtualKeyCode::N],
+            Action::CloseDoor => &[VirtualKeyCode::C],
+            Action::Descend => &[VirtualKeyCode::Period],
+            Action::Ascend => &[VirtualKeyCode::Comma],
+            Action::Inventory => &[VirtualKeyCode::I],
+            Action::Minimap => &[VirtualKeyCode::M],
+            Action::Equip => &[VirtualKeyCode::W],
+            Action::Drop => &[VirtualKeyCode::D],
+            Action::DropAll => &[VirtualKeyCode::Delete],
+            Action::PickUp => &[VirtualKeyCode::P],
+            Action::PickUpAll => &[VirtualKeyCode::Insert],
+            Action::Eat => &[VirtualKeyCode::E],
+            Action::Quaff => &[VirtualKeyCode::Q],
+            Action::Throw => &[VirtualKeyCode::T],
+            Action::Look => &[VirtualKeyCode::X],
+            Action::Travel => &[VirtualKeyCode::G],
+            Action::TravelToStairs => &[VirtualKeyCode::Return],
+            Action::Explore => &[VirtualKeyCode::O],
+            Action::Fire => &[VirtualKeyCode::F],
+            Action::Swipe => &[VirtualKeyCode::A],
+            Action::Rest => &[VirtualKeyCode::R],
+            // S already saves
+            Action::Search => &[VirtualKeyCode::Space],
+            Action::Wait => &[VirtualKeyCode::Numpad5],
+            Action::Run => &[VirtualKeyCode::Z],
+            Action::Sneak => &[VirtualKeyCode::V],
+            Action::Undo => &[VirtualKeyCode::Back],
+            Action::ScrollUp => &[VirtualKeyCode::PageUp],
+            Action::ScrollDown => &[VirtualKeyCode::PageDown],
+            Action::Save => &[VirtualKeyCode::S],
+            Action::Help => &[VirtualKeyCode::Slash],
+            Action::Settings => &[VirtualKeyCode::Home],
+            // B already moves down and to the left
+            Action::Bestiary => &[VirtualKeyCode::Tab],
+            Action::Quit => &[VirtualKeyCode::End],
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct KeyBindings {
+    keys: HashMap<VirtualKeyCode, Action>,
+}
+
+impl Default for KeyBindings {
+    fn default() -> Self {
+        let mut keys = HashMap::new();
+        for &action in Action::ALL.iter() {
+            for &key in action.default_keys() {
+                keys.insert(key, action);
+            }
+        }
+        Self { keys }
+    }
+}
+
+impl KeyBindings {
+    /// Load bindings from the TOML file at `path`, falling back to the defaults.
+    ///
+    /// The file maps action names to lists of key names, for example
+    /// `move_up = ["Up", "W"]`. Any action listed replaces all of its default keys.
+    /// Problems with the file are returned as warnings rather than failing.
+    pub fn load(path: &str) -> (Self, Vec<String>) {
+        let mut bindings = Self::default();
+        let mut warnings = Vec::new();
+
+        let contents = match fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(_) => return (bindings, warnings),
+        };
+        let table: HashMap<String, Vec<String>> = match toml::from_str(&contents) {
+            Ok(table) => table,
+            Err(e) => {
+                warnings.push(format!("Could not read {}: {}", path, e));
+                return (bindings, warnings);
+            }
+        };
+
+        for (name, key_names) in table {
+            let action = match Action::from_name(&name) {
+                Some(action) => action,
+                None => {
+                    warnings.push(format!("Unknown action \"{}\" in {}.", name, path));
+                    continue;
+                }
+            };
+            bindings.keys.retain(|_, bound| *bound != action);
+            for key_name in key_names {
+                match key_from_name(&key_name) {
+                    Some(key) => {
+                        bindings.keys.insert(key, action);
+                    }
+                    None => warnings.push(format!("Unknown key \"{}\" in {}.", key_name, path)),
+                }
+            }
+        }
+
+        (bindings, warnings)
+    }
+
+    pub fn action(&self, key: VirtualKeyCode) -> Option<Action> {
+        self.keys.get(&key).copied()
+    }
+
+    /// Return the names of every key bound to `action`, sorted
+    pub fn keys(&self, action: Action) -> Vec<&'static str> {
+        let mut names: Vec<&'static str> = self
+            .keys
+            .iter()
+            .filter(|(_, &bound)| bound == action)
+            .filter_map(|(&key, _)| key_name(key))
+            .collect();
+        names.sort_unstable();
+        names
+    }
+}
+
+/// Names keys go by in the keybindings file
+const KEY_NAMES: [(&str, VirtualKeyCode); 64] = [
+    ("A", VirtualKeyCode::A),
+    ("B", VirtualKeyCode::B),
+    ("C", VirtualKeyCode::C),
+    ("D", VirtualKeyCode::D),
+    ("E", VirtualKeyCode::E),
+    ("F", VirtualKeyCode::F),
+    ("G", VirtualKeyCode::G),
+    ("H", VirtualKeyCode::H),
+    ("I", VirtualKeyCode::I),
+    ("J", VirtualKeyCode::J),
+    ("K", VirtualKeyCode::K),
+    ("L", VirtualKeyCode::L),
+    ("M", VirtualKeyCode::M),
+    ("N", VirtualKeyCode::N),
+    ("O", VirtualKeyCode::O),
+    ("P", VirtualKeyCode::P),
+    ("Q", VirtualKeyCode::Q),
+    ("R", VirtualKeyCode::R),
+    ("S", VirtualKeyCode::S),
+    ("T", VirtualKeyCode::T),
+    ("U", VirtualKeyCode::U),
+    ("V", VirtualKeyCode::V),
+    ("W", VirtualKeyCode::W),
+    ("X", VirtualKeyCode::X),
+    ("Y", VirtualKeyCode::Y),
+    ("Z", VirtualKeyCode::Z),
+    ("0", VirtualKeyCode::Key0),
+    ("1", VirtualKeyCode::Key1),
+    ("2", VirtualKeyCode::Key2),
+    ("3", VirtualKeyCode::Key3),
+    ("4", VirtualKeyCode::Key4),
+    ("5", VirtualKeyCode::Key5),
+    ("6", VirtualKeyCode::Key6),
+    ("7", VirtualKeyCode::Key7),
+    ("8", VirtualKeyCode::Key8),
+    ("9", VirtualKeyCode::Key9),
+    ("Numpad0", VirtualKeyCode::Numpad0),
+    ("Numpad1", VirtualKeyCode::Numpad1),
+    ("Numpad2", VirtualKeyCode::Numpad2),
+    ("Numpad3", VirtualKeyCode::Numpad3),
+    ("Numpad4", VirtualKeyCode::Numpad4),
+    ("Numpad5", VirtualKeyCode::Numpad5),
+    ("Numpad6", VirtualKeyCode::Numpad6),
+    ("Numpad7", VirtualKeyCode::Numpad7),
+    ("Numpad8", VirtualKeyCode::Numpad8),
+    ("Numpad9", VirtualKeyCode::Numpad9),
+    ("Up", VirtualKeyCode::Up),
+    ("Down", VirtualKeyCode::Down),
+    ("Left", VirtualKeyCode::Left),
+    ("Right", VirtualKeyCode::Right),
+    ("Home", VirtualKeyCode::Home),
+    ("End", VirtualKeyCode::End),
+    ("Insert", VirtualKeyCode::Insert),
+    ("Delete", VirtualKeyCode::Delete),
+    ("PageUp", VirtualKeyCode::PageUp),
+    ("PageDown", VirtualKeyCode::PageDown),
+    ("Space", VirtualKeyCode::Space),
+    ("Return", VirtualKeyCode::Return),
+    ("Tab", VirtualKeyCode::Tab),
+    ("Escape", VirtualKeyCode::Escape),
+    ("Back", VirtualKeyCode::Back),
+    ("Period", VirtualKeyCode::Period),
+    ("Comma", VirtualKeyCode::Comma),
+    ("Slash", VirtualKeyCode::Slash),
+];
+
+fn key_from_name(name: &str) -> Option<VirtualKeyCode> {
+    KEY_NAMES
+        .iter()
+        .find(|(key_name, _)| *key_name == name)
+        .map(|&(_, key)| key)
+}
+
+/// Return the name `key` goes by in the keybindings file
+pub fn key_name(key: VirtualKeyCode) -> Option<&'static str> {
+    KEY_NAMES
+        .iter()
+        .find(|(_, named)| *named == key)
+        .map(|&(name, _)| name)
+}
@@ -0,0 +1,92 @@
+use bracket_lib::prelude::*;
+
+/// A panel size given as a share of the window, kept within a range
+#[derive(Clone, Copy, Debug)]
+pub struct Span {
+    pub fraction: f32,
+    pub min: u32,
+    pub max: u32,
+}
+
+impl Span {
+    /// Return the size this span takes up out of `total`
+    fn of(self, total: u32) -> u32 {
+        ((total as f32 * self.fraction) as u32)
+            .max(self.min)
+            .min(self.max)
+            .min(total)
+    }
+}
+
+/// How much of the window each panel gets.
+///
+/// The defaults lay an 80 by 25 window out the way it always has been.
+#[derive(Clone, Copy, Debug)]
+pub struct LayoutConfig {
+    /// Width of the inventory box on the right
+    pub inventory_width: Span,
+    /// Width of the player info column in the bottom right
+    pub info_width: Span,
+    /// Height of the message panel along the bottom
+    pub events_height: Span,
+}
+
+impl Default for LayoutConfig {
+    fn default() -> Self {
+        Self {
+            inventory_width: Span {
+                fraction: 0.25,
+                min: 16,
+                max: 40,
+            },
+            info_width: Span {
+                fraction: 0.0,
+                min: 15,
+                max: 15,
+            },
+            events_height: Span {
+                fraction: 0.0,
+                min: 6,
+                max: 6,
+            },
+        }
+    }
+}
+
+/// Where each panel goes in a window of a particular size
+#[derive(Clone, Copy, Debug)]
+pub struct Layout {
+    /// The part of the window the map is drawn in
+    pub canvas: Rect,
+    pub inventory: Rect,
+    pub info: Rect,
+    pub events: Rect,
+}
+
+impl Layout {
+    pub fn new(config: &LayoutConfig, window_size: (u32, u32)) -> Self {
+        let (w, h) = (window_size.0 as i32, window_size.1 as i32);
+        let inventory_width = config.inventory_width.of(window_size.0) as i32;
+        let info_width = config.info_width.of(window_size.0) as i32;
+        let events_height = config.events_height.of(window_size.1) as i32;
+
+        Self {
+            canvas: Rect::with_exact(0, 0, w, h - events_height),
+            inventory: Rect::with_size(w - inventory_width - 2, 1, inventory_width, h - 3),
+            info: Rect::with_exact(w - info_width, h - events_height - 5, w, h - 1),
+            events: Rect::with_exact(1, h - events_height, w - 1, h - 1),
+        }
+    }
+
+    /// Return the middle of the canvas, where the player is drawn.
+    ///
+    /// The canvas covers columns `x1` up to but not including `x2`, and the same for rows,
+    /// so with an odd size this is the tile with as many on either side,
+    /// and with an even size the second of the two middle tiles.
+    pub fn center(&self) -> Point {
+        Point::new(
+            self.canvas.x1 + self.canvas.width() / 2,
+            self.canvas.y1 + self.canvas.height() / 2,
+        )
+    }
+}
@@ -1,5 +1,24 @@
+mod compass;
 mod components;
+mod config;
+mod export;
+mod feedback;
+mod floating;
+mod fov;
+mod input;
+mod layout;
+mod los;
+mod menu;
+mod path;
+mod persistence;
+mod scent;
+mod spatial;
 mod state;
+mod systems;
+mod theme;
 mod types;
+mod worldgen;
 
+pub use input::Action;
 pub use state::State;
+pub use types::{todays_seed, Loadout};
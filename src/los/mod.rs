@@ -0,0 +1,43 @@
+use bracket_lib::prelude::*;
+
+/// Return every point on the line from `from` to `to`, both ends included,
+/// using Bresenham's line algorithm.
+///
+/// Points are in the same world coordinates as `GameCell`s.
+pub fn line(from: Point, to: Point) -> Vec<Point> {
+    let dx = (to.x - from.x).abs();
+    let dy = -(to.y - from.y).abs();
+    let sx = (to.x - from.x).signum();
+    let sy = (to.y - from.y).signum();
+
+    let mut points = Vec::with_capacity(dx.max(-dy) as usize + 1);
+    let mut point = from;
+    let mut err = dx + dy;
+    loop {
+        points.push(point);
+        if point == to {
+            break;
+        }
+        let e2 = 2 * err;
+        if e2 >= dy {
+            err += dy;
+            point.x += sx;
+        }
+        if e2 <= dx {
+            err += dx;
+            point.y += sy;
+        }
+    }
+
+    points
+}
+
+/// Follow the line from `from` toward `to` and return the first point past `from`
+/// that `is_blocked`, or `to` if nothing is in the way.
+pub fn first_blocked<F: FnMut(Point) -> bool>(from: Point, to: Point, mut is_blocked: F) -> Point {
+    line(from, to)
+        .into_iter()
+        .skip(1)
+        .find(|&point| is_blocked(point))
+        .unwrap_or(to)
+}
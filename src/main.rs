@@ -1,6 +1,8 @@
+use std::env;
+
 use bracket_lib::prelude::*;
 
-use blademaster::State;
+use blademaster::{todays_seed, Loadout, State};
 
 fn main() -> BError {
     let tw = 24;
@@ -8,14 +10,90 @@ fn main() -> BError {
     let w = 1366 / tw;
     let h = 768 / th;
 
+    // `--seed N` starts new games from the menu that play out the same every time,
+    // `--daily` starts from today's seed, the same for everyone playing on the same UTC date,
+    // and can't be given along with `--seed`,
+    // `--log PATH` also writes every event to a file,
+    // `--bell` rings the terminal bell on damage, pickups and death unless `--quiet` is given too,
+    // `--loadout NAME` offers the warrior, mage or rogue kit first on the menu,
+    // `--high-contrast` draws weapons and armor with shapes and colors that are easier to tell apart,
+    // `--opportunity-attacks` lets enemies take a free swing at a player stepping away from them,
+    // `--letters` draws items as a letter for what they are, like `w` for a weapon,
+    // `--realtime TPS` lets the world take TPS turns a second on its own while the player waits,
+    // `--export PATH` writes the first level to a text file, or an image if it ends in `.png`,
+    // and quits without opening a window
+    let mut args = env::args().skip(1);
+    let mut seed = None;
+    let mut daily = false;
+    let mut log = None;
+    let mut bell = false;
+    let mut quiet = false;
+    let mut realtime = None;
+    let mut high_contrast = false;
+    let mut letters = false;
+    let mut opportunity_attacks = false;
+    let mut loadout = Loadout::Wanderer;
+    let mut export = None;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--seed" => seed = args.next().and_then(|s| s.parse().ok()),
+            "--daily" => daily = true,
+            "--log" => log = args.next(),
+            "--bell" => bell = true,
+            "--quiet" => quiet = true,
+            "--high-contrast" => high_contrast = true,
+            "--letters" => letters = true,
+            "--opportunity-attacks" => opportunity_attacks = true,
+            "--loadout" => {
+                if let Some(chosen) = args.next().as_deref().and_then(Loadout::from_name) {
+                    loadout = chosen;
+                }
+            }
+            "--realtime" => realtime = args.next().and_then(|s| s.parse().ok()),
+            "--export" => export = args.next(),
+            _ => (),
+        }
+    }
+
+    let seed = match (seed, daily) {
+        (Some(_), true) => {
+            return Err("--daily picks its own seed, so it can't go with --seed".into())
+        }
+        (seed, false) => seed,
+        (None, true) => Some(todays_seed()),
+    };
+
+    let mut gs = State::new(w - 1, h, seed, log.as_deref());
+    gs.start_with(loadout);
+    // Flags given on the command line win over the config file
+    if bell || quiet {
+        gs.set_bell(bell && !quiet);
+    }
+    if opportunity_attacks {
+        gs.set_opportunity_attacks(true);
+    }
+    gs.set_symbols(high_contrast, letters);
+    if let Some(ticks_per_second) = realtime {
+        gs.set_realtime(ticks_per_second);
+    }
+
+    if let Some(path) = export {
+        gs.export_map(&path)?;
+        println!("Wrote the level from seed {} to {}", gs.seed(), path);
+        return Ok(());
+    }
+
     let ctx = BTermBuilder::simple(w, h)?
         .with_tile_dimensions(tw, th)
         .with_advanced_input(true)
         .with_fps_cap(60.0)
         .with_fullscreen(true)
         .with_title("Blademaster")
-        .build()?;
-    let gs = State::new(w - 1, h);
+        .build()
+        // Most often there's no display to open one on, as under CI or over SSH
+        .map_err(|e| format!("Blademaster could not open its window: {}", e))?;
 
+    // Quitting breaks out of the loop rather than exiting the process,
+    // so the window is torn down normally and a clean quit reports success
     main_loop(ctx, gs)
 }
@@ -0,0 +1,206 @@
+use bracket_lib::prelude::*;
+
+use crate::types::Loadout;
+
+/// Longest seed that can be typed in, which always fits in a `u64`
+const MAX_SEED_DIGITS: usize = 19;
+
+/// What the player settled on at the start menu
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum StartChoice {
+    /// Start a fresh game with `Loadout`, from the chosen seed if there is one
+    NewGame(Option<u64>, Loadout),
+    LoadGame,
+    Quit,
+}
+
+/// An entry on the first page of the start menu
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Entry {
+    NewGame,
+    LoadGame,
+    ChooseSeed,
+    Quit,
+}
+
+impl Entry {
+    fn label(self) -> &'static str {
+        match self {
+            Entry::NewGame => "New Game",
+            Entry::LoadGame => "Load Game",
+            Entry::ChooseSeed => "Choose Seed",
+            Entry::Quit => "Quit",
+        }
+    }
+}
+
+/// Which page of the start menu is showing
+#[derive(Clone, Debug, PartialEq)]
+enum Page {
+    Main,
+    /// Typing in a seed, digit by digit
+    Seed(String),
+    /// Picking a kit for a new game, from the seed typed in if there was one
+    Loadout(Option<u64>),
+}
+
+/// The menu shown before the game starts.
+///
+/// It only keeps track of what's highlighted and what's been typed;
+/// nothing about the game changes until a `StartChoice` comes back from `input`.
+pub struct StartMenu {
+    entries: Vec<Entry>,
+    page: Page,
+    selected: usize,
+    /// The kit highlighted when the kits are first shown
+    loadout: Loadout,
+}
+
+impl StartMenu {
+    /// Make a menu that only offers to load a game when `has_save` is true
+    pub fn new(has_save: bool) -> Self {
+        let mut entries = vec![Entry::NewGame];
+        if has_save {
+            entries.push(Entry::LoadGame);
+        }
+        entries.push(Entry::ChooseSeed);
+        entries.push(Entry::Quit);
+
+        Self {
+            entries,
+            page: Page::Main,
+            selected: 0,
+            loadout: Loadout::Wanderer,
+        }
+    }
+
+    /// Highlight `loadout` when the kits are first shown
+    pub fn preselect(&mut self, loadout: Loadout) {
+        self.loadout = loadout;
+    }
+
+    /// Move the highlight with the arrow keys, and go forward with enter or back with escape.
+    /// Return what the player chose once they've finished choosing.
+    pub fn input(&mut self, key: VirtualKeyCode) -> Option<StartChoice> {
+        let len = self.len();
+        match key {
+            VirtualKeyCode::Up if len > 0 => self.selected = (self.selected + len - 1) % len,
+            VirtualKeyCode::Down if len > 0 => self.selected = (self.selected + 1) % len,
+            VirtualKeyCode::Return | VirtualKeyCode::NumpadEnter => return self.confirm(),
+            VirtualKeyCode::Escape => self.back(),
+            VirtualKeyCode::Back => {
+                if let Page::Seed(digits) = &mut self.page {
+                    digits.pop();
+                }
+            }
+            _ => {
+                if let (Page::Seed(digits), Some(digit)) = (&mut self.page, digit(key)) {
+                    if digits.len() < MAX_SEED_DIGITS {
+                        digits.push(digit);
+                    }
+                }
+            }
+        }
+        None
+    }
+
+    fn confirm(&mut self) -> Option<StartChoice> {
+        match &self.page {
+            Page::Main => match self.entries[self.selected] {
+                Entry::NewGame => self.open(Page::Loadout(None)),
+                Entry::LoadGame => return Some(StartChoice::LoadGame),
+                Entry::ChooseSeed => self.open(Page::Seed(String::new())),
+                Entry::Quit => return Some(StartChoice::Quit),
+            },
+            Page::Seed(digits) => {
+                if let Ok(seed) = digits.parse() {
+                    self.open(Page::Loadout(Some(seed)));
+                }
+            }
+            &Page::Loadout(seed) => {
+                return Some(StartChoice::NewGame(seed, Loadout::ALL[self.selected]));
+            }
+        }
+        None
+    }
+
+    /// Go back to the first page, from wherever the player is
+    fn back(&mut self) {
+        if self.page != Page::Main {
+            self.page = Page::Main;
+            self.selected = 0;
+        }
+    }
+
+    fn open(&mut self, page: Page) {
+        self.selected = match page {
+            Page::Loadout(_) => Loadout::ALL
+                .iter()
+                .position(|&loadout| loadout == self.loadout)
+                .unwrap_or(0),
+            _ => 0,
+        };
+        self.page = page;
+    }
+
+    /// Return how many lines there are to pick from on the current page
+    fn len(&self) -> usize {
+        match self.page {
+            Page::Main => self.entries.len(),
+            Page::Seed(_) => 0,
+            Page::Loadout(_) => Loadout::ALL.len(),
+        }
+    }
+
+    pub fn print(&self, ctx: &mut BTerm, window_size: (u32, u32)) {
+        let top = window_size.1 as i32 / 2 - 4;
+        ctx.print_centered(top, "Blademaster");
+
+        let lines: Vec<String> = match &self.page {
+            Page::Main => self
+                .entries
+                .iter()
+                .map(|entry| entry.label().to_string())
+                .collect(),
+            Page::Seed(digits) => {
+                ctx.print_centered(top + 2, "Type a seed and press enter");
+                ctx.print_centered(top + 4, format!("{}_", digits));
+                return;
+            }
+            Page::Loadout(_) => {
+                ctx.print_centered(top + 2, "Choose your kit");
+                Loadout::ALL
+                    .iter()
+                    .map(|loadout| loadout.name().to_string())
+                    .collect()
+            }
+        };
+
+        for (y, line) in lines.iter().enumerate() {
+            let (fg, text) = if y == self.selected {
+                (RGB::named(YELLOW), format!("> {} <", line))
+            } else {
+                (RGB::named(GRAY), line.clone())
+            };
+            ctx.print_color_centered(top + 4 + y as i32, fg, RGB::new(), text);
+        }
+    }
+}
+
+/// Return the digit typed with `key`, from either the number row or the numpad
+fn digit(key: VirtualKeyCode) -> Option<char> {
+    let n = match key {
+        VirtualKeyCode::Key0 | VirtualKeyCode::Numpad0 => 0,
+        VirtualKeyCode::Key1 | VirtualKeyCode::Numpad1 => 1,
+        VirtualKeyCode::Key2 | VirtualKeyCode::Numpad2 => 2,
+        VirtualKeyCode::Key3 | VirtualKeyCode::Numpad3 => 3,
+        VirtualKeyCode::Key4 | VirtualKeyCode::Numpad4 => 4,
+        VirtualKeyCode::Key5 | VirtualKeyCode::Numpad5 => 5,
+        VirtualKeyCode::Key6 | VirtualKeyCode::Numpad6 => 6,
+        VirtualKeyCode::Key7 | VirtualKeyCode::Numpad7 => 7,
+        VirtualKeyCode::Key8 | VirtualKeyCode::Numpad8 => 8,
+        VirtualKeyCode::Key9 | VirtualKeyCode::Numpad9 => 9,
+        _ => return None,
+    };
+    std::char::from_digit(n, 10)
+}
@@ -0,0 +1,188 @@
+use std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, HashMap, HashSet, VecDeque},
+};
+
+use bracket_lib::prelude::*;
+
+use legion::*;
+
+use crate::{components::GameCell, types::CellAccess};
+
+/// Return the shortest walk from `from` to `to`, one tile per step in any
+/// of the eight directions, using A*.
+///
+/// Only tiles with a cell and no `Impassable` cell can be walked on.
+/// The path leaves out `from` and ends with `to`.
+pub fn pathfind(world: &World, from: Point, to: Point) -> Option<Vec<Point>> {
+    let walkable = walkable(world);
+    let passable = |point: &Point| walkable.contains(point);
+    if !passable(&to) {
+        return None;
+    }
+    if from == to {
+        return Some(Vec::new());
+    }
+
+    let mut came_from: HashMap<Point, Point> = HashMap::new();
+    let mut cost: HashMap<Point, i32> = HashMap::new();
+    let mut open = BinaryHeap::new();
+    cost.insert(from, 0);
+    open.push(Reverse((chebyshev(from, to), 0, from.x, from.y)));
+
+    while let Some(Reverse((_, steps, x, y))) = open.pop() {
+        let current = Point::new(x, y);
+        if current == to {
+            let mut path = vec![current];
+            let mut point = current;
+            while let Some(&prev) = came_from.get(&point) {
+                if prev == from {
+                    break;
+                }
+                path.push(prev);
+                point = prev;
+            }
+            path.reverse();
+            return Some(path);
+        }
+        if cost.get(&current).map_or(false, |&c| steps > c) {
+            continue;
+        }
+
+        for dx in -1..=1 {
+            for dy in -1..=1 {
+                let next = Point::new(x + dx, y + dy);
+                if (dx, dy) == (0, 0) || !passable(&next) {
+                    continue;
+                }
+                let next_steps = steps + 1;
+                if cost.get(&next).map_or(true, |&c| next_steps < c) {
+                    cost.insert(next, next_steps);
+                    came_from.insert(next, current);
+                    open.push(Reverse((
+                        next_steps + chebyshev(next, to),
+                        next_steps,
+                        next.x,
+                        next.y,
+                    )));
+                }
+            }
+        }
+    }
+
+    None
+}
+
+/// Return the shortest walk from `from` to the closest tile that `is_goal` picks out,
+/// searching outward a step at a time.
+///
+/// Tiles can be walked on as for `pathfind`, and the path likewise leaves out `from`.
+pub fn nearest<F: Fn(Point) -> bool>(world: &World, from: Point, is_goal: F) -> Option<Vec<Point>> {
+    let walkable = walkable(world);
+
+    let mut came_from: HashMap<Point, Point> = HashMap::new();
+    let mut open = VecDeque::new();
+    open.push_back(from);
+
+    while let Some(current) = open.pop_front() {
+        if current != from && is_goal(current) {
+            let mut path = vec![current];
+            let mut point = current;
+            while let Some(&prev) = came_from.get(&point) {
+                if prev == from {
+                    break;
+                }
+                path.push(prev);
+                point = prev;
+            }
+            path.reverse();
+            return Some(path);
+        }
+
+        for dx in -1..=1 {
+            for dy in -1..=1 {
+                let next = Point::new(current.x + dx, current.y + dy);
+                if next == from || !walkable.contains(&next) || came_from.contains_key(&next) {
+                    continue;
+                }
+                came_from.insert(next, current);
+                open.push_back(next);
+            }
+        }
+    }
+
+    None
+}
+
+/// Return every tile with a cell and no `Impassable` cell
+fn walkable(world: &World) -> HashSet<Point> {
+    let mut query = <(Read<GameCell>,)>::query();
+
+    let mut ground = HashSet::new();
+    let mut blocked = HashSet::new();
+    for (cell,) in query.iter(world) {
+        if cell.access() == CellAccess::Impassable {
+            blocked.insert(cell.point());
+        } else {
+            ground.insert(cell.point());
+        }
+    }
+    ground.retain(|point| !blocked.contains(point));
+    ground
+}
+
+fn chebyshev(a: Point, b: Point) -> i32 {
+    (a.x - b.x).abs().max((a.y - b.y).abs())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::types::CellKind;
+
+    /// Return a world drawn from `rows`, with `#` for a wall and `.` for floor
+    fn map(rows: &[&str]) -> World {
+        let mut world = World::default();
+        for (y, row) in rows.iter().enumerate() {
+            for (x, tile) in row.chars().enumerate() {
+                let (kind, access) = match tile {
+                    '#' => (CellKind::Wall, CellAccess::Impassable),
+                    _ => (CellKind::Floor, CellAccess::Static),
+                };
+                let point = Point::new(x as i32, y as i32);
+                world.push((GameCell::new(
+                    point,
+                    kind,
+                    kind.name(),
+                    RGB::named(GRAY),
+                    access,
+                ),));
+            }
+        }
+        world
+    }
+
+    #[test]
+    fn walks_around_a_wall() {
+        let world = map(&["..#..", "..#..", "....."]);
+        let path = pathfind(&world, Point::new(0, 0), Point::new(4, 0)).unwrap();
+
+        assert_eq!(
+            path,
+            vec![
+                Point::new(1, 1),
+                Point::new(2, 2),
+                Point::new(3, 1),
+                Point::new(4, 0),
+            ]
+        );
+    }
+
+    #[test]
+    fn finds_no_way_through_a_wall() {
+        let world = map(&["..#..", "..#..", "..#.."]);
+        assert_eq!(pathfind(&world, Point::new(0, 0), Point::new(4, 0)), None);
+        assert_eq!(pathfind(&world, Point::new(0, 0), Point::new(2, 0)), None);
+    }
+}
@@ -0,0 +1,332 @@
+use std::{collections::HashMap, fs, io};
+
+use legion::*;
+
+use serde::{Deserialize, Serialize};
+
+use serde_json::Value;
+
+use crate::{
+    components::{CellVisibility, EnemyAI, GameCell, Inventory},
+    types::{GameEvents, Player, Potions, StatusEffects},
+};
+
+/// Version of the save format written by this build.
+/// Bump it whenever `SaveData` changes in a way older saves can't be read as,
+/// and add a step to `migrate` that brings the previous version up to date.
+pub const CURRENT_SAVE_VERSION: u32 = 3;
+
+/// A single entity of the world, flattened into the components it carries
+#[derive(Serialize, Deserialize)]
+struct SavedCell {
+    cell: GameCell,
+    visibility: CellVisibility,
+    enemy: Option<EnemyAI>,
+    /// Effects on an enemy, like bleeding from a serrated blade
+    #[serde(default)]
+    effects: Option<StatusEffects>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct SaveData {
+    version: u32,
+    cells: Vec<SavedCell>,
+    #[serde(default)]
+    levels: HashMap<u32, Vec<SavedCell>>,
+    player: Player,
+    inventory: Inventory,
+    game_events: GameEvents,
+    #[serde(default)]
+    potions: Option<Potions>,
+    depth: u32,
+    seed: u64,
+}
+
+/// Everything that goes into a save, borrowed from the running game
+pub struct GameRef<'a> {
+    pub world: &'a World,
+    pub levels: &'a HashMap<u32, World>,
+    pub player: &'a Player,
+    pub inventory: &'a Inventory,
+    pub game_events: &'a GameEvents,
+    pub potions: &'a Potions,
+    pub depth: u32,
+    pub seed: u64,
+}
+
+/// Everything needed to pick a saved game back up
+pub struct LoadedGame {
+    pub world: World,
+    pub levels: HashMap<u32, World>,
+    pub player: Player,
+    pub inventory: Inventory,
+    pub game_events: GameEvents,
+    pub potions: Option<Potions>,
+    pub depth: u32,
+    pub seed: u64,
+}
+
+/// Write the current level, the levels left behind, the player, inventory and event log
+/// to `path` as JSON.
+///
+/// Legion entities can't be serialized directly, so every entity with a
+/// `GameCell` is flattened into a `SavedCell` holding its components.
+/// Levels never visited aren't kept; the depth and seed let them be generated.
+pub fn save(game: &GameRef, path: &str) -> io::Result<()> {
+    let data = SaveData {
+        version: CURRENT_SAVE_VERSION,
+        cells: save_cells(game.world),
+        levels: game
+            .levels
+            .iter()
+            .map(|(&depth, level)| (depth, save_cells(level)))
+            .collect(),
+        player: game.player.clone(),
+        inventory: game.inventory.clone(),
+        game_events: game.game_events.clone(),
+        potions: Some(game.potions.clone()),
+        depth: game.depth,
+        seed: game.seed,
+    };
+
+    fs::write(path, serde_json::to_string(&data)?)
+}
+
+/// Read a game back from `path`.
+///
+/// Saves from older versions are migrated first, while saves from a newer
+/// version than this build are refused rather than read wrong.
+/// Each `SavedCell` is pushed as a fresh entity with the same components,
+/// so entity ids differ from the saved game but the world's contents match.
+pub fn load(path: &str) -> io::Result<LoadedGame> {
+    let mut value: Value = serde_json::from_str(&fs::read_to_string(path)?)?;
+    migrate(&mut value)?;
+    let data: SaveData = serde_json::from_value(value)?;
+
+    Ok(LoadedGame {
+        world: load_cells(data.cells),
+        levels: data
+            .levels
+            .into_iter()
+            .map(|(depth, cells)| (depth, load_cells(cells)))
+            .collect(),
+        player: data.player,
+        inventory: data.inventory,
+        game_events: data.game_events,
+        potions: data.potions,
+        depth: data.depth,
+        seed: data.seed,
+    })
+}
+
+/// Bring a save of any older version up to `CURRENT_SAVE_VERSION`, one version at a time.
+/// Saves from before versioning have no `version` and count as version 1.
+fn migrate(value: &mut Value) -> io::Result<()> {
+    let version = value.get("version").and_then(Value::as_u64).unwrap_or(1) as u32;
+    if version > CURRENT_SAVE_VERSION {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "save is version {} but this game only reads up to version {}",
+                version, CURRENT_SAVE_VERSION
+            ),
+        ));
+    }
+
+    for from in version..CURRENT_SAVE_VERSION {
+        match from {
+            1 => migrate_v1(value),
+            2 => migrate_v2(value),
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("no way to migrate a version {} save", from),
+                ))
+            }
+        }
+        value["version"] = Value::from(from + 1);
+    }
+    Ok(())
+}
+
+/// Version 1 saves only held the current level and had no potion colors,
+/// so give them an empty set of levels left behind and leave the potions to be rolled
+fn migrate_v1(value: &mut Value) {
+    if let Some(save) = value.as_object_mut() {
+        save.entry("levels")
+            .or_insert_with(|| Value::Object(Default::default()));
+        save.entry("potions").or_insert(Value::Null);
+    }
+}
+
+/// Version 2 saves wrote the enemy marker as `null`, the same as no marker at all,
+/// so give every enemy cell a sleeping `EnemyAI` back
+fn migrate_v2(value: &mut Value) {
+    restore_enemy_ai(&mut value["cells"]);
+    if let Some(levels) = value["levels"].as_object_mut() {
+        for cells in levels.values_mut() {
+            restore_enemy_ai(cells);
+        }
+    }
+}
+
+fn restore_enemy_ai(cells: &mut Value) {
+    for saved in cells.as_array_mut().into_iter().flatten() {
+        if saved["cell"]["kind"] == "Enemy" && saved["enemy"].is_null() {
+            saved["enemy"] = serde_json::json!({ "state": "Asleep", "unseen": 0 });
+        }
+    }
+}
+
+fn save_cells(world: &World) -> Vec<SavedCell> {
+    let mut query = <(
+        Read<GameCell>,
+        Read<CellVisibility>,
+        TryRead<EnemyAI>,
+        TryRead<StatusEffects>,
+    )>::query();
+
+    query
+        .iter(world)
+        .map(|(cell, visibility, enemy, effects)| SavedCell {
+            cell: cell.clone(),
+            visibility: *visibility,
+            enemy: enemy.copied(),
+            effects: effects.cloned(),
+        })
+        .collect()
+}
+
+fn load_cells(cells: Vec<SavedCell>) -> World {
+    let mut world = World::default();
+    for saved in cells {
+        let entity = match saved.enemy {
+            Some(enemy) => world.push((saved.cell, saved.visibility, enemy)),
+            None => world.push((saved.cell, saved.visibility)),
+        };
+        if let (Some(effects), Some(mut entry)) = (saved.effects, world.entry(entity)) {
+            entry.add_component(effects);
+        }
+    }
+    world
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use bracket_lib::prelude::{Point, RGB};
+
+    use crate::{
+        components::AiState,
+        types::{CellAccess, CellKind, Effect},
+    };
+
+    /// Return a version 1 save with no version number, no levels left behind,
+    /// no potion colors and an enemy written without its marker
+    fn v1_save() -> Value {
+        let enemy = GameCell::new(
+            Point::new(3, 4),
+            CellKind::Enemy,
+            "rat",
+            RGB::named((150, 0, 0)),
+            CellAccess::Hostile,
+        );
+        serde_json::json!({
+            "cells": [{
+                "cell": serde_json::to_value(&enemy).unwrap(),
+                "visibility": serde_json::to_value(CellVisibility::Visible).unwrap(),
+                "enemy": null,
+            }],
+            "depth": 1,
+            "seed": 7,
+        })
+    }
+
+    #[test]
+    fn migrates_a_v1_save_to_the_current_version() {
+        let mut save = v1_save();
+        migrate(&mut save).unwrap();
+
+        assert_eq!(save["version"], CURRENT_SAVE_VERSION);
+        assert_eq!(save["levels"], serde_json::json!({}));
+        assert!(save["potions"].is_null());
+
+        let cells: Vec<SavedCell> = serde_json::from_value(save["cells"].clone()).unwrap();
+        assert_eq!(cells[0].cell.name(), "rat");
+        assert_eq!(cells[0].enemy.map(|ai| ai.state), Some(AiState::Asleep));
+    }
+
+    #[test]
+    fn refuses_a_save_from_a_newer_version() {
+        let mut save = serde_json::json!({ "version": CURRENT_SAVE_VERSION + 1 });
+        assert!(migrate(&mut save).is_err());
+    }
+
+    #[test]
+    fn cells_come_back_with_their_enemy_marker_and_effects() {
+        let mut world = World::default();
+        world.push((
+            GameCell::new(
+                Point::new(1, 1),
+                CellKind::Floor,
+                "floor",
+                RGB::named((150, 150, 150)),
+                CellAccess::Static,
+            ),
+            CellVisibility::Dark,
+        ));
+        let mut effects = StatusEffects::default();
+        effects.apply(Effect::Bleed, 3);
+        let ai = EnemyAI {
+            state: AiState::Chasing,
+            ..EnemyAI::new().with_pack(2)
+        };
+        world.push((
+            GameCell::new(
+                Point::new(2, 1),
+                CellKind::Enemy,
+                "goblin",
+                RGB::named((0, 150, 0)),
+                CellAccess::Hostile,
+            ),
+            CellVisibility::Visible,
+            ai,
+            effects,
+        ));
+
+        let json = serde_json::to_string(&save_cells(&world)).unwrap();
+        let world = load_cells(serde_json::from_str(&json).unwrap());
+
+        let mut query = <(
+            Read<GameCell>,
+            Read<CellVisibility>,
+            TryRead<EnemyAI>,
+            TryRead<StatusEffects>,
+        )>::query();
+        let mut loaded = query
+            .iter(&world)
+            .map(|(cell, visibility, enemy, effects)| {
+                (
+                    cell.name(),
+                    *visibility,
+                    enemy.copied(),
+                    effects.map(StatusEffects::active),
+                )
+            })
+            .collect::<Vec<_>>();
+        loaded.sort_by(|a, b| a.0.cmp(&b.0));
+        assert_eq!(
+            loaded,
+            vec![
+                (String::from("floor"), CellVisibility::Dark, None, None),
+                (
+                    String::from("goblin"),
+                    CellVisibility::Visible,
+                    Some(ai),
+                    Some(vec![Effect::Bleed])
+                ),
+            ]
+        );
+    }
+}
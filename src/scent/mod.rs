@@ -0,0 +1,67 @@
+use std::collections::HashMap;
+
+use bracket_lib::prelude::*;
+
+/// How strong the scent is on the player's own tile
+const MAX_SCENT: u32 = 64;
+/// How much scent a tile loses every turn
+const DECAY: u32 = 2;
+/// How much weaker scent is on a tile than on the neighbor it spread from
+const SPREAD: u32 = 4;
+
+/// The player's scent trail, so enemies that can't see the player can still follow them.
+///
+/// The player's tile is topped up every turn and the scent spreads out from there,
+/// getting weaker with every tile and every turn, so it never reaches further than
+/// `MAX_SCENT / SPREAD` tiles. Only tiles with some scent on them are stored.
+#[derive(Clone, Debug, Default)]
+pub struct ScentMap {
+    scent: HashMap<Point, u32>,
+}
+
+impl ScentMap {
+    /// Lay fresh scent at `origin`, then let it decay and spread a tile
+    /// into every neighbor that `is_open`
+    pub fn update<F: FnMut(Point) -> bool>(&mut self, origin: Point, mut is_open: F) {
+        let mut next: HashMap<Point, u32> = HashMap::with_capacity(self.scent.len() * 2);
+
+        for (&point, &strength) in &self.scent {
+            let decayed = strength.saturating_sub(DECAY);
+            if decayed > 0 {
+                let entry = next.entry(point).or_insert(0);
+                *entry = (*entry).max(decayed);
+            }
+
+            let spread = strength.saturating_sub(SPREAD);
+            if spread == 0 {
+                continue;
+            }
+            for neighbor in neighbors(point) {
+                if next.get(&neighbor).map_or(false, |&s| s >= spread) || !is_open(neighbor) {
+                    continue;
+                }
+                next.insert(neighbor, spread);
+            }
+        }
+        next.insert(origin, MAX_SCENT);
+
+        self.scent = next;
+    }
+
+    /// Return how strong the scent is at `point`
+    pub fn at(&self, point: Point) -> u32 {
+        self.scent.get(&point).copied().unwrap_or(0)
+    }
+
+    /// Forget the whole trail, as when the player leaves the level
+    pub fn clear(&mut self) {
+        self.scent.clear();
+    }
+}
+
+fn neighbors(point: Point) -> impl Iterator<Item = Point> {
+    (-1..=1)
+        .flat_map(|dy| (-1..=1).map(move |dx| (dx, dy)))
+        .filter(|&(dx, dy)| dx != 0 || dy != 0)
+        .map(move |(dx, dy)| Point::new(point.x + dx, point.y + dy))
+}
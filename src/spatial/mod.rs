@@ -0,0 +1,226 @@
+use std::collections::HashMap;
+
+use bracket_lib::prelude::*;
+
+use legion::*;
+
+use crate::components::{CellVisibility, GameCell};
+
+/// Index from world points to the entities standing there, so collision
+/// checks look at a single tile instead of every cell in the world.
+///
+/// Moving the player only shifts the render offset, so the grid only needs
+/// updating when cells themselves are added, removed or moved.
+#[derive(Clone, Debug, Default)]
+pub struct SpatialGrid {
+    cells: HashMap<Point, Vec<Entity>>,
+}
+
+impl SpatialGrid {
+    pub fn new(world: &World) -> Self {
+        let mut grid = Self::default();
+        grid.rebuild(world);
+        grid
+    }
+
+    /// Throw the index away and rebuild it from every `GameCell` in `world`
+    pub fn rebuild(&mut self, world: &World) {
+        self.cells.clear();
+
+        let mut query = <(Read<GameCell>,)>::query();
+
+        for chunk in query.iter_chunks(world) {
+            for (entity, (cell,)) in chunk.into_iter_entities() {
+                for tile in cell.tiles() {
+                    self.insert(tile, entity);
+                }
+            }
+        }
+    }
+
+    pub fn at(&self, point: Point) -> &[Entity] {
+        self.cells
+            .get(&point)
+            .map_or(&[], |entities| entities.as_slice())
+    }
+
+    pub fn insert(&mut self, point: Point, entity: Entity) {
+        self.cells
+            .entry(point)
+            .or_insert_with(Vec::new)
+            .push(entity);
+    }
+
+    pub fn remove(&mut self, point: Point, entity: Entity) {
+        if let Some(entities) = self.cells.get_mut(&point) {
+            entities.retain(|&e| e != entity);
+            if entities.is_empty() {
+                self.cells.remove(&point);
+            }
+        }
+    }
+
+    /// Move an entity covering several tiles from the tiles in `from` to those in `to`
+    pub fn move_tiles(&mut self, entity: Entity, from: &[Point], to: &[Point]) {
+        for &tile in from {
+            self.remove(tile, entity);
+        }
+        for &tile in to {
+            self.insert(tile, entity);
+        }
+    }
+
+    /// Return whether any cell at `point` matches `pred`
+    pub fn any_at<F: Fn(&GameCell) -> bool>(
+        &self,
+        world: &mut World,
+        point: Point,
+        pred: F,
+    ) -> bool {
+        self.at(point).iter().any(|&entity| {
+            world.entry(entity).map_or(false, |entry| {
+                entry
+                    .get_component::<GameCell>()
+                    .map_or(false, |cell| pred(cell))
+            })
+        })
+    }
+
+    /// Return whether `cell` can go where it stands: on ground covered by nothing,
+    /// or nothing but items it piles up with
+    pub fn has_room_for(&self, world: &mut World, cell: &GameCell) -> bool {
+        cell.tiles().into_iter().all(|tile| {
+            self.any_at(world, tile, |other| other.kind().is_ground())
+                && !self.any_at(world, tile, |other| {
+                    !other.kind().is_ground() && !other.stacks_with(cell)
+                })
+        })
+    }
+
+    /// Put `cell` into `world` where it stands, unless something is already there
+    /// other than items it piles up with.
+    /// Returns the new entity, or `None` if it wasn't placed so the caller can try another spot.
+    pub fn place_cell(
+        &mut self,
+        world: &mut World,
+        cell: GameCell,
+        visibility: CellVisibility,
+    ) -> Option<Entity> {
+        if !self.has_room_for(world, &cell) {
+            return None;
+        }
+        let tiles = cell.tiles();
+        let entity = world.push((cell, visibility));
+        for tile in tiles {
+            self.insert(tile, entity);
+        }
+        Some(entity)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::types::{CellAccess, CellKind};
+
+    fn cell(kind: CellKind, name: &str, access: CellAccess) -> GameCell {
+        GameCell::new(Point::new(2, 3), kind, name, RGB::named(GRAY), access)
+    }
+
+    #[test]
+    fn places_nothing_on_a_taken_tile_but_what_piles_up() {
+        let mut world = World::default();
+        world.push((
+            cell(CellKind::Floor, "floor", CellAccess::Static),
+            CellVisibility::Visible,
+        ));
+        let mut grid = SpatialGrid::new(&world);
+        let arrow = cell(CellKind::PointedWeapon, "arrow", CellAccess::Takeable);
+
+        assert!(grid
+            .place_cell(&mut world, arrow.clone(), CellVisibility::Visible)
+            .is_some());
+        assert!(grid
+            .place_cell(&mut world, arrow, CellVisibility::Visible)
+            .is_some());
+        let rat = cell(CellKind::Enemy, "rat", CellAccess::Hostile);
+        assert!(grid
+            .place_cell(&mut world, rat.clone(), CellVisibility::Visible)
+            .is_none());
+        // Nor anywhere there's no ground
+        assert!(grid
+            .place_cell(
+                &mut world,
+                rat.with_point(Point::new(9, 9)),
+                CellVisibility::Visible
+            )
+            .is_none());
+        assert_eq!(grid.at(Point::new(2, 3)).len(), 3);
+    }
+
+    /// Time checking the four tiles next to a player for something in the way on a
+    /// 10k-cell map, with the grid and with the full scan every keypress used to do.
+    /// Run with `cargo test --release keypress -- --ignored --nocapture`.
+    #[test]
+    #[ignore]
+    fn keypress_collision_checks_on_a_10k_cell_map() {
+        use std::time::Instant;
+
+        let mut world = World::default();
+        for y in 0..100 {
+            for x in 0..100 {
+                let (kind, access) = if x % 7 == 0 {
+                    (CellKind::Wall, CellAccess::Impassable)
+                } else {
+                    (CellKind::Floor, CellAccess::Static)
+                };
+                let point = Point::new(x, y);
+                world.push((
+                    GameCell::new(point, kind, kind.name(), RGB::named(GRAY), access),
+                    CellVisibility::Visible,
+                ));
+            }
+        }
+        let grid = SpatialGrid::new(&world);
+        let keypresses = (0..1000).map(|i| Point::new(i % 100, i / 10));
+        let neighbors = |p: Point| {
+            vec![
+                Point::new(p.x + 1, p.y),
+                Point::new(p.x - 1, p.y),
+                Point::new(p.x, p.y + 1),
+                Point::new(p.x, p.y - 1),
+            ]
+        };
+
+        let start = Instant::now();
+        let by_grid = keypresses
+            .clone()
+            .flat_map(neighbors)
+            .filter(|&tile| {
+                grid.any_at(&mut world, tile, |cell| {
+                    cell.access() == CellAccess::Impassable
+                })
+            })
+            .count();
+        let grid_time = start.elapsed();
+
+        let mut query = <(Read<GameCell>,)>::query();
+        let start = Instant::now();
+        let by_scan = keypresses
+            .flat_map(neighbors)
+            .filter(|&tile| {
+                query
+                    .iter(&world)
+                    .any(|(cell,)| cell.point() == tile && cell.access() == CellAccess::Impassable)
+            })
+            .count();
+        let scan_time = start.elapsed();
+
+        assert_eq!(by_grid, by_scan);
+        println!(
+            "1000 keypresses: {:?} with the grid, {:?} scanning every cell",
+            grid_time, scan_time
+        );
+    }
+}
@@ -0,0 +1,65 @@
+/// How hard a level is, scaled by how deep it is
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DifficultyParams {
+    /// How many enemies are spawned
+    pub spawn_count: usize,
+    /// Enemy max HP, as a percentage of their usual max HP
+    pub hp_percent: u32,
+    /// Extra dice added to every enemy's damage
+    pub extra_damage_dice: u32,
+    /// How much better loot is than on the first level, starting from 0
+    pub loot_quality: u32,
+    /// How many hidden traps are laid
+    pub trap_count: usize,
+}
+
+/// Return how hard the level at `depth` is.
+///
+/// The first level, at depth 0, plays as it always has.
+/// Every level down adds an enemy and a fifth to their HP,
+/// every third level adds a die to their damage,
+/// every level improves the loot, and every second level lays another trap.
+pub fn difficulty_for_depth(depth: u32) -> DifficultyParams {
+    DifficultyParams {
+        spawn_count: 2 + depth as usize,
+        hp_percent: 100 + 20 * depth,
+        extra_damage_dice: depth / 3,
+        loot_quality: depth,
+        trap_count: 1 + depth as usize / 2,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_first_level_plays_as_it_always_has() {
+        assert_eq!(
+            difficulty_for_depth(0),
+            DifficultyParams {
+                spawn_count: 2,
+                hp_percent: 100,
+                extra_damage_dice: 0,
+                loot_quality: 0,
+                trap_count: 1,
+            }
+        );
+    }
+
+    #[test]
+    fn every_level_down_is_at_least_as_hard() {
+        for depth in 1..30 {
+            let (above, below) = (difficulty_for_depth(depth - 1), difficulty_for_depth(depth));
+            assert!(below.spawn_count > above.spawn_count, "depth {}", depth);
+            assert!(below.hp_percent > above.hp_percent, "depth {}", depth);
+            assert!(
+                below.extra_damage_dice >= above.extra_damage_dice,
+                "depth {}",
+                depth
+            );
+            assert!(below.loot_quality > above.loot_quality, "depth {}", depth);
+            assert!(below.trap_count >= above.trap_count, "depth {}", depth);
+        }
+    }
+}
@@ -0,0 +1,507 @@
+use std::{collections::HashMap, mem};
+
+use bracket_lib::prelude::*;
+
+use legion::*;
+
+use rand::{rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
+
+use super::difficulty::difficulty_for_depth;
+use crate::{
+    components::{CellVisibility, EnemyAI, GameCell},
+    spatial::SpatialGrid,
+    types::{CellAccess, CellKind, Dice, LootTable, POTION_COLORS},
+    worldgen,
+};
+
+const WHITE: (u8, u8, u8) = (255, 255, 255);
+
+pub const MAP_WIDTH: i32 = 80;
+pub const MAP_HEIGHT: i32 = 50;
+
+/// Keeps every level the player has left so going back restores it as it was
+pub struct LevelManager {
+    seed: u64,
+    depth: u32,
+    levels: HashMap<u32, World>,
+}
+
+impl LevelManager {
+    pub fn new(seed: u64, depth: u32) -> Self {
+        Self {
+            seed,
+            depth,
+            levels: HashMap::new(),
+        }
+    }
+
+    /// Pick a game back up with the levels the player had already left
+    pub fn restore(seed: u64, depth: u32, levels: HashMap<u32, World>) -> Self {
+        Self {
+            seed,
+            depth,
+            levels,
+        }
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+    pub fn depth(&self) -> u32 {
+        self.depth
+    }
+    /// Return the levels the player has left, by depth
+    pub fn levels(&self) -> &HashMap<u32, World> {
+        &self.levels
+    }
+
+    /// Generate the current level and return it with the player's starting point
+    pub fn generate(&self) -> (World, Point) {
+        generate(self.seed, self.depth)
+    }
+
+    /// Swap `world` for the level at `depth`, caching the level being left.
+    /// Returns the stairs the player arrives on.
+    pub fn travel(&mut self, world: &mut World, depth: u32) -> Point {
+        let (next, start) = match self.levels.remove(&depth) {
+            Some(level) => (level, None),
+            None => {
+                let (level, start) = generate(self.seed, depth);
+                (level, Some(start))
+            }
+        };
+        let arrival = if depth > self.depth {
+            CellKind::StairsUp
+        } else {
+            CellKind::StairsDown
+        };
+
+        let prev = mem::replace(world, next);
+        self.levels.insert(self.depth, prev);
+        self.depth = depth;
+
+        let mut query = <(Read<GameCell>,)>::query();
+        query
+            .iter(world)
+            .find(|(cell,)| cell.kind() == arrival)
+            .map(|(cell,)| cell.point())
+            .or(start)
+            .unwrap_or_else(|| Point::new(0, 0))
+    }
+}
+
+/// How far a torch lights up around it
+const TORCH_LIGHT: i32 = 3;
+/// How many turns a torch burns for
+const TORCH_FUEL: u32 = 300;
+/// How many times a chest's contents are rolled from the loot table
+const CHEST_ROLLS: usize = 3;
+/// Fewest and most goblins in the pack that roams every level below the first
+const PACK_SIZE: (usize, usize) = (3, 5);
+
+/// Generate the level at `depth` and return it with the player's starting point
+fn generate(seed: u64, depth: u32) -> (World, Point) {
+    let mut world = World::default();
+
+    let rooms = worldgen::generate_rooms(
+        &mut world,
+        MAP_WIDTH,
+        MAP_HEIGHT,
+        seed.wrapping_add(u64::from(depth)),
+    );
+    let start = rooms[0].center();
+    let stairs = rooms[rooms.len() - 1].center();
+
+    if depth > 0 {
+        world.push((
+            GameCell::new(
+                start,
+                CellKind::StairsUp,
+                "staircase up",
+                RGB::named(WHITE),
+                CellAccess::Static,
+            ),
+            CellVisibility::Unvisited,
+        ));
+    }
+    world.push((
+        GameCell::new(
+            stairs,
+            CellKind::StairsDown,
+            "staircase down",
+            RGB::named(WHITE),
+            CellAccess::Static,
+        ),
+        CellVisibility::Unvisited,
+    ));
+
+    let mut grid = SpatialGrid::new(&world);
+    let difficulty = difficulty_for_depth(depth);
+    let mut rng = StdRng::seed_from_u64(seed.wrapping_add(u64::from(depth)).rotate_left(32));
+
+    // Lock one of the doors, with its key lying next to the start so it can always be reached
+    let doors = <(Read<GameCell>,)>::query()
+        .iter(&world)
+        .filter(|(cell,)| cell.kind() == CellKind::ClosedDoor)
+        .count();
+    if doors > 0 {
+        let locked = rng.gen_range(0, doors);
+        if let Some((door,)) = <(Write<GameCell>,)>::query()
+            .iter_mut(&mut world)
+            .filter(|(cell,)| cell.kind() == CellKind::ClosedDoor)
+            .nth(locked)
+        {
+            door.set_kind(CellKind::LockedDoor);
+            door.set_name("locked door");
+            door.set_lock(depth);
+        }
+        let key = GameCell::new(
+            start,
+            CellKind::Key,
+            "brass key",
+            RGB::from_u8(255, 215, 0),
+            CellAccess::Takeable,
+        )
+        .with_lock(depth)
+        .with_weight(1);
+        // East of the start if there's room, otherwise any other tile around it
+        let around = [
+            (1, 0),
+            (-1, 0),
+            (0, 1),
+            (0, -1),
+            (1, 1),
+            (-1, 1),
+            (1, -1),
+            (-1, -1),
+        ];
+        around.iter().any(|&(dx, dy)| {
+            grid.place_cell(
+                &mut world,
+                key.clone()
+                    .with_point(Point::new(start.x + dx, start.y + dy)),
+                CellVisibility::Unvisited,
+            )
+            .is_some()
+        });
+    }
+    let (potion, (r, g, b)) = POTION_COLORS[rng.gen_range(0, POTION_COLORS.len())];
+    let mut spots = floor_tiles(&world, &rooms[0], &mut rng).into_iter();
+
+    let items = vec![
+        GameCell::weapon(
+            CellKind::EdgedWeapon,
+            "sword",
+            RGB::from_u8(0, 0, 255),
+            Dice::new(1, 8 + 2 * (difficulty.loot_quality / 2)),
+        )
+        .with_weight(8),
+        GameCell::armor(
+            CellKind::SoftArmor,
+            "leather armor",
+            RGB::from_u8(150, 75, 0),
+            1 + difficulty.loot_quality as i32 / 3,
+        )
+        .with_weight(10),
+        GameCell::new(
+            Point::new(0, 0),
+            CellKind::Food,
+            "ration",
+            RGB::from_u8(200, 150, 100),
+            CellAccess::Takeable,
+        )
+        .with_weight(1),
+        GameCell::new(
+            Point::new(0, 0),
+            CellKind::Potion,
+            &format!("{} potion", potion),
+            RGB::from_u8(r, g, b),
+            CellAccess::Takeable,
+        )
+        .with_weight(1),
+        GameCell::new(
+            Point::new(0, 0),
+            CellKind::Torch,
+            "torch",
+            RGB::from_u8(255, 150, 0),
+            CellAccess::Takeable,
+        )
+        .with_weight(2)
+        .with_light(TORCH_LIGHT, TORCH_FUEL),
+    ];
+    for item in items {
+        spots.any(|spot| {
+            grid.place_cell(
+                &mut world,
+                item.clone().with_point(spot),
+                CellVisibility::Unvisited,
+            )
+            .is_some()
+        });
+    }
+
+    let roster = enemy_roster(depth);
+    let enemies = roster.iter().cycle().take(difficulty.spawn_count).cloned();
+    for enemy in enemies {
+        let placed = spots.find_map(|spot| {
+            grid.place_cell(
+                &mut world,
+                enemy.clone().with_point(spot),
+                CellVisibility::Unvisited,
+            )
+        });
+        if let Some(mut entry) = placed.and_then(|entity| world.entry(entity)) {
+            entry.add_component(EnemyAI::new());
+        }
+    }
+
+    // The first level plays as it always has, without a pack
+    if depth > 0 {
+        if let Some(den) = spots.next() {
+            let goblin = roster[0].clone();
+            let size = rng.gen_range(PACK_SIZE.0, PACK_SIZE.1 + 1);
+            let mut around = (-2..=2)
+                .flat_map(|dy| (-2..=2).map(move |dx| Point::new(den.x + dx, den.y + dy)))
+                .collect::<Vec<_>>();
+            around.sort_by_key(|spot| (spot.x - den.x).abs().max((spot.y - den.y).abs()));
+
+            let mut around = around.into_iter();
+            for _ in 0..size {
+                let placed = around.find_map(|spot| {
+                    grid.place_cell(
+                        &mut world,
+                        goblin.clone().with_point(spot),
+                        CellVisibility::Unvisited,
+                    )
+                });
+                let entity = match placed {
+                    Some(entity) => entity,
+                    None => break,
+                };
+                if let Some(mut entry) = world.entry(entity) {
+                    entry.add_component(EnemyAI::new().with_pack(depth));
+                }
+            }
+        }
+    }
+
+    for amount in &[15, 25, 40] {
+        let amount = amount * (4 + difficulty.loot_quality) / 4;
+        let gold = GameCell::new(
+            Point::new(0, 0),
+            CellKind::Gold,
+            "gold",
+            RGB::from_u8(255, 215, 0),
+            CellAccess::Takeable,
+        )
+        .with_value(amount);
+        spots.any(|spot| {
+            grid.place_cell(
+                &mut world,
+                gold.clone().with_point(spot),
+                CellVisibility::Unvisited,
+            )
+            .is_some()
+        });
+    }
+
+    let shopkeeper = GameCell::new(
+        Point::new(0, 0),
+        CellKind::Shopkeeper,
+        "shopkeeper",
+        RGB::from_u8(255, 255, 0),
+        CellAccess::Impassable,
+    );
+    spots.any(|spot| {
+        grid.place_cell(
+            &mut world,
+            shopkeeper.clone().with_point(spot),
+            CellVisibility::Unvisited,
+        )
+        .is_some()
+    });
+
+    let loot = LootTable::new();
+    let contents: Vec<GameCell> = (0..CHEST_ROLLS)
+        .filter_map(|_| loot.roll("chest", &mut rng))
+        .collect();
+    // A chest that rolled nothing is left out rather than standing there empty
+    if !contents.is_empty() {
+        let chest = GameCell::new(
+            Point::new(0, 0),
+            CellKind::Chest,
+            "chest",
+            RGB::from_u8(150, 75, 0),
+            CellAccess::Impassable,
+        )
+        .with_contents(contents);
+        spots.any(|spot| {
+            grid.place_cell(
+                &mut world,
+                chest.clone().with_point(spot),
+                CellVisibility::Unvisited,
+            )
+            .is_some()
+        });
+    }
+
+    let trap = GameCell::new(
+        Point::new(0, 0),
+        CellKind::Trap,
+        "spike trap",
+        RGB::from_u8(200, 0, 0),
+        CellAccess::Static,
+    )
+    .with_damage(Dice::new(1, 4))
+    .with_hidden();
+    // Traps draw from floor of their own, so everything placed before can't use it all up
+    let mut trap_spots = floor_tiles(&world, &rooms[0], &mut rng).into_iter();
+    for _ in 0..difficulty.trap_count {
+        trap_spots.any(|spot| {
+            grid.place_cell(
+                &mut world,
+                trap.clone().with_point(spot),
+                CellVisibility::Unvisited,
+            )
+            .is_some()
+        });
+    }
+
+    (world, start)
+}
+
+/// Return every floor tile outside the `start` room, shuffled, for things to be scattered over
+fn floor_tiles(world: &World, start: &Rect, rng: &mut StdRng) -> Vec<Point> {
+    let mut tiles = <(Read<GameCell>,)>::query()
+        .iter(world)
+        .filter(|(cell,)| cell.kind() == CellKind::Floor)
+        .map(|(cell,)| cell.point())
+        .filter(|&point| !start.point_in_rect(point))
+        .collect::<Vec<_>>();
+    tiles.shuffle(rng);
+    tiles
+}
+
+/// Return the name of every kind of monster, in the order the bestiary lists them
+pub fn monster_names() -> Vec<String> {
+    // Every kind has turned up by the second level
+    enemy_roster(1).iter().map(|enemy| enemy.name()).collect()
+}
+
+/// Return every kind of enemy that turns up at `depth`, already made as tough as the level is
+pub fn enemy_roster(depth: u32) -> Vec<GameCell> {
+    let difficulty = difficulty_for_depth(depth);
+
+    let mut roster = vec![
+        GameCell::new(
+            Point::new(0, 0),
+            CellKind::Enemy,
+            "goblin",
+            RGB::from_u8(0, 150, 0),
+            CellAccess::Hostile,
+        )
+        .with_damage(Dice::new(1, 4))
+        .with_health(8),
+        GameCell::new(
+            Point::new(0, 0),
+            CellKind::Enemy,
+            "rat",
+            RGB::from_u8(150, 100, 50),
+            CellAccess::Hostile,
+        )
+        .with_damage(Dice::new(1, 2))
+        .with_health(4),
+    ];
+    if depth > 0 {
+        roster.push(
+            GameCell::new(
+                Point::new(0, 0),
+                CellKind::Enemy,
+                "ogre",
+                RGB::from_u8(100, 120, 60),
+                CellAccess::Hostile,
+            )
+            .with_damage(Dice::new(2, 4))
+            .with_health(20)
+            .with_footprint(&[Point::new(1, 0), Point::new(0, 1), Point::new(1, 1)]),
+        );
+    }
+    roster
+        .into_iter()
+        .map(|enemy| {
+            let max = enemy.health().map_or(1, |(_, max)| max);
+            let damage = enemy.damage().unwrap_or_else(|| Dice::new(1, 3));
+            enemy
+                .with_health(max * difficulty.hp_percent / 100)
+                .with_damage(damage.with_extra_dice(difficulty.extra_damage_dice))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn count(world: &World, kind: CellKind) -> usize {
+        <(Read<GameCell>,)>::query()
+            .iter(world)
+            .filter(|(cell,)| cell.kind() == kind)
+            .count()
+    }
+
+    #[test]
+    fn spawns_as_many_enemies_as_the_depth_calls_for() {
+        for seed in 0..5 {
+            let (world, _) = generate(seed, 0);
+            assert_eq!(
+                count(&world, CellKind::Enemy),
+                difficulty_for_depth(0).spawn_count
+            );
+
+            // Deeper down the pack comes on top of them
+            let (world, _) = generate(seed, 4);
+            assert!(count(&world, CellKind::Enemy) > difficulty_for_depth(4).spawn_count);
+        }
+    }
+
+    #[test]
+    fn lays_as_many_traps_as_the_depth_calls_for() {
+        for seed in 0..5 {
+            for &depth in &[0, 3, 6] {
+                let (world, _) = generate(seed, depth);
+                assert_eq!(
+                    count(&world, CellKind::Trap),
+                    difficulty_for_depth(depth).trap_count
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn never_stacks_a_feature_on_something_else() {
+        for seed in 0..5 {
+            let (world, _) = generate(seed, 2);
+            assert_eq!(count(&world, CellKind::Shopkeeper), 1);
+
+            let cells = <(Read<GameCell>,)>::query()
+                .iter(&world)
+                .map(|(cell,)| cell.clone())
+                .collect::<Vec<_>>();
+            let features = cells.iter().filter(|cell| {
+                matches!(
+                    cell.kind(),
+                    CellKind::Shopkeeper | CellKind::Chest | CellKind::Trap
+                )
+            });
+            for feature in features {
+                let covering = cells
+                    .iter()
+                    .filter(|cell| {
+                        !cell.kind().is_ground() && cell.tiles().contains(&feature.point())
+                    })
+                    .count();
+                assert_eq!(covering, 1, "{} shares its tile", feature.name());
+            }
+        }
+    }
+}
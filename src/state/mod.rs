@@ -1,163 +1,493 @@
+mod difficulty;
+mod levels;
+mod scheduler;
+mod spawn;
+mod undo;
+
+use std::{
+    collections::{HashMap, HashSet},
+    fs, io,
+    path::Path,
+    time::Duration,
+};
+
 use bracket_lib::prelude::*;
 
 use legion::*;
 
+use rand::Rng;
+
+use self::levels::{LevelManager, MAP_HEIGHT, MAP_WIDTH};
+use self::scheduler::{Actor, TurnScheduler};
+use self::spawn::SpawnController;
+use self::undo::{Snapshot, UndoHistory};
+
 use crate::{
-    components::{CellVisibility, GameCell, Inventory},
-    types::{CellAccess, CellKind, GameEvents, Player},
+    compass,
+    components::{CellVisibility, EnemyAI, GameCell, Inventory, NORMAL_SPEED},
+    config::{Config, Setting},
+    export, feedback,
+    floating::FloatingTexts,
+    fov::{self, LightMap},
+    input::{Action, KeyBindings},
+    layout::{Layout, LayoutConfig},
+    los,
+    menu::{StartChoice, StartMenu},
+    path, persistence,
+    scent::ScentMap,
+    spatial::SpatialGrid,
+    systems::{
+        afflict, AiSystem, CombatSystem, MoveOutcome, MovementSystem, RegenSystem, SearchSystem,
+        StatusSystem,
+    },
+    theme::Theme,
+    types::{
+        counted, hp_bar, CellAccess, CellKind, Dice, Effect, EquipSlot, EventKind, GameEvents,
+        GameRng, Loadout, Player, PotionEffect, Potions, Shop, HP_BAR_WIDTH,
+    },
+    worldgen,
 };
 
-const GREEN: (u8, u8, u8) = (0, 170, 0);
 const GRAY: (u8, u8, u8) = (150, 150, 150);
 const DARK_GRAY: (u8, u8, u8) = (100, 100, 100);
-const WHITE: (u8, u8, u8) = (255, 255, 255);
+
+/// Smallest window the game can be laid out in
+const MIN_WINDOW_SIZE: (u32, u32) = (40, 20);
+
+/// Chance out of 100 of spotting each hidden trap in view every turn
+const SPOT_TRAP_CHANCE: u32 = 20;
+
+/// Most turns a single rest can last
+const MAX_REST_TURNS: u32 = 200;
+/// Resting stops once hunger falls this low
+const REST_HUNGER: u32 = 20;
+
+/// How far away the player can make out tiles lit by a light source
+const LIT_SIGHT: i32 = 20;
+
+/// Size of the inside of the minimap box
+const MINIMAP_SIZE: (i32, i32) = (20, 10);
+
+/// Fastest the world can be made to move on its own in real-time mode
+const MAX_TICKS_PER_SECOND: f32 = 20.0;
+
+/// How many of the first items in the inventory the number keys use directly
+const HOTBAR_SLOTS: usize = 9;
+
+const FOOD_NUTRITION: u32 = 50;
+const POTION_HEALING: i32 = 8;
+/// How many turns a potion of poison keeps hurting for
+const POTION_POISON: u32 = 6;
+const POTION_STRENGTH: u32 = 2;
+
+const SAVE_PATH: &str = "save.json";
+const KEYBINDINGS_PATH: &str = "keybindings.toml";
+const THEME_PATH: &str = "theme.toml";
+const CONFIG_PATH: &str = "config.toml";
 
 #[derive(Clone, Debug)]
 pub enum CurrentState {
     Menu,
     Playing,
+    Help,
+    Settings,
+    Bestiary,
     Quitting,
+    Dead,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum InventoryAction {
+    Equip,
+    Drop,
+    Eat,
+    Quaff,
+    Throw,
+}
+
+impl InventoryAction {
+    fn prompt(self) -> &'static str {
+        match self {
+            InventoryAction::Equip => "Equip which item?",
+            InventoryAction::Drop => "Drop which item?",
+            InventoryAction::Eat => "Eat which item?",
+            InventoryAction::Quaff => "Drink which item?",
+            InventoryAction::Throw => "Throw which item?",
+        }
+    }
+}
+
+/// What picking a tile with the reticle does
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Aim {
+    Fire,
+    /// Throw the inventory item at this index
+    Throw(usize),
+}
+
+/// Something the player has to agree to before it happens
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Confirm {
+    Quit,
+    DropAll,
+}
+
+impl Confirm {
+    fn prompt(self) -> &'static str {
+        match self {
+            Confirm::Quit => "Are you sure you want to quit?",
+            Confirm::DropAll => "Drop everything you're carrying?",
+        }
+    }
+
+    fn cancelled(self) -> &'static str {
+        match self {
+            Confirm::Quit => "Quit cancelled.",
+            Confirm::DropAll => "You keep hold of everything.",
+        }
+    }
 }
 
 add_wasm_support!();
 
 pub struct State {
     curr_state: CurrentState,
+    menu: StartMenu,
     world: World,
+    grid: SpatialGrid,
+    levels: LevelManager,
     player: Player,
     inventory: Inventory,
+    potions: Potions,
+    inventory_action: Option<InventoryAction>,
+    confirm: Option<Confirm>,
     game_events: GameEvents,
+    key_bindings: KeyBindings,
+    theme: Theme,
+    shop: Shop,
+    shopping: bool,
+    looking: Option<Point>,
+    aim: Option<Aim>,
+    travel: Vec<Point>,
+    /// The down stairs to take once the planned walk ends on them
+    descending: Option<Point>,
+    /// Whether the next movement key starts a run in its direction
+    running: bool,
+    /// How many items the player knew of when they last auto-explored a step,
+    /// while auto-exploring
+    exploring: Option<usize>,
+    light: LightMap,
+    scent: ScentMap,
+    spawner: SpawnController,
+    /// Who acts when, going by how fast the player and each enemy are
+    scheduler: TurnScheduler<Actor>,
+    /// Damage numbers hanging over whatever was just hurt
+    floating: FloatingTexts,
+    /// Exploring steps that can be taken back, cleared by anything else that takes a turn
+    undo: UndoHistory,
+    show_minimap: bool,
+    /// Options in effect, from the config file, the command line and the settings screen
+    settings: Config,
+    /// Options as kept in the config file, changed only on the settings screen
+    saved_settings: Config,
+    /// Which row of the settings screen is highlighted
+    setting: usize,
+    /// How far down the bestiary is scrolled
+    bestiary_scroll: usize,
+    /// How long the world waits for the player before taking a turn on its own,
+    /// in real-time mode
+    realtime: Option<Duration>,
+    /// How long it has been since the last turn, in real-time mode
+    since_turn: Duration,
     window_size: (u32, u32),
+    layout_config: LayoutConfig,
+    layout: Layout,
     tic: u8,
-    offset: (i32, i32),
     mouse: Point,
     mouse_click: Option<(usize, bool)>,
     mouse_pressed: (usize, bool),
     cursor: String,
+    rng: GameRng,
 }
 
 impl State {
-    pub fn new(w: u32, h: u32) -> Self {
-        let mut world = World::default();
+    /// Start at the start menu, with a fresh game from `seed` ready behind it
+    /// and the saved game on offer unless a `seed` is given.
+    /// Events are also written to the file at `log` if there is one.
+    pub fn new(w: u32, h: u32, seed: Option<u64>, log: Option<&str>) -> Self {
+        let mut state = Self::headless(w, h, seed.unwrap_or_else(|| GameRng::from_clock().seed()));
+        state.menu = StartMenu::new(seed.is_none() && Path::new(SAVE_PATH).exists());
 
-        let positions = vec![
-            (
-                GameCell::new(
-                    Point::new(4, 3),
-                    CellKind::EdgedWeapon,
-                    "sword",
-                    RGB::from_u8(0, 0, 255),
-                    CellAccess::Takeable,
-                ),
-                CellVisibility::Unvisited,
-            ),
-            (
-                GameCell::new(
-                    Point::new(7, 2),
-                    CellKind::SoftArmor,
-                    "leather armor",
-                    RGB::from_u8(150, 75, 0),
-                    CellAccess::Takeable,
-                ),
-                CellVisibility::Unvisited,
-            ),
-        ];
-        world.extend(positions);
-
-        let map = vec![
-            "#.########",
-            "#.##......",
-            "#.##.#####",
-            "#.##...###",
-            "#.####.###",
-            "#......###",
-            "##########",
-        ];
-        let mut positions = Vec::with_capacity(70);
-        for (y, row) in map.iter().rev().enumerate() {
-            for (x, c) in row.chars().enumerate() {
-                if c == '#' {
-                    positions.push((
-                        GameCell::new(
-                            Point::new(x as i32 + 10, y as i32 + 5),
-                            CellKind::Wall,
-                            "wall",
-                            RGB::named(GRAY),
-                            CellAccess::Impassable,
-                        ),
-                        CellVisibility::Unvisited,
-                    ));
-                } else {
-                    positions.push((
-                        GameCell::new(
-                            Point::new(x as i32 + 10, y as i32 + 5),
-                            CellKind::Floor,
-                            "floor",
-                            RGB::named(GRAY),
-                            CellAccess::Static,
-                        ),
-                        CellVisibility::Unvisited,
-                    ));
-                }
-            }
+        if let Some(path) = log {
+            state.game_events.open_log(path);
         }
-        world.extend(positions);
 
-        let mut positions = Vec::with_capacity(70);
-        for x in 0..100 {
-            positions.push((
-                GameCell::new(
-                    Point::new(x as i32, 17),
-                    CellKind::Wall,
-                    "wall",
-                    RGB::from_u8(150, 150, 150),
-                    CellAccess::Impassable,
-                ),
-                CellVisibility::Unvisited,
-            ));
+        let (key_bindings, warnings) = KeyBindings::load(KEYBINDINGS_PATH);
+        state.key_bindings = key_bindings;
+        for warning in warnings {
+            state.game_events.post_event(warning, EventKind::Warning);
         }
-        world.extend(positions);
 
-        let mut positions = Vec::with_capacity(70);
-        for y in 0..100 {
-            positions.push((
-                GameCell::new(
-                    Point::new(-17, y as i32),
-                    CellKind::Tunnel,
-                    "tunnel",
-                    RGB::from_u8(150, 150, 150),
-                    CellAccess::Static,
-                ),
-                CellVisibility::Unvisited,
-            ));
+        let (theme, warnings) = Theme::load(THEME_PATH);
+        state.theme = theme;
+        for warning in warnings {
+            state.game_events.post_event(warning, EventKind::Warning);
         }
-        world.extend(positions);
+
+        let (settings, warnings) = Config::load(CONFIG_PATH);
+        state.saved_settings = settings.clone();
+        state.apply_settings(settings);
+        for warning in warnings {
+            state.game_events.post_event(warning, EventKind::Warning);
+        }
+
+        state
+    }
+
+    /// Pick the saved game back up, keeping the fresh game and posting a warning
+    /// if it can't be read
+    fn load_game(&mut self) {
+        let saved = match persistence::load(SAVE_PATH) {
+            Ok(saved) => saved,
+            Err(e) => {
+                if e.kind() != io::ErrorKind::NotFound {
+                    self.game_events.post_event(
+                        format!("Could not load the saved game: {}", e),
+                        EventKind::Warning,
+                    );
+                }
+                return;
+            }
+        };
+
+        self.rng = GameRng::new(saved.seed);
+        self.world = saved.world;
+        self.player = saved.player;
+        self.inventory = saved.inventory;
+        self.game_events.restore(saved.game_events);
+        if let Some(potions) = saved.potions {
+            self.potions = potions;
+        }
+        self.levels = LevelManager::restore(saved.seed, saved.depth, saved.levels);
+        self.grid.rebuild(&self.world);
+        self.game_events.set_turn(self.player.turns());
+    }
+
+    /// Throw away the game waiting behind the start menu for a fresh one from `seed`
+    fn restart(&mut self, seed: u64) {
+        let fresh = Self::headless(self.window_size.0, self.window_size.1, seed);
+        self.world = fresh.world;
+        self.grid = fresh.grid;
+        self.levels = fresh.levels;
+        self.player = fresh.player;
+        self.inventory = fresh.inventory;
+        self.potions = fresh.potions;
+        self.rng = fresh.rng;
+    }
+
+    /// Start a fresh game from `seed` without reading any files,
+    /// so it can be driven with `step` and no terminal
+    pub fn headless(w: u32, h: u32, seed: u64) -> Self {
+        let levels = LevelManager::new(seed, 0);
+        let (world, start) = levels.generate();
+        let mut rng = GameRng::new(seed);
+        let potions = Potions::new(&mut rng);
 
         Self {
             curr_state: CurrentState::Menu,
+            menu: StartMenu::new(false),
+            grid: SpatialGrid::new(&world),
             world,
-            player: Player::new(Point::new(w as i32 / 2, h as i32 / 2)),
+            levels,
+            player: Player::new(start),
             inventory: Inventory::new(),
+            potions,
+            inventory_action: None,
+            confirm: None,
             game_events: GameEvents::new(),
+            key_bindings: KeyBindings::default(),
+            theme: Theme::default(),
+            shop: Shop::new(),
+            shopping: false,
+            looking: None,
+            aim: None,
+            travel: Vec::new(),
+            descending: None,
+            running: false,
+            exploring: None,
+            light: LightMap::default(),
+            scent: ScentMap::default(),
+            spawner: SpawnController::default(),
+            scheduler: TurnScheduler::default(),
+            floating: FloatingTexts::default(),
+            undo: UndoHistory::default(),
+            show_minimap: true,
+            settings: Config::default(),
+            saved_settings: Config::default(),
+            setting: 0,
+            bestiary_scroll: 0,
+            realtime: None,
+            since_turn: Duration::default(),
             window_size: (w, h),
+            layout_config: LayoutConfig::default(),
+            layout: Layout::new(&LayoutConfig::default(), (w, h)),
             tic: 0,
-            offset: (0, 0),
             mouse: Point::new(0, 0),
             mouse_click: None,
             mouse_pressed: (0, false),
             cursor: String::from("<"),
+            rng,
+        }
+    }
+
+    /// Put the options in `settings` into effect
+    pub fn apply_settings(&mut self, settings: Config) {
+        self.set_realtime(settings.realtime);
+        self.floating.set_lifetime(settings.float_lifetime);
+        self.floating.set_offset(settings.float_offset);
+        self.settings = settings;
+        self.player.set_sight(self.settings.fov_radius);
+    }
+
+    pub fn set_bell(&mut self, bell: bool) {
+        self.settings.bell = bell;
+    }
+
+    /// Offer the kit in `loadout` first when a new game is started from the menu
+    pub fn start_with(&mut self, loadout: Loadout) {
+        self.menu.preselect(loadout);
+    }
+
+    /// Draw with the high-contrast symbol set, and with items as letters if `letters`
+    pub fn set_symbols(&mut self, high_contrast: bool, letters: bool) {
+        self.theme.set_high_contrast(high_contrast);
+        self.theme.set_letters(letters);
+    }
+
+    /// Let enemies take a free attack at the player for stepping away from them
+    pub fn set_opportunity_attacks(&mut self, opportunity_attacks: bool) {
+        self.settings.opportunity_attacks = opportunity_attacks;
+    }
+
+    /// Let the world take a turn on its own `ticks_per_second` times a second
+    /// whenever the player doesn't act first, up to `MAX_TICKS_PER_SECOND`.
+    /// The game stays turn-based unless this is called.
+    pub fn set_realtime(&mut self, ticks_per_second: f32) {
+        self.settings.realtime = ticks_per_second.max(0.0).min(MAX_TICKS_PER_SECOND);
+        self.realtime = if ticks_per_second > 0.0 {
+            Some(Duration::from_secs_f32(
+                1.0 / ticks_per_second.min(MAX_TICKS_PER_SECOND),
+            ))
+        } else {
+            None
+        };
+    }
+
+    /// Count `elapsed` toward the next turn in real-time mode, and pass a turn
+    /// once the player has let a whole tick go by.
+    /// Nothing happens while a prompt or menu is waiting on the player.
+    fn advance_time(&mut self, elapsed: Duration) {
+        let tick = match self.realtime {
+            Some(tick) => tick,
+            None => return,
+        };
+        if self.confirm.is_some()
+            || self.inventory_action.is_some()
+            || self.shopping
+            || self.looking.is_some()
+            || self.running
+        {
+            return;
+        }
+
+        self.since_turn += elapsed;
+        if self.since_turn >= tick {
+            self.commit_turn();
+        }
+    }
+
+    /// Ring the terminal bell if that's turned on
+    fn ring(&self) {
+        if self.settings.bell {
+            feedback::beep();
+        }
+    }
+
+    pub fn player(&self) -> &Player {
+        &self.player
+    }
+
+    pub fn inventory(&self) -> &Inventory {
+        &self.inventory
+    }
+
+    pub fn game_events(&self) -> &GameEvents {
+        &self.game_events
+    }
+
+    /// Return a hash of the current level, equal for any two identical maps
+    pub fn fingerprint(&self) -> u64 {
+        worldgen::world_fingerprint(&self.world)
+    }
+
+    /// Write out the whole level as text to the file at `path`,
+    /// or as an image if it ends in `.png`
+    pub fn export_map(&self, path: &str) -> io::Result<()> {
+        if path.ends_with(".png") {
+            export::png(&self.world, &self.theme, self.player.point(), path)
+        } else {
+            fs::write(
+                path,
+                export::ascii(&self.world, &self.theme, self.player.point()),
+            )
+        }
+    }
+
+    /// Return the seed the game was started from
+    pub fn seed(&self) -> u64 {
+        self.rng.seed()
+    }
+
+    /// Return what to add to a world point to draw it on screen.
+    ///
+    /// The view always scrolls so the player is on the middle tile of the canvas,
+    /// so this is worked out from the player and the layout every time it's needed
+    /// rather than kept up to date as the player moves.
+    fn offset(&self) -> (i32, i32) {
+        let center = self.layout.center();
+        (center.x - self.player.x(), center.y - self.player.y())
+    }
+
+    /// Lay the game out for a window of `w` by `h`
+    fn resize(&mut self, w: u32, h: u32) {
+        if (w, h) == self.window_size {
+            return;
         }
+        self.window_size = (w, h);
+        self.layout = Layout::new(&self.layout_config, self.window_size);
     }
 
     fn menu_state(&mut self, ctx: &mut BTerm) {
-        ctx.print_centered(self.window_size.1 as i32 / 2 - 1, "Blademaster");
-        ctx.print_centered(
-            self.window_size.1 as i32 / 2 + 1,
-            "Press the spacebar to start",
-        );
+        self.menu.print(ctx, self.window_size);
 
-        if let Some(VirtualKeyCode::Space) = ctx.key {
-            self.curr_state = CurrentState::Playing;
+        let choice = match ctx.key {
+            Some(key) => self.menu.input(key),
+            None => None,
+        };
+        match choice {
+            Some(StartChoice::NewGame(seed, loadout)) => {
+                if let Some(seed) = seed {
+                    self.restart(seed);
+                }
+                loadout.equip(&mut self.inventory);
+                self.curr_state = CurrentState::Playing;
+            }
+            Some(StartChoice::LoadGame) => {
+                self.load_game();
+                self.curr_state = CurrentState::Playing;
+            }
+            Some(StartChoice::Quit) => self.curr_state = CurrentState::Quitting,
+            None => (),
         }
     }
 
@@ -173,221 +503,2451 @@ impl State {
         self.render_cells(ctx);
 
         ctx.print_color(
-            self.window_size.0 as i32 / 2,
-            self.window_size.1 as i32 / 2,
+            self.player.x() + self.offset().0,
+            self.player.y() + self.offset().1,
             RGB::named((0, 255, 0)),
             RGB::new(),
             "@",
         );
 
-        self.game_events.print(ctx, self.window_size);
-        self.player.print_info(ctx, self.window_size);
-        self.inventory.print(ctx, self.window_size);
+        if let Some(point) = self.looking {
+            if self.aim.is_some() {
+                let landing = self.shot_landing(point);
+                for step in los::line(self.player.point(), landing).into_iter().skip(1) {
+                    ctx.print_color(
+                        step.x + self.offset().0,
+                        step.y + self.offset().1,
+                        RGB::named((255, 100, 0)),
+                        RGB::new(),
+                        "*",
+                    );
+                }
+            }
+            ctx.print_color(
+                point.x + self.offset().0,
+                point.y + self.offset().1,
+                RGB::named((255, 255, 0)),
+                RGB::new(),
+                "X",
+            );
+        }
+
+        self.render_compass(ctx);
+        self.render_minimap(ctx);
+
+        self.game_events.print(ctx, self.layout.events);
+        self.player
+            .print_info(ctx, self.layout.info, self.inventory.len());
+        self.render_hotbar(ctx);
+        self.inventory.print(
+            ctx,
+            self.layout.inventory,
+            self.inventory_action.map(InventoryAction::prompt),
+        );
+        if self.shopping {
+            self.shop.print(ctx, self.window_size, self.player.gold());
+        }
+        if let Some(confirm) = self.confirm {
+            draw_confirm(ctx, self.window_size, confirm.prompt());
+        }
+
+        self.floating.print(ctx, self.view(), self.offset());
 
         self.discover_cells();
 
-        self.take_items();
-
-        /*match self.mouse_click {
-            _ => (),
-        }*/
+        let enemies = self.enemy_health();
+        let health = self.player.health();
+        if let Some((0, true)) = self.mouse_click {
+            if self.confirm.is_none()
+                && self.inventory_action.is_none()
+                && !self.shopping
+                && self.looking.is_none()
+            {
+                self.click_tile(Point::new(
+                    self.mouse.x - self.offset().0,
+                    self.mouse.y - self.offset().1,
+                ));
+            }
+        }
 
-        self.key_input(ctx);
+        if self.exploring.is_some() && self.travel.is_empty() && ctx.key.is_none() {
+            self.explore_step();
+        }
+        if self.travel.is_empty() {
+            if ctx.key.is_some() && self.exploring.take().is_some() {
+                self.game_events
+                    .post_event(String::from("You stop exploring."), EventKind::Info);
+            } else {
+                self.key_input(ctx);
+            }
+        } else if ctx.key.is_some() {
+            self.travel.clear();
+            self.descending = None;
+            self.game_events.post_event(
+                String::from(if self.exploring.take().is_some() {
+                    "You stop exploring."
+                } else {
+                    "You stop travelling."
+                }),
+                EventKind::Info,
+            );
+        } else {
+            self.travel_step();
+        }
+        self.advance_time(Duration::from_secs_f32(ctx.frame_time_ms / 1000.0));
+        if self.player.health() < health {
+            self.ring();
+        }
+        self.float_damage(&enemies, health);
 
-        self.player.default_sight();
+        self.player.set_sight(self.settings.fov_radius);
     }
 
     fn key_input(&mut self, ctx: &mut BTerm) {
         if let Some(key) = ctx.key {
-            match key {
-                VirtualKeyCode::Up
-                | VirtualKeyCode::Down
-                | VirtualKeyCode::Left
-                | VirtualKeyCode::Right => {
-                    self.move_player(key);
+            if let Some(confirm) = self.confirm {
+                self.confirm_input(confirm, key);
+                return;
+            }
+            if let Some(action) = self.inventory_action {
+                self.select_item(action, key);
+                return;
+            }
+            if self.shopping {
+                self.buy_item(key);
+                return;
+            }
+            if self.looking.is_some() {
+                self.look_input(key);
+                return;
+            }
+            if self.running {
+                self.running = false;
+                if let Some((dx, dy)) = self.key_bindings.action(key).and_then(Action::direction) {
+                    self.run(dx, dy);
+                }
+                return;
+            }
+            // Browsing the open inventory takes over moving up and down
+            if self.inventory.is_shown() {
+                match self.key_bindings.action(key) {
+                    Some(Action::MoveUp) => {
+                        self.inventory.select_prev();
+                        return;
+                    }
+                    Some(Action::MoveDown) => {
+                        self.inventory.select_next();
+                        return;
+                    }
+                    _ if key == VirtualKeyCode::Escape => {
+                        self.inventory.toggle();
+                        return;
+                    }
+                    _ if key == VirtualKeyCode::Tab => {
+                        self.inventory.next_category();
+                        return;
+                    }
+                    _ => (),
+                }
+            }
+            match self.key_bindings.action(key) {
+                Some(action) => self.step(action),
+                None => {
+                    if let Some(slot) = hotbar_slot(key) {
+                        self.use_hotbar(slot);
+                    }
                 }
-                VirtualKeyCode::I => self.inventory.toggle(),
-                VirtualKeyCode::End => self.curr_state = CurrentState::Quitting,
-                _ => (),
             }
         }
     }
 
-    fn move_player(&mut self, key: VirtualKeyCode) {
-        let mut a = 0;
-        let mut b = 0;
+    /// Eat, drink or equip the item in hotbar `slot`, one of the first few in the inventory.
+    /// An empty slot or an item with no use does nothing.
+    pub fn use_hotbar(&mut self, slot: usize) {
+        if slot >= HOTBAR_SLOTS {
+            return;
+        }
+        let kind = match self.inventory.get(slot) {
+            Some(item) => item.kind(),
+            None => return,
+        };
+        let acted = match kind {
+            CellKind::Food => self.eat_item(slot),
+            CellKind::Potion => self.quaff_item(slot),
+            _ if kind.equip_slot().is_some() => self.equip_item(slot),
+            _ => false,
+        };
+        if acted {
+            self.commit_turn();
+        }
+    }
 
-        match key {
-            VirtualKeyCode::Up => {
-                a = 0;
-                b = 1;
+    /// Carry out a single player action, independent of any terminal input
+    pub fn step(&mut self, action: Action) {
+        if let Some((dx, dy)) = action.direction() {
+            let snapshot = self.snapshot(dx, dy);
+            if self.move_player(dx, dy) {
+                self.end_turn();
+                // Creeping along takes twice as long
+                if self.player.sneaking() {
+                    self.end_turn();
+                }
+                match snapshot {
+                    Some(snapshot) if self.undoable_since(&snapshot) => self.undo.push(snapshot),
+                    _ => self.undo.clear(),
+                }
+            }
+            return;
+        }
+        match action {
+            Action::CloseDoor => {
+                if self.close_doors() {
+                    self.commit_turn();
+                }
             }
-            VirtualKeyCode::Down => {
-                a = 0;
-                b = -1;
+            Action::Descend => {
+                if self.take_stairs(CellKind::StairsDown) {
+                    self.commit_turn();
+                }
             }
-            VirtualKeyCode::Left => {
-                a = 1;
-                b = 0;
+            Action::Ascend => {
+                if self.take_stairs(CellKind::StairsUp) {
+                    self.commit_turn();
+                }
             }
-            VirtualKeyCode::Right => {
-                a = -1;
-                b = 0;
+            Action::Inventory => self.inventory.toggle(),
+            Action::Minimap => self.show_minimap = !self.show_minimap,
+            Action::ScrollUp => self.game_events.scroll_up(),
+            Action::ScrollDown => self.game_events.scroll_down(),
+            Action::Save => self.save(),
+            Action::Help => self.curr_state = CurrentState::Help,
+            Action::Settings => self.curr_state = CurrentState::Settings,
+            Action::Bestiary => self.curr_state = CurrentState::Bestiary,
+            Action::Equip => self.inventory_action = Some(InventoryAction::Equip),
+            Action::Drop => self.inventory_action = Some(InventoryAction::Drop),
+            Action::Eat => self.inventory_action = Some(InventoryAction::Eat),
+            Action::Throw => self.inventory_action = Some(InventoryAction::Throw),
+            Action::Quaff => self.inventory_action = Some(InventoryAction::Quaff),
+            Action::Look => {
+                self.looking = Some(self.player.point());
+                self.game_events
+                    .post_event(self.describe(self.player.point()), EventKind::Info);
             }
-            _ => (),
-        }
-
-        let mut query = <(Read<GameCell>,)>::query();
-
-        let mut collided = false;
-        for (cell,) in query.iter(&self.world) {
-            if cell.access() == CellAccess::Impassable
-                && self.player.x() == cell.x() + a
-                && self.player.y() == cell.y() + b
-            {
-                self.game_events.post_event(
-                    format!("You ran into the {}.", cell.name()),
-                    RGB::named(WHITE),
+            Action::Fire => {
+                let ranged = self
+                    .inventory
+                    .equipped(EquipSlot::Weapon)
+                    .map_or(false, |weapon| weapon.kind() == CellKind::RangedWeapon);
+                if ranged {
+                    self.looking = Some(self.player.point());
+                    self.aim = Some(Aim::Fire);
+                    self.game_events.post_event(
+                        String::from("Pick a target and press enter."),
+                        EventKind::Info,
+                    );
+                } else {
+                    self.game_events
+                        .post_event(String::from("You have nothing to fire."), EventKind::Info);
+                }
+            }
+            Action::Swipe => {
+                if !CombatSystem::swipe(
+                    &mut self.world,
+                    &mut self.grid,
+                    &mut self.player,
+                    &mut self.inventory,
+                    &mut self.game_events,
+                    &mut self.rng,
+                ) {
+                    self.curr_state = CurrentState::Dead;
+                }
+                self.commit_turn();
+            }
+            Action::Rest => self.rest(),
+            Action::Search => {
+                let found = SearchSystem::run(
+                    &mut self.world,
+                    &self.grid,
+                    &self.player,
+                    &mut self.game_events,
+                    &mut self.rng,
                 );
-                collided = true;
-                break;
+                if found == 0 {
+                    self.game_events.post_event(
+                        String::from("You search but find nothing."),
+                        EventKind::Info,
+                    );
+                }
+                self.commit_turn();
             }
-        }
-        if !collided {
-            self.offset.0 += a;
-            self.offset.1 += b;
-            self.player.move_pos(-a, -b);
-        }
-    }
-
-    fn render_cells(&mut self, ctx: &mut BTerm) {
-        let mut query = <(Read<GameCell>, Read<CellVisibility>)>::query();
-
-        for (cell, visible) in query.iter(&self.world) {
-            if *visible != CellVisibility::Unvisited
-                && Rect::with_exact(
-                    -self.offset.0,
-                    -self.offset.1,
-                    self.window_size.0 as i32 - self.offset.0,
-                    self.window_size.1 as i32 - self.offset.1,
-                )
-                .point_in_rect(cell.point())
-            {
-                if Rect::with_exact(
-                    self.player.x() - self.player.sight().0,
-                    self.player.y() - self.player.sight().1,
-                    self.player.x() + self.player.sight().2,
-                    self.player.y() + self.player.sight().3,
-                )
-                .point_in_rect(cell.point())
-                {
-                    ctx.print_color(
-                        cell.x() + self.offset.0,
-                        cell.y() + self.offset.1,
-                        if self.mouse.x - self.offset.0 == cell.x()
-                            && self.mouse.y - self.offset.1 == cell.y()
-                        {
-                            cell.color_bright()
-                        } else {
-                            cell.color()
-                        },
-                        cell.bg_color(),
-                        &cell.symbol().to_string(),
+            Action::Wait => self.commit_turn(),
+            Action::Undo => self.undo_move(),
+            Action::Sneak => {
+                let message = if self.player.toggle_sneak() {
+                    "You start sneaking."
+                } else {
+                    "You stop sneaking."
+                };
+                self.game_events
+                    .post_event(String::from(message), EventKind::Info);
+            }
+            Action::Run => {
+                self.running = true;
+                self.game_events
+                    .post_event(String::from("Run which way?"), EventKind::Info);
+            }
+            Action::Explore => {
+                if self.hostile_in_view() {
+                    self.game_events.post_event(
+                        String::from("You can't explore with enemies in view."),
+                        EventKind::Warning,
                     );
                 } else {
-                    ctx.print_color(
-                        cell.x() + self.offset.0,
-                        cell.y() + self.offset.1,
-                        RGB::named(DARK_GRAY),
-                        cell.bg_color(),
-                        &cell.symbol().to_string(),
+                    self.exploring = Some(self.known_items());
+                }
+            }
+            Action::Travel => {
+                self.looking = Some(self.player.point());
+                self.game_events.post_event(
+                    String::from("Pick a destination and press enter."),
+                    EventKind::Info,
+                );
+            }
+            Action::TravelToStairs => self.travel_to_stairs(),
+            Action::PickUp | Action::PickUpAll => {
+                if self.take_items() {
+                    self.commit_turn();
+                } else {
+                    self.game_events.post_event(
+                        String::from("There is nothing here to pick up."),
+                        EventKind::Info,
                     );
                 }
             }
+            Action::DropAll => {
+                if self.inventory.is_empty() {
+                    self.game_events
+                        .post_event(String::from("You have nothing to drop."), EventKind::Info);
+                } else {
+                    self.confirm = Some(Confirm::DropAll);
+                }
+            }
+            Action::Quit => self.confirm = Some(Confirm::Quit),
+            _ => (),
         }
     }
 
-    fn discover_cells(&mut self) {
-        let mut query = <(Read<GameCell>, Write<CellVisibility>)>::query();
+    /// Take a step toward the visible tile at `target`, which bumps into
+    /// whatever is there when it's next to the player.
+    /// Clicking the player's own tile picks up what is lying there.
+    fn click_tile(&mut self, target: Point) {
+        if target == self.player.point() {
+            self.take_items();
+            return;
+        }
 
-        for (cell, visible) in query.iter_mut(&mut self.world) {
-            if Rect::with_exact(
-                self.player.x() - self.player.sight().0,
-                self.player.y() - self.player.sight().1,
-                self.player.x() + self.player.sight().2,
-                self.player.y() + self.player.sight().3,
-            )
-            .point_in_rect(cell.point())
-            {
-                *visible = CellVisibility::Visible;
-            } else if *visible == CellVisibility::Visible {
-                *visible = CellVisibility::Dark;
+        let mut query = <(Read<GameCell>, Read<CellVisibility>)>::query();
+
+        let visible = query
+            .iter(&self.world)
+            .any(|(cell, visible)| cell.point() == target && *visible == CellVisibility::Visible);
+        if !visible {
+            return;
+        }
+
+        let dx = target.x - self.player.x();
+        let dy = target.y - self.player.y();
+        if dx.abs() > 1 || dy.abs() > 1 {
+            self.start_travel(target);
+        } else if self.move_player(dx, dy) {
+            self.commit_turn();
+        }
+    }
+
+    /// Plan a walk to `target` to be taken a step per frame
+    fn start_travel(&mut self, target: Point) {
+        if self.hostile_in_view() {
+            self.game_events.post_event(
+                String::from("You can't travel with enemies in view."),
+                EventKind::Warning,
+            );
+            return;
+        }
+        match path::pathfind(&self.world, self.player.point(), target) {
+            Some(path) if !path.is_empty() => {
+                self.descending = None;
+                self.travel = path.into_iter().rev().collect();
             }
+            Some(_) => (),
+            None => self
+                .game_events
+                .post_event(String::from("You can't find a way there."), EventKind::Info),
         }
     }
 
-    fn take_items(&mut self) {
-        let mut query = <(Read<GameCell>,)>::query();
+    /// Plan a walk to the closest down stairs the player knows of, to be taken
+    /// once the walk gets there
+    fn travel_to_stairs(&mut self) {
+        let mut query = <(Read<GameCell>, Read<CellVisibility>)>::query();
 
-        let mut taken = None;
-        for chunk in query.iter_chunks(&self.world) {
-            for (entity, (cell,)) in chunk.into_iter_entities() {
-                if cell.access() == CellAccess::Takeable
-                    && self.player.x() == cell.x()
-                    && self.player.y() == cell.y()
-                {
-                    self.game_events.post_event(
-                        format!("You now have the {}.", cell.name()),
-                        RGB::named(GREEN),
-                    );
-                    self.inventory.take((*cell).clone());
-                    taken = Some(entity);
-                    break;
-                }
+        let stairs = query
+            .iter(&self.world)
+            .filter(|(cell, visible)| {
+                cell.kind() == CellKind::StairsDown && **visible != CellVisibility::Unvisited
+            })
+            .map(|(cell, _)| cell.point())
+            .collect::<Vec<_>>();
+        if stairs.is_empty() {
+            self.game_events.post_event(
+                String::from("You don't know where the stairs are."),
+                EventKind::Info,
+            );
+            return;
+        }
+        if stairs.contains(&self.player.point()) {
+            if self.take_stairs(CellKind::StairsDown) {
+                self.commit_turn();
             }
+            return;
         }
-        if let Some(entity) = taken {
-            self.world.remove(entity);
+        if self.hostile_in_view() {
+            self.game_events.post_event(
+                String::from("You can't travel with enemies in view."),
+                EventKind::Warning,
+            );
+            return;
         }
-    }
-
-    fn quit_state(&mut self, ctx: &mut BTerm) {
-        ctx.print(5, 5, "Are you sure you want to quit? (y/n)");
 
-        if let Some(VirtualKeyCode::Y) = ctx.key {
-            ctx.quit();
-        } else if let Some(VirtualKeyCode::N) = ctx.key {
-            self.curr_state = CurrentState::Playing;
+        let closest = stairs
+            .into_iter()
+            .filter_map(|point| path::pathfind(&self.world, self.player.point(), point))
+            .filter(|path| !path.is_empty())
+            .min_by_key(|path| path.len());
+        match closest {
+            Some(path) => {
+                self.descending = path.last().copied();
+                self.travel = path.into_iter().rev().collect();
+            }
+            None => self.game_events.post_event(
+                String::from("You can't find a way to the stairs."),
+                EventKind::Info,
+            ),
         }
     }
-}
-
-impl GameState for State {
-    fn tick(&mut self, ctx: &mut BTerm) {
-        ctx.cls();
 
-        let mut input = INPUT.lock();
+    /// Plan the next step toward the closest explored tile next to an unexplored one,
+    /// stopping once something new turns up or there's nowhere left to go
+    fn explore_step(&mut self) {
+        let known = self.known_items();
+        if self.exploring.map_or(false, |seen| known > seen) {
+            self.exploring = None;
+            self.game_events
+                .post_event(String::from("You find something."), EventKind::Info);
+            return;
+        }
+        self.exploring = Some(known);
 
-        input.for_each_message(|event| match event {
-            BEvent::MouseClick { button, pressed } => self.mouse_click = Some((button, pressed)),
-            BEvent::MouseButtonUp { button } => self.mouse_pressed = (button, false),
-            BEvent::MouseButtonDown { button } => self.mouse_pressed = (button, true),
-            _ => (),
-        });
+        let mut query = <(Read<GameCell>, Read<CellVisibility>)>::query();
 
-        self.tic += 4;
-        if self.tic > 99 {
-            self.tic = 0;
+        let mut explored = HashSet::new();
+        let mut unexplored = HashSet::new();
+        for (cell, visible) in query.iter(&self.world) {
+            if *visible == CellVisibility::Unvisited {
+                unexplored.extend(cell.tiles());
+            } else {
+                explored.extend(cell.tiles());
+            }
+        }
+        let frontier = |point: Point| {
+            explored.contains(&point)
+                && (-1..=1).any(|dx| {
+                    (-1..=1).any(|dy| {
+                        let next = Point::new(point.x + dx, point.y + dy);
+                        unexplored.contains(&next) && !explored.contains(&next)
+                    })
+                })
+        };
+
+        match path::nearest(&self.world, self.player.point(), frontier) {
+            Some(path) if !path.is_empty() => self.travel = vec![path[0]],
+            _ => {
+                self.exploring = None;
+                self.game_events.post_event(
+                    String::from("There's nothing left to explore."),
+                    EventKind::Info,
+                );
+            }
+        }
+    }
+
+    /// Return how many items and piles of gold the player has come across on this level
+    fn known_items(&self) -> usize {
+        let mut query = <(Read<GameCell>, Read<CellVisibility>)>::query();
+
+        query
+            .iter(&self.world)
+            .filter(|(cell, visible)| {
+                (cell.access() == CellAccess::Takeable || cell.kind() == CellKind::Gold)
+                    && **visible != CellVisibility::Unvisited
+            })
+            .count()
+    }
+
+    /// Take the next step of the planned walk, stopping if an enemy shows up
+    /// or something gets in the way, and going down the stairs it was headed for
+    fn travel_step(&mut self) {
+        if self.hostile_in_view() {
+            self.travel.clear();
+            self.exploring = None;
+            self.descending = None;
+            self.game_events.post_event(
+                String::from("You stop, an enemy is in view."),
+                EventKind::Warning,
+            );
+            return;
+        }
+        if let Some(next) = self.travel.pop() {
+            let took_turn = self.move_player(next.x - self.player.x(), next.y - self.player.y());
+            if self.player.point() != next {
+                self.travel.clear();
+            }
+            if took_turn {
+                self.commit_turn();
+            }
+        }
+        if self.travel.is_empty() {
+            if let Some(stairs) = self.descending.take() {
+                if self.player.point() == stairs && self.take_stairs(CellKind::StairsDown) {
+                    self.commit_turn();
+                }
+            }
+        }
+    }
+
+    fn hostile_in_view(&self) -> bool {
+        let mut query = <(Read<GameCell>, Read<CellVisibility>)>::query();
+
+        query.iter(&self.world).any(|(cell, visible)| {
+            cell.access() == CellAccess::Hostile && *visible == CellVisibility::Visible
+        })
+    }
+
+    /// Go through with `confirm` on y, or call it off on n or escape
+    fn confirm_input(&mut self, confirm: Confirm, key: VirtualKeyCode) {
+        match key {
+            VirtualKeyCode::Y => {
+                self.confirm = None;
+                match confirm {
+                    Confirm::Quit => self.curr_state = CurrentState::Quitting,
+                    Confirm::DropAll => {
+                        if self.drop_all() {
+                            self.commit_turn();
+                        }
+                    }
+                }
+            }
+            VirtualKeyCode::N | VirtualKeyCode::Escape => {
+                self.confirm = None;
+                self.game_events
+                    .post_event(String::from(confirm.cancelled()), EventKind::Info);
+            }
+            _ => (),
+        }
+    }
+
+    /// Move the look reticle with the movement keys, travel to it or aim at it
+    /// with enter, or stop looking with escape.
+    /// The view stays put while the reticle moves.
+    fn look_input(&mut self, key: VirtualKeyCode) {
+        if key == VirtualKeyCode::Escape {
+            self.looking = None;
+            self.aim = None;
+            return;
+        }
+        if key == VirtualKeyCode::Return {
+            if let Some(target) = self.looking.take() {
+                match self.aim.take() {
+                    Some(Aim::Fire) => self.fire_at(target),
+                    Some(Aim::Throw(index)) => self.throw_at(index, target),
+                    None => self.start_travel(target),
+                }
+            }
+            return;
+        }
+        let direction = self.key_bindings.action(key).and_then(Action::direction);
+        if let (Some(point), Some((dx, dy))) = (self.looking, direction) {
+            let point = Point::new(point.x + dx, point.y + dy);
+            self.looking = Some(point);
+            self.game_events
+                .post_event(self.describe(point), EventKind::Info);
+        }
+    }
+
+    /// Return where a shot toward `target` stops, at the first wall or enemy in the way
+    fn shot_landing(&mut self, target: Point) -> Point {
+        let (grid, world) = (&self.grid, &mut self.world);
+        los::first_blocked(self.player.point(), target, |point| {
+            grid.any_at(world, point, |cell| {
+                cell.access() == CellAccess::Impassable || cell.access() == CellAccess::Hostile
+            })
+        })
+    }
+
+    /// Fire the wielded ranged weapon toward `target`, hitting the first enemy on the way
+    fn fire_at(&mut self, target: Point) {
+        let landing = self.shot_landing(target);
+        if self.grid.any_at(&mut self.world, landing, |cell| {
+            cell.access() == CellAccess::Hostile
+        }) {
+            CombatSystem::shoot(
+                &mut self.world,
+                &mut self.grid,
+                &mut self.player,
+                &mut self.inventory,
+                &mut self.game_events,
+                landing,
+                &mut self.rng,
+            );
+        } else {
+            self.game_events
+                .post_event(String::from("Your shot hits nothing."), EventKind::Info);
+        }
+        self.commit_turn();
+    }
+
+    /// Throw one of the item at `index` toward `target`.
+    /// It hits the first enemy in the way, or drops short of the first wall.
+    /// Potions shatter, working on any enemy they hit, and anything else lands where it stopped.
+    fn throw_at(&mut self, index: usize, target: Point) {
+        let item = match self.inventory.remove(index) {
+            Some(item) => item,
+            None => return,
+        };
+        self.game_events
+            .post_event(format!("You throw the {}.", item.name()), EventKind::Info);
+
+        let path = los::line(self.player.point(), self.shot_landing(target));
+        let end = path[path.len() - 1];
+        let hit = self.grid.any_at(&mut self.world, end, |cell| {
+            cell.access() == CellAccess::Hostile
+        });
+        let landing = if hit || path.len() == 1 {
+            end
+        } else if self.grid.any_at(&mut self.world, end, |cell| {
+            cell.access() == CellAccess::Impassable
+        }) {
+            path[path.len() - 2]
+        } else {
+            end
+        };
+
+        if item.kind() == CellKind::Potion {
+            self.game_events
+                .post_event(format!("The {} shatters.", item.name()), EventKind::Info);
+            if hit {
+                if let Some(effect) = self.potions.effect(item.color()) {
+                    self.splash(landing, effect);
+                    self.identify_potion(&item);
+                }
+            }
+        } else {
+            if hit {
+                CombatSystem::hurl(
+                    &mut self.world,
+                    &mut self.grid,
+                    &mut self.player,
+                    &mut self.game_events,
+                    landing,
+                    item.damage().unwrap_or_else(|| Dice::new(1, 1)),
+                    &mut self.rng,
+                );
+            }
+            let entity = self
+                .world
+                .push((item.with_point(landing), CellVisibility::Visible));
+            self.grid.insert(landing, entity);
+        }
+
+        self.commit_turn();
+    }
+
+    /// Douse the enemy at `target` in a potion that does `effect`,
+    /// which works on it the way it would on the player
+    fn splash(&mut self, target: Point, effect: PotionEffect) {
+        if effect == PotionEffect::Poison {
+            afflict(
+                &mut self.world,
+                &self.grid,
+                &mut self.game_events,
+                target,
+                Some((Effect::Poison, POTION_POISON)),
+            );
+            return;
+        }
+        for &entity in self.grid.at(target) {
+            let mut entry = match self.world.entry(entity) {
+                Some(entry) => entry,
+                None => continue,
+            };
+            let cell = match entry.get_component_mut::<GameCell>() {
+                Ok(cell) if cell.access() == CellAccess::Hostile => cell,
+                _ => continue,
+            };
+            let looks = match effect {
+                PotionEffect::Heal => {
+                    cell.heal(POTION_HEALING as u32);
+                    "healthier"
+                }
+                PotionEffect::Strength => {
+                    cell.raise_max_health(POTION_STRENGTH);
+                    "stronger"
+                }
+                PotionEffect::Poison => continue,
+            };
+            self.game_events.post_event(
+                format!("The {} looks {}.", cell.name(), looks),
+                EventKind::Warning,
+            );
+        }
+    }
+
+    /// Learn what potions the color of `potion` do, if that isn't known yet,
+    /// renaming every one being carried
+    fn identify_potion(&mut self, potion: &GameCell) {
+        if self.potions.is_identified(potion.color()) {
+            return;
+        }
+        self.potions.identify(potion.color());
+        let name = self.potions.name(potion.color(), &potion.name());
+        self.inventory
+            .rename(CellKind::Potion, potion.color(), &name);
+        self.game_events
+            .post_event(format!("It was a {}.", name), EventKind::Info);
+    }
+
+    /// Describe the most interesting thing the player knows of at `point`
+    fn describe(&self, point: Point) -> String {
+        if point == self.player.point() {
+            return String::from("You see yourself.");
+        }
+
+        let mut query = <(Read<GameCell>, Read<CellVisibility>)>::query();
+
+        let mut seen: Option<&GameCell> = None;
+        for (cell, visible) in query.iter(&self.world) {
+            let known = match visible {
+                CellVisibility::Visible => true,
+                CellVisibility::Dark => cell.access() != CellAccess::Hostile,
+                CellVisibility::Unvisited => false,
+            };
+            if known
+                && !cell.hidden()
+                && cell.occupies(point)
+                && seen.map_or(true, |s| interest(cell.access()) > interest(s.access()))
+            {
+                seen = Some(cell);
+            }
+        }
+
+        match seen {
+            Some(cell) => match cell.health() {
+                Some((cur, max)) if cell.access() == CellAccess::Hostile => format!(
+                    "You see a {} {}.",
+                    cell.label(),
+                    hp_bar(cur, max, HP_BAR_WIDTH)
+                ),
+                _ if cell.kind() == CellKind::Potion => format!(
+                    "You see a {}.",
+                    self.potions.name(cell.color(), &cell.name())
+                ),
+                _ => format!("You see a {}.", cell.label()),
+            },
+            None => String::from("You don't know what is there."),
+        }
+    }
+
+    /// Apply `action` to the inventory item picked with `key`, or cancel with escape
+    fn select_item(&mut self, action: InventoryAction, key: VirtualKeyCode) {
+        if key == VirtualKeyCode::Escape {
+            self.inventory_action = None;
+            return;
+        }
+        let index = letter_to_option(key);
+        if index < 0 || index as usize >= self.inventory.len() {
+            return;
+        }
+        self.inventory_action = None;
+
+        let acted = match action {
+            InventoryAction::Equip => self.equip_item(index as usize),
+            InventoryAction::Drop => self.drop_item(index as usize),
+            InventoryAction::Eat => self.eat_item(index as usize),
+            InventoryAction::Quaff => self.quaff_item(index as usize),
+            InventoryAction::Throw => {
+                self.looking = Some(self.player.point());
+                self.aim = Some(Aim::Throw(index as usize));
+                self.game_events.post_event(
+                    String::from("Pick a target and press enter."),
+                    EventKind::Info,
+                );
+                false
+            }
+        };
+        if acted {
+            self.commit_turn();
+        }
+    }
+
+    /// Return how many tiles away the wielded weapon strikes, 1 when empty-handed
+    fn reach(&self) -> i32 {
+        self.inventory
+            .equipped(EquipSlot::Weapon)
+            .map_or(1, GameCell::reach)
+    }
+
+    /// Save how things stand before stepping by `dx` and `dy`, or `None` if the step
+    /// would fight, unlock or trade with whatever is in the way
+    fn snapshot(&mut self, dx: i32, dy: i32) -> Option<Snapshot> {
+        let target = Point::new(self.player.x() + dx, self.player.y() + dy);
+        let reach = self.reach();
+        if self.grid.any_at(&mut self.world, target, |cell| {
+            cell.access() == CellAccess::Hostile
+                || matches!(
+                    cell.kind(),
+                    CellKind::LockedDoor | CellKind::Shopkeeper | CellKind::Chest
+                )
+        }) || MovementSystem::in_reach(&mut self.world, &self.grid, target, dx, dy, reach)
+            .is_some()
+        {
+            return None;
+        }
+
+        let mut cells = Vec::new();
+        let mut query = <(Read<GameCell>, TryRead<EnemyAI>)>::query();
+        for chunk in query.iter_chunks(&self.world) {
+            for (entity, (cell, enemy)) in chunk.into_iter_entities() {
+                if enemy.is_some() || cell.occupies(target) {
+                    cells.push((entity, cell.clone()));
+                }
+            }
+        }
+
+        Some(Snapshot {
+            player: self.player.clone(),
+            items: self.inventory.item_count(),
+            cells,
+        })
+    }
+
+    /// Return whether nothing that can't be taken back happened since `snapshot`,
+    /// like picking something up or getting hurt
+    fn undoable_since(&self, snapshot: &Snapshot) -> bool {
+        !matches!(self.curr_state, CurrentState::Dead)
+            && self.inventory.item_count() == snapshot.items
+            && self.player.gold() == snapshot.player.gold()
+            && self.player.health() >= snapshot.player.health()
+    }
+
+    /// Take back the last exploring step
+    fn undo_move(&mut self) {
+        match self.undo.pop() {
+            Some(snapshot) => {
+                self.player = snapshot.restore(&mut self.world, &mut self.grid);
+                self.game_events.set_turn(self.player.turns());
+                self.game_events
+                    .post_event(String::from("You retrace your step."), EventKind::Info);
+            }
+            None => self
+                .game_events
+                .post_event(String::from("Can't undo now."), EventKind::Info),
+        }
+    }
+
+    /// End a turn spent on something that can't be taken back,
+    /// forgetting the steps that could have been
+    fn commit_turn(&mut self) {
+        self.undo.clear();
+        self.end_turn();
+    }
+
+    /// Let the rest of the world act after the player has used up their turn
+    fn end_turn(&mut self) {
+        if let CurrentState::Dead = self.curr_state {
+            return;
+        }
+        self.since_turn = Duration::default();
+        self.player.pass_turn();
+        self.game_events.set_turn(self.player.turns());
+        let (grid, world) = (&self.grid, &mut self.world);
+        self.scent.update(self.player.point(), |point| {
+            grid.any_at(world, point, |_| true)
+                && !grid.any_at(world, point, |cell| {
+                    cell.access() == CellAccess::Impassable || cell.kind().is_opaque()
+                })
+        });
+        self.pass_time();
+        if self.spawner.run(
+            &mut self.world,
+            &mut self.grid,
+            self.levels.depth(),
+            self.player.turns(),
+            self.player.point(),
+            &mut self.rng,
+        ) {
+            self.game_events.post_event(
+                String::from("You hear something stirring in the distance."),
+                EventKind::Info,
+            );
+        }
+        RegenSystem::run(&self.world, &mut self.player, &mut self.game_events);
+        StatusSystem::run(&mut self.player, &mut self.game_events);
+        CombatSystem::run_effects(
+            &mut self.world,
+            &mut self.grid,
+            &mut self.player,
+            &mut self.game_events,
+            &mut self.rng,
+        );
+        self.player.recover_stamina();
+        self.spot_traps();
+        if let Some(name) = self.inventory.burn_light() {
+            self.game_events
+                .post_event(format!("Your {} sputters out.", name), EventKind::Warning);
+        }
+
+        if self.player.health() <= 0 {
+            self.game_events.post_event(
+                String::from("You succumb to your ailments."),
+                EventKind::Combat,
+            );
+            let cause = if self.player.effects().has(Effect::Poison) {
+                "Succumbed to poison"
+            } else {
+                "Succumbed to ailments"
+            };
+            self.player.stats_mut().record_death(cause);
+            self.curr_state = CurrentState::Dead;
+            return;
+        }
+
+        if self.player.digest() {
+            self.game_events
+                .post_event(String::from("You are starving!"), EventKind::Warning);
+            if self.player.health() <= 0 {
+                self.game_events
+                    .post_event(String::from("You starved to death."), EventKind::Combat);
+                self.player.stats_mut().record_death("Starved to death");
+                self.curr_state = CurrentState::Dead;
+            }
+        }
+    }
+
+    /// Return how fast the player acts: as fast as the wielded weapon lets them,
+    /// and twice that while hasted
+    fn speed(&self) -> u32 {
+        let speed = self
+            .inventory
+            .equipped(EquipSlot::Weapon)
+            .map_or(NORMAL_SPEED, GameCell::speed);
+        if self.player.effects().has(Effect::Haste) {
+            speed * 2
+        } else {
+            speed
+        }
+    }
+
+    /// Let time pass until the player gets to act again,
+    /// with every enemy taking the turns its speed earns it on the way
+    fn pass_time(&mut self) {
+        let mut query = <(Read<GameCell>, Read<EnemyAI>)>::query();
+
+        let mut actors = vec![(Actor::Player, self.speed().max(1))];
+        for chunk in query.iter_chunks(&self.world) {
+            for (entity, (cell, _)) in chunk.into_iter_entities() {
+                actors.push((Actor::Enemy(entity), cell.speed().max(1)));
+            }
+        }
+
+        loop {
+            let mut player_ready = false;
+            let mut acting = HashSet::new();
+            for actor in self.scheduler.tick(&actors) {
+                match actor {
+                    Actor::Player => player_ready = true,
+                    Actor::Enemy(entity) => {
+                        acting.insert(entity);
+                    }
+                }
+            }
+            if !acting.is_empty() {
+                AiSystem::run(
+                    &mut self.world,
+                    &mut self.grid,
+                    &self.scent,
+                    &self.player,
+                    &acting,
+                    &mut self.rng,
+                );
+            }
+            if player_ready {
+                return;
+            }
+        }
+    }
+
+    /// Run by `dx` and `dy` until something is in the way, something turns up,
+    /// or the player runs out of stamina.
+    /// Every tile after the first costs a point of stamina.
+    fn run(&mut self, dx: i32, dy: i32) {
+        let mut steps = 0;
+        loop {
+            let next = Point::new(self.player.x() + dx, self.player.y() + dy);
+            let open = self.grid.any_at(&mut self.world, next, |_| true)
+                && !self.grid.any_at(&mut self.world, next, |cell| {
+                    matches!(cell.access(), CellAccess::Impassable | CellAccess::Hostile)
+                });
+            if !open {
+                break;
+            }
+            if steps > 0 && !self.player.spend_stamina(1) {
+                self.game_events
+                    .post_event(String::from("You are out of breath."), EventKind::Warning);
+                break;
+            }
+
+            let health = self.player.health();
+            let items = self.inventory.len();
+            if !self.move_player(dx, dy) {
+                break;
+            }
+            self.commit_turn();
+            self.discover_cells();
+            steps += 1;
+
+            let stopped = self.player.health() < health
+                || self.inventory.len() != items
+                || self.hostile_in_view();
+            if stopped || matches!(self.curr_state, CurrentState::Dead) {
+                break;
+            }
+        }
+    }
+
+    /// Pass turns until the player is fully healed, getting hungry,
+    /// or an enemy comes into view, whichever comes first
+    fn rest(&mut self) {
+        for _ in 0..MAX_REST_TURNS {
+            if self.hostile_in_view() {
+                self.game_events.post_event(
+                    String::from("You can't rest with enemies in view."),
+                    EventKind::Warning,
+                );
+                return;
+            }
+            if self.player.health() >= self.player.max_health() as i32
+                || self.player.hunger() <= REST_HUNGER
+            {
+                break;
+            }
+
+            self.commit_turn();
+            self.discover_cells();
+            if let CurrentState::Dead = self.curr_state {
+                return;
+            }
+        }
+        self.game_events
+            .post_event(String::from("You finish resting."), EventKind::Info);
+    }
+
+    /// Eat the food at `index`, returning whether anything was eaten
+    fn eat_item(&mut self, index: usize) -> bool {
+        match self.inventory.get(index) {
+            Some(item) if item.kind() == CellKind::Food => (),
+            Some(item) => {
+                self.game_events.post_event(
+                    format!("You can't eat the {}.", item.name()),
+                    EventKind::Info,
+                );
+                return false;
+            }
+            None => return false,
+        }
+
+        match self.inventory.remove(index) {
+            Some(item) => {
+                self.player.eat(FOOD_NUTRITION);
+                self.game_events
+                    .post_event(format!("You eat the {}.", item.name()), EventKind::Loot);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Drink the potion at `index`, learning what its color does.
+    /// Returns whether anything was drunk.
+    fn quaff_item(&mut self, index: usize) -> bool {
+        match self.inventory.get(index) {
+            Some(item) if item.kind() == CellKind::Potion => (),
+            Some(item) => {
+                self.game_events.post_event(
+                    format!("You can't drink the {}.", item.name()),
+                    EventKind::Info,
+                );
+                return false;
+            }
+            None => return false,
+        }
+        let potion = match self.inventory.remove(index) {
+            Some(potion) => potion,
+            None => return false,
+        };
+
+        self.game_events
+            .post_event(format!("You drink the {}.", potion.name()), EventKind::Info);
+        match self.potions.effect(potion.color()) {
+            Some(PotionEffect::Heal) => {
+                self.player
+                    .set_health(self.player.health() + POTION_HEALING);
+                self.game_events
+                    .post_event(String::from("You feel better."), EventKind::Loot);
+            }
+            Some(PotionEffect::Poison) => {
+                self.player
+                    .effects_mut()
+                    .apply(Effect::Poison, POTION_POISON);
+                self.game_events
+                    .post_event(String::from("You feel sick."), EventKind::Warning);
+            }
+            Some(PotionEffect::Strength) => {
+                self.player.raise_max_health(POTION_STRENGTH);
+                self.game_events
+                    .post_event(String::from("You feel stronger."), EventKind::Loot);
+            }
+            None => (),
+        }
+
+        self.identify_potion(&potion);
+
+        true
+    }
+
+    /// Buy the shop item picked with `key`, or leave the shop with escape
+    fn buy_item(&mut self, key: VirtualKeyCode) {
+        if key == VirtualKeyCode::Escape {
+            self.shopping = false;
+            return;
+        }
+        let index = letter_to_option(key);
+        if index < 0 || index as usize >= self.shop.len() {
+            return;
+        }
+
+        match self
+            .shop
+            .buy(index as usize, &mut self.player, &mut self.inventory)
+        {
+            Ok(msg) => self.game_events.post_event(msg, EventKind::Loot),
+            Err(msg) => self.game_events.post_event(msg, EventKind::Warning),
+        }
+    }
+
+    /// Equip the item at `index`, or take it off if it's already equipped.
+    /// Returns whether anything changed hands.
+    fn equip_item(&mut self, index: usize) -> bool {
+        if let Some(slot) = self.inventory.slot_of(index) {
+            if let Some(item) = self.inventory.unequip(slot) {
+                let verb = match slot {
+                    EquipSlot::Weapon => "put away",
+                    EquipSlot::Armor => "take off",
+                    EquipSlot::OffHand => "lower",
+                };
+                self.game_events.post_event(
+                    format!("You {} the {}.", verb, item.name()),
+                    EventKind::Info,
+                );
+            }
+            return true;
+        }
+
+        let off_hand = self
+            .inventory
+            .equipped(EquipSlot::OffHand)
+            .map(|item| item.name());
+        match self.inventory.equip(index) {
+            Ok(slot) => {
+                if let Some(name) = off_hand {
+                    if self.inventory.equipped(EquipSlot::OffHand).is_none() {
+                        self.game_events.post_event(
+                            format!("You sling the {} over your back to free both hands.", name),
+                            EventKind::Info,
+                        );
+                    }
+                }
+                let verb = match slot {
+                    EquipSlot::Weapon => "wield",
+                    EquipSlot::Armor => "put on",
+                    EquipSlot::OffHand => "raise",
+                };
+                if let Some(item) = self.inventory.get(index) {
+                    self.game_events.post_event(
+                        format!("You {} the {}.", verb, item.name()),
+                        EventKind::Loot,
+                    );
+                }
+                true
+            }
+            Err(msg) => {
+                self.game_events.post_event(msg, EventKind::Info);
+                false
+            }
+        }
+    }
+
+    /// Move the player or attack whatever is in the way.
+    /// Returns whether the player used up their turn.
+    fn move_player(&mut self, dx: i32, dy: i32) -> bool {
+        let reach = self.reach();
+        let from = self.player.point();
+        let opportunists = if self.settings.opportunity_attacks {
+            MovementSystem::opportunists(
+                &mut self.world,
+                &self.grid,
+                from,
+                Point::new(from.x + dx, from.y + dy),
+            )
+        } else {
+            Vec::new()
+        };
+        let outcome = MovementSystem::try_move(
+            &mut self.world,
+            &self.grid,
+            &mut self.player,
+            &mut self.game_events,
+            dx,
+            dy,
+            reach,
+        );
+        match outcome {
+            MoveOutcome::Moved => {
+                // Every enemy left behind swings before the player gets away
+                for enemy in opportunists {
+                    if !CombatSystem::opportunity_attack(
+                        &mut self.world,
+                        &self.grid,
+                        &mut self.player,
+                        &mut self.inventory,
+                        &mut self.game_events,
+                        enemy,
+                        &mut self.rng,
+                    ) {
+                        self.curr_state = CurrentState::Dead;
+                        return true;
+                    }
+                }
+                if self.settings.auto_pickup {
+                    self.take_items();
+                } else {
+                    self.see_here();
+                }
+                self.trigger_traps();
+            }
+            MoveOutcome::Attacked(target) => {
+                if !CombatSystem::resolve(
+                    &mut self.world,
+                    &mut self.grid,
+                    &mut self.player,
+                    &mut self.inventory,
+                    &mut self.game_events,
+                    target,
+                    &mut self.rng,
+                ) {
+                    self.curr_state = CurrentState::Dead;
+                }
+            }
+            MoveOutcome::Shop => {
+                self.game_events.post_event(
+                    String::from("\"Take a look at my wares.\""),
+                    EventKind::Info,
+                );
+                self.shopping = true;
+            }
+            MoveOutcome::Locked(target, lock) => return self.unlock_door(target, lock),
+            MoveOutcome::Chest(target) => return self.open_chest(target),
+            MoveOutcome::OpenedDoor | MoveOutcome::Blocked => (),
+        }
+
+        outcome.took_turn()
+    }
+
+    /// Open the chest at `target`, spilling what's inside onto the free tiles around it.
+    /// Anything there's no room for stays in the chest for another try.
+    /// Returns whether anything came out.
+    fn open_chest(&mut self, target: Point) -> bool {
+        let world = &mut self.world;
+        let chest = self.grid.at(target).iter().copied().find(|&entity| {
+            world.entry(entity).map_or(false, |entry| {
+                entry
+                    .get_component::<GameCell>()
+                    .map_or(false, |cell| cell.kind() == CellKind::Chest)
+            })
+        });
+        let chest = match chest {
+            Some(chest) => chest,
+            None => return false,
+        };
+        let mut contents = match self.world.entry(chest) {
+            Some(mut entry) => match entry.get_component_mut::<GameCell>() {
+                Ok(cell) => cell.take_contents(),
+                Err(_) => return false,
+            },
+            None => return false,
+        };
+
+        let mut spots = (-1..=1)
+            .flat_map(|dy| (-1..=1).map(move |dx| Point::new(target.x + dx, target.y + dy)))
+            .filter(|&spot| spot != target);
+        let mut found = Vec::new();
+        while let Some(item) = contents.pop() {
+            let world = &mut self.world;
+            let grid = &mut self.grid;
+            let placed = spots.any(|spot| {
+                grid.place_cell(
+                    world,
+                    item.clone().with_point(spot),
+                    CellVisibility::Visible,
+                )
+                .is_some()
+            });
+            if !placed {
+                contents.push(item);
+                break;
+            }
+            found.push(match item.kind() {
+                CellKind::Gold => format!("{} gold", item.value()),
+                _ => format!("a {}", item.label()),
+            });
+        }
+
+        let emptied = contents.is_empty();
+        if let Some(mut entry) = self.world.entry(chest) {
+            if let Ok(cell) = entry.get_component_mut::<GameCell>() {
+                if emptied {
+                    cell.set_name("open chest");
+                }
+                cell.set_contents(contents);
+            }
+        }
+
+        let message = match (found.is_empty(), emptied) {
+            (true, _) => String::from("There's no room around the chest to open it."),
+            (false, true) => format!("You open the chest and find {}.", found.join(", ")),
+            (false, false) => format!(
+                "You open the chest and find {}. There's no room for the rest.",
+                found.join(", ")
+            ),
+        };
+        self.game_events.post_event(message, EventKind::Loot);
+        !found.is_empty()
+    }
+
+    /// Open the locked door at `target` with the key for `lock` if the player has it,
+    /// using the key up. Returns whether the door was unlocked.
+    fn unlock_door(&mut self, target: Point, lock: u32) -> bool {
+        let key = match self.inventory.key_for(lock) {
+            Some(key) => key,
+            None => {
+                self.game_events
+                    .post_event(String::from("The door is locked."), EventKind::Info);
+                return false;
+            }
+        };
+
+        let world = &mut self.world;
+        let door = self.grid.at(target).iter().copied().find(|&entity| {
+            world.entry(entity).map_or(false, |entry| {
+                entry
+                    .get_component::<GameCell>()
+                    .map_or(false, |cell| cell.kind() == CellKind::LockedDoor)
+            })
+        });
+        let unlocked =
+            door.and_then(|door| world.entry(door))
+                .map_or(false, |mut entry| {
+                    match entry.get_component_mut::<GameCell>() {
+                        Ok(cell) => {
+                            cell.set_kind(CellKind::OpenedDoor);
+                            cell.set_access(CellAccess::Static);
+                            true
+                        }
+                        Err(_) => false,
+                    }
+                });
+        if unlocked {
+            self.inventory.remove(key);
+            self.game_events
+                .post_event(String::from("You unlock the door."), EventKind::Info);
+        }
+        unlocked
+    }
+
+    /// Travel to the next level if the player is standing on stairs of `kind`,
+    /// returning whether they did
+    fn take_stairs(&mut self, kind: CellKind) -> bool {
+        let mut query = <(Read<GameCell>,)>::query();
+
+        let on_stairs = query
+            .iter(&self.world)
+            .any(|(cell,)| cell.kind() == kind && cell.point() == self.player.point());
+        if !on_stairs {
+            self.game_events
+                .post_event(String::from("There are no stairs here."), EventKind::Info);
+            return false;
+        }
+
+        let depth = if kind == CellKind::StairsDown {
+            self.levels.depth() + 1
+        } else {
+            self.levels.depth() - 1
+        };
+        let arrival = self.levels.travel(&mut self.world, depth);
+        self.player.stats_mut().record_depth(depth);
+        self.grid.rebuild(&self.world);
+        self.scent.clear();
+        self.floating.clear();
+        self.scheduler.clear();
+        self.player.set_point(arrival);
+        self.game_events.post_event(
+            format!(
+                "You {} to depth {}.",
+                if kind == CellKind::StairsDown {
+                    "descend"
+                } else {
+                    "climb"
+                },
+                depth
+            ),
+            EventKind::Info,
+        );
+
+        true
+    }
+
+    /// Close every open door next to the player.
+    /// Returns whether any door was closed.
+    fn close_doors(&mut self) -> bool {
+        let mut query = <(Read<GameCell>,)>::query();
+
+        let occupied: Vec<Point> = query
+            .iter(&self.world)
+            .filter(|(cell,)| cell.access() == CellAccess::Hostile)
+            .flat_map(|(cell,)| cell.tiles())
+            .collect();
+
+        let mut query = <(Write<GameCell>,)>::query();
+
+        let mut closed = false;
+        for (cell,) in query.iter_mut(&mut self.world) {
+            let dx = (self.player.x() - cell.x()).abs();
+            let dy = (self.player.y() - cell.y()).abs();
+            if cell.kind() == CellKind::OpenedDoor
+                && dx <= 1
+                && dy <= 1
+                && (dx, dy) != (0, 0)
+                && !occupied.contains(&cell.point())
+            {
+                cell.set_kind(CellKind::ClosedDoor);
+                cell.set_access(CellAccess::Impassable);
+                closed = true;
+            }
+        }
+        if closed {
+            self.game_events
+                .post_event(String::from("You close the door."), EventKind::Info);
+        }
+
+        closed
+    }
+
+    fn save(&mut self) {
+        let game = persistence::GameRef {
+            world: &self.world,
+            levels: self.levels.levels(),
+            player: &self.player,
+            inventory: &self.inventory,
+            game_events: &self.game_events,
+            potions: &self.potions,
+            depth: self.levels.depth(),
+            seed: self.levels.seed(),
+        };
+        match persistence::save(&game, SAVE_PATH) {
+            Ok(()) => self
+                .game_events
+                .post_event(String::from("Game saved."), EventKind::Loot),
+            Err(e) => self
+                .game_events
+                .post_event(format!("Could not save: {}", e), EventKind::Warning),
+        }
+    }
+
+    /// Return the part of the world shown on the canvas, in world coordinates
+    fn view(&self) -> Rect {
+        Rect::with_exact(
+            self.layout.canvas.x1 - self.offset().0,
+            self.layout.canvas.y1 - self.offset().1,
+            self.layout.canvas.x2 - self.offset().0,
+            self.layout.canvas.y2 - self.offset().1,
+        )
+    }
+
+    /// Return every enemy with where it stands and its health,
+    /// to tell afterwards which of them got hurt
+    fn enemy_health(&self) -> Vec<(Entity, Point, u32)> {
+        let mut query = <(Read<GameCell>,)>::query();
+
+        let mut enemies = Vec::new();
+        for chunk in query.iter_chunks(&self.world) {
+            for (entity, (cell,)) in chunk.into_iter_entities() {
+                if let (CellAccess::Hostile, Some((cur, _))) = (cell.access(), cell.health()) {
+                    enemies.push((entity, cell.point(), cur));
+                }
+            }
+        }
+        enemies
+    }
+
+    /// Float the damage taken since `enemies` and the player's `health` were noted
+    /// over whoever took it, with enemies that died counted down to 0
+    fn float_damage(&mut self, enemies: &[(Entity, Point, u32)], health: i32) {
+        let now = self
+            .enemy_health()
+            .into_iter()
+            .map(|(entity, _, cur)| (entity, cur))
+            .collect::<HashMap<_, _>>();
+        for &(entity, point, before) in enemies {
+            let after = now.get(&entity).copied().unwrap_or(0);
+            if after < before {
+                self.floating.add(
+                    point,
+                    format!("-{}", before - after),
+                    RGB::named((255, 255, 0)),
+                );
+            }
+        }
+        if self.player.health() < health {
+            self.floating.add(
+                self.player.point(),
+                format!("-{}", health - self.player.health()),
+                RGB::named((255, 60, 60)),
+            );
+        }
+    }
+
+    fn render_cells(&mut self, ctx: &mut BTerm) {
+        let mut query = <(Read<GameCell>, Read<CellVisibility>)>::query();
+
+        let view = self.view();
+
+        let mut cells = query
+            .iter(&self.world)
+            .filter(|(cell, visible)| **visible != CellVisibility::Unvisited && !cell.hidden())
+            .collect::<Vec<_>>();
+        cells.sort_by_key(|(cell, _)| cell.kind().render_layer());
+        // Walls only join up with the walls and doors the player knows about
+        let walls = cells
+            .iter()
+            .filter(|(cell, _)| cell.kind().joins_walls())
+            .flat_map(|(cell, _)| cell.tiles())
+            .collect::<HashSet<_>>();
+
+        for (cell, visible) in cells {
+            for tile in cell.tiles() {
+                if !view.point_in_rect(tile) {
+                    continue;
+                }
+                let symbol = match cell.kind() {
+                    kind if kind.looks_like_wall() => {
+                        self.theme.wall_symbol(tile, |p| walls.contains(&p))
+                    }
+                    kind => self.theme.symbol(kind),
+                };
+                if *visible == CellVisibility::Visible {
+                    ctx.print_color(
+                        tile.x + self.offset().0,
+                        tile.y + self.offset().1,
+                        if self.mouse.x - self.offset().0 == tile.x
+                            && self.mouse.y - self.offset().1 == tile.y
+                        {
+                            self.theme.color_bright(cell)
+                        } else {
+                            self.theme.color(cell)
+                        },
+                        cell.bg_color(),
+                        &symbol.to_string(),
+                    );
+                } else if cell.access() != CellAccess::Hostile {
+                    ctx.print_color(
+                        tile.x + self.offset().0,
+                        tile.y + self.offset().1,
+                        RGB::named(DARK_GRAY),
+                        cell.bg_color(),
+                        &symbol.to_string(),
+                    );
+                }
+            }
+        }
+    }
+
+    /// Draw the symbol of the item in each hotbar slot under the player's status,
+    /// with a dash for an empty slot
+    fn render_hotbar(&self, ctx: &mut BTerm) {
+        let symbols: String = (0..HOTBAR_SLOTS)
+            .map(|slot| {
+                self.inventory
+                    .get(slot)
+                    .map_or('-', |item| self.theme.symbol(item.kind()))
+            })
+            .collect();
+        ctx.print(
+            self.layout.info.x1,
+            self.layout.info.y1 + 10,
+            format!("1-9: {}", symbols),
+        );
+    }
+
+    /// Point out every found feature of a kind the compass tracks that's off screen
+    /// with an arrow on the edge of the canvas, in the feature's color
+    fn render_compass(&self, ctx: &mut BTerm) {
+        let mut query = <(Read<GameCell>, Read<CellVisibility>)>::query();
+
+        let offset = self.offset();
+        let from = Point::new(self.player.x() + offset.0, self.player.y() + offset.1);
+        for (cell, visible) in query.iter(&self.world) {
+            if *visible == CellVisibility::Unvisited
+                || !self.settings.compass.contains(&cell.kind())
+            {
+                continue;
+            }
+            let target = Point::new(cell.x() + offset.0, cell.y() + offset.1);
+            if let Some((point, arrow)) = compass::marker(self.layout.canvas, from, target) {
+                ctx.print_color(
+                    point.x,
+                    point.y,
+                    cell.color(),
+                    RGB::new(),
+                    arrow.to_string(),
+                );
+            }
+        }
+    }
+
+    /// Draw every explored wall of the level shrunk down into a box in the top left corner
+    fn render_minimap(&self, ctx: &mut BTerm) {
+        if !self.show_minimap {
+            return;
+        }
+
+        ctx.draw_box(
+            0,
+            0,
+            MINIMAP_SIZE.0 + 1,
+            MINIMAP_SIZE.1 + 1,
+            RGB::named(DARK_GRAY),
+            RGB::new(),
+        );
+
+        let scale = (
+            (MAP_WIDTH + MINIMAP_SIZE.0 - 1) / MINIMAP_SIZE.0,
+            (MAP_HEIGHT + MINIMAP_SIZE.1 - 1) / MINIMAP_SIZE.1,
+        );
+        let mut query = <(Read<GameCell>, Read<CellVisibility>)>::query();
+
+        for (cell, visible) in query.iter(&self.world) {
+            if *visible != CellVisibility::Unvisited && cell.kind().looks_like_wall() {
+                ctx.print_color(
+                    cell.x() / scale.0 + 1,
+                    cell.y() / scale.1 + 1,
+                    RGB::named(GRAY),
+                    RGB::new(),
+                    ".",
+                );
+            }
+        }
+
+        ctx.print_color(
+            self.player.x() / scale.0 + 1,
+            self.player.y() / scale.1 + 1,
+            RGB::named((0, 255, 0)),
+            RGB::new(),
+            "@",
+        );
+    }
+
+    fn discover_cells(&mut self) {
+        let mut query = <(Read<GameCell>,)>::query();
+
+        let opaque: HashSet<Point> = query
+            .iter(&self.world)
+            .filter(|(cell,)| cell.kind().is_opaque())
+            .map(|(cell,)| cell.point())
+            .collect();
+        let sources = query
+            .iter(&self.world)
+            .filter(|(cell,)| cell.light() > 0)
+            .map(|(cell,)| (cell.point(), cell.light()))
+            .collect();
+        self.light.update(sources, &opaque);
+
+        let mut in_view = fov::field_of_view(
+            self.player.point(),
+            self.player.sight() + self.inventory.light(),
+            |p| opaque.contains(&p),
+        );
+        // Lit tiles can be seen from further off than the player could see in the dark
+        if !self.light.is_empty() {
+            let light = &self.light;
+            in_view.extend(
+                fov::field_of_view(self.player.point(), LIT_SIGHT, |p| opaque.contains(&p))
+                    .into_iter()
+                    .filter(|&p| light.is_lit(p)),
+            );
+        }
+
+        let mut query = <(Read<GameCell>, Write<CellVisibility>)>::query();
+
+        let mut enemies = Vec::new();
+        for (cell, visible) in query.iter_mut(&mut self.world) {
+            if cell.tiles().iter().any(|tile| in_view.contains(tile)) {
+                *visible = CellVisibility::Visible;
+                if cell.kind() == CellKind::Enemy {
+                    enemies.push(cell.clone());
+                }
+            } else if *visible == CellVisibility::Visible {
+                *visible = CellVisibility::Dark;
+            }
+        }
+        for enemy in enemies {
+            if self.player.bestiary_mut().record_sighting(&enemy) {
+                self.game_events.post_event(
+                    format!("You note the {} down in your bestiary.", enemy.name()),
+                    EventKind::Info,
+                );
+            }
+        }
+    }
+
+    /// Say what is lying on the player's tile, if anything
+    fn see_here(&mut self) {
+        let mut query = <(Read<GameCell>,)>::query();
+
+        let names: Vec<String> = query
+            .iter(&self.world)
+            .filter(|(cell,)| {
+                cell.access() == CellAccess::Takeable && cell.occupies(self.player.point())
+            })
+            .map(|(cell,)| match cell.kind() {
+                CellKind::Gold => format!("{} gold", cell.value()),
+                CellKind::Potion => {
+                    format!("a {}", self.potions.name(cell.color(), &cell.name()))
+                }
+                _ => format!("a {}", cell.label()),
+            })
+            .collect();
+        if !names.is_empty() {
+            self.game_events.post_event(
+                format!("You see here: {}.", names.join(", ")),
+                EventKind::Info,
+            );
+        }
+    }
+
+    /// Take everything lying on the player's tile.
+    /// Items with the same name count as a stack, and each stack is summed up in one
+    /// line, along with how many were left behind for want of room.
+    /// Return whether anything was taken.
+    fn take_items(&mut self) -> bool {
+        let mut query = <(Read<GameCell>,)>::query();
+
+        let mut lying = Vec::new();
+        for chunk in query.iter_chunks(&self.world) {
+            for (entity, (cell,)) in chunk.into_iter_entities() {
+                if cell.point() == self.player.point()
+                    && (cell.kind() == CellKind::Gold || cell.access() == CellAccess::Takeable)
+                {
+                    lying.push((entity, (*cell).clone()));
+                }
+            }
+        }
+
+        // Each stack's name, how many were taken and how many were left behind
+        let mut stacks: Vec<(String, usize, usize)> = Vec::new();
+        let mut gold = 0;
+        let mut took = false;
+        let mut refused = None;
+        for (entity, mut item) in lying {
+            if item.kind() == CellKind::Gold {
+                self.player.add_gold(item.value());
+                gold += item.value();
+            } else {
+                if item.kind() == CellKind::Potion {
+                    item.set_name(&self.potions.name(item.color(), &item.name()));
+                }
+                let name = item.name();
+                let stack = match stacks.iter().position(|(stacked, _, _)| *stacked == name) {
+                    Some(stack) => stack,
+                    None => {
+                        stacks.push((name, 0, 0));
+                        stacks.len() - 1
+                    }
+                };
+                if let Err(msg) = self.inventory.take(item) {
+                    stacks[stack].2 += 1;
+                    refused = Some(msg);
+                    continue;
+                }
+                stacks[stack].1 += 1;
+            }
+            self.world.remove(entity);
+            self.grid.remove(self.player.point(), entity);
+            took = true;
+        }
+
+        let mut parts = stacks
+            .iter()
+            .filter(|(_, taken, _)| *taken > 0)
+            .map(|(name, taken, left)| match left {
+                0 => counted(name, *taken),
+                left => format!("{} ({} left behind)", counted(name, *taken), left),
+            })
+            .collect::<Vec<_>>();
+        if gold > 0 {
+            parts.push(format!("{} gold", gold));
+        }
+        match (stacks.as_slice(), gold) {
+            ([(name, 1, 0)], 0) => self
+                .game_events
+                .post_event(format!("You now have the {}.", name), EventKind::Loot),
+            _ if !parts.is_empty() => self.game_events.post_event(
+                format!("You pick up {}.", parts.join(", ")),
+                EventKind::Loot,
+            ),
+            _ => (),
+        }
+        if let Some(msg) = refused {
+            self.game_events.post_event(msg, EventKind::Warning);
+        }
+        if took {
+            self.ring();
+        }
+        took
+    }
+
+    /// Spring any trap on the player's tile, revealing it
+    fn trigger_traps(&mut self) {
+        let mut query = <(Write<GameCell>,)>::query();
+
+        let mut sprung = Vec::new();
+        for (cell,) in query.iter_mut(&mut self.world) {
+            if cell.kind() == CellKind::Trap && cell.point() == self.player.point() {
+                cell.reveal();
+                sprung.push(cell.clone());
+            }
+        }
+        for trap in sprung {
+            if !CombatSystem::spring_trap(
+                &mut self.player,
+                &trap,
+                &mut self.game_events,
+                &mut self.rng,
+            ) {
+                self.curr_state = CurrentState::Dead;
+            }
+        }
+    }
+
+    /// Give every hidden trap in view a chance of being noticed
+    fn spot_traps(&mut self) {
+        let mut query = <(Write<GameCell>, Read<CellVisibility>)>::query();
+
+        for (cell, visible) in query.iter_mut(&mut self.world) {
+            if cell.hidden()
+                && *visible == CellVisibility::Visible
+                && self.rng.gen_range(0, 100) < SPOT_TRAP_CHANCE
+            {
+                cell.reveal();
+                self.game_events
+                    .post_event(format!("You spot a {}.", cell.name()), EventKind::Warning);
+            }
+        }
+    }
+
+    /// Drop one of the item at `index` onto the player's tile, returning whether it was dropped
+    fn drop_item(&mut self, index: usize) -> bool {
+        let room = match self.inventory.get(index) {
+            Some(item) => {
+                let item = item.clone().with_point(self.player.point());
+                self.grid.has_room_for(&mut self.world, &item)
+            }
+            None => return false,
+        };
+        if !room {
+            self.game_events.post_event(
+                String::from("There is no room to drop anything here."),
+                EventKind::Warning,
+            );
+            return false;
+        }
+
+        match self.inventory.remove(index) {
+            Some(item) => {
+                self.game_events
+                    .post_event(format!("You drop the {}.", item.name()), EventKind::Info);
+                self.grid
+                    .place_cell(
+                        &mut self.world,
+                        item.with_point(self.player.point()),
+                        CellVisibility::Visible,
+                    )
+                    .is_some()
+            }
+            None => false,
+        }
+    }
+
+    /// Drop everything in the pack, piling it on the player's tile and spilling onto
+    /// the tiles next to them where it won't pile up, and return whether anything was dropped.
+    /// Whatever finds no room stays in the pack and is named.
+    fn drop_all(&mut self) -> bool {
+        let origin = self.player.point();
+        // The player's own tile first, then its neighbors
+        let spots = std::iter::once(origin)
+            .chain(
+                (-1..=1)
+                    .flat_map(|dy| (-1..=1).map(move |dx| (dx, dy)))
+                    .filter(|&(dx, dy)| dx != 0 || dy != 0)
+                    .map(move |(dx, dy)| Point::new(origin.x + dx, origin.y + dy)),
+            )
+            .collect::<Vec<_>>();
+
+        let mut dropped = 0;
+        let mut kept = Vec::new();
+        for index in (0..self.inventory.len()).rev() {
+            let item = match self.inventory.get(index) {
+                Some(item) => item.clone(),
+                None => continue,
+            };
+            // Empty the stack one item at a time, since each may pile up somewhere else
+            for _ in 0..self.inventory.count(index) {
+                let spot = spots.iter().copied().find(|&spot| {
+                    self.grid
+                        .has_room_for(&mut self.world, &item.clone().with_point(spot))
+                });
+                let spot = match spot {
+                    Some(spot) => spot,
+                    None => {
+                        kept.push(item.name());
+                        break;
+                    }
+                };
+                if let Some(item) = self.inventory.remove(index) {
+                    self.grid.place_cell(
+                        &mut self.world,
+                        item.with_point(spot),
+                        CellVisibility::Visible,
+                    );
+                    dropped += 1;
+                }
+            }
+        }
+        kept.reverse();
+
+        let message = match (dropped, kept.is_empty()) {
+            (0, _) => String::from("There is no room to drop anything here."),
+            (dropped, true) => format!("You drop all {} of your things.", dropped),
+            (dropped, false) => format!(
+                "You drop {} things, but there is no room for: {}.",
+                dropped,
+                kept.join(", ")
+            ),
+        };
+        self.game_events.post_event(message, EventKind::Info);
+        dropped > 0
+    }
+
+    /// List every action with the keys bound to it, and what each symbol on the map is
+    fn help_state(&mut self, ctx: &mut BTerm) {
+        ctx.print_color(2, 1, RGB::named(WHITE), RGB::new(), "Keys");
+        for (y, &action) in Action::ALL.iter().enumerate() {
+            ctx.print_color(
+                2,
+                3 + y as i32,
+                RGB::named(GRAY),
+                RGB::new(),
+                &format!(
+                    "{:<16} {}",
+                    action.name().replace('_', " "),
+                    self.key_bindings.keys(action).join(", ")
+                ),
+            );
+        }
+
+        let x = self.window_size.0 as i32 / 2;
+        ctx.print_color(x, 1, RGB::named(WHITE), RGB::new(), "Symbols");
+        ctx.print_color(x, 3, RGB::named((0, 255, 0)), RGB::new(), "@");
+        ctx.print_color(x + 2, 3, RGB::named(GRAY), RGB::new(), "you");
+        for (y, &kind) in CellKind::ALL.iter().enumerate() {
+            ctx.print_color(
+                x,
+                4 + y as i32,
+                RGB::named(WHITE),
+                RGB::new(),
+                &self.theme.symbol(kind).to_string(),
+            );
+            ctx.print_color(
+                x + 2,
+                4 + y as i32,
+                RGB::named(GRAY),
+                RGB::new(),
+                &kind.name().replace('_', " "),
+            );
+        }
+
+        ctx.print_color(
+            2,
+            self.window_size.1 as i32 - 4,
+            RGB::named(GRAY),
+            RGB::new(),
+            &format!("Seed: {}", self.rng.seed()),
+        );
+        ctx.print_centered(
+            self.window_size.1 as i32 - 2,
+            "Press any key to return to the game",
+        );
+
+        if ctx.key.is_some() {
+            self.curr_state = CurrentState::Playing;
+        }
+    }
+
+    /// List the options with how each stands. Up and down pick one, left, right and enter
+    /// change it straight away, and escape goes back to the game, saving the options.
+    /// Options given on the command line are in effect but only saved once changed here.
+    fn settings_state(&mut self, ctx: &mut BTerm) {
+        let top = self.window_size.1 as i32 / 2 - Setting::ALL.len() as i32 / 2 - 2;
+        ctx.print_centered(top, "Settings");
+        for (y, &setting) in Setting::ALL.iter().enumerate() {
+            let fg = if y == self.setting {
+                RGB::named(YELLOW)
+            } else {
+                RGB::named(GRAY)
+            };
+            ctx.print_color_centered(
+                top + 2 + y as i32,
+                fg,
+                RGB::new(),
+                &format!(
+                    "{:<26} {:>4}",
+                    setting.label(),
+                    setting.value(&self.settings)
+                ),
+            );
+        }
+        ctx.print_centered(
+            self.window_size.1 as i32 - 2,
+            "Press escape to return to the game",
+        );
+
+        let len = Setting::ALL.len();
+        match ctx.key {
+            Some(VirtualKeyCode::Up) => self.setting = (self.setting + len - 1) % len,
+            Some(VirtualKeyCode::Down) => self.setting = (self.setting + 1) % len,
+            Some(VirtualKeyCode::Left) => self.change_setting(false),
+            Some(VirtualKeyCode::Right) | Some(VirtualKeyCode::Return) => self.change_setting(true),
+            Some(VirtualKeyCode::Escape) => {
+                if let Err(e) = self.saved_settings.save(CONFIG_PATH) {
+                    self.game_events.post_event(
+                        format!("Could not save the settings: {}", e),
+                        EventKind::Warning,
+                    );
+                }
+                self.curr_state = CurrentState::Playing;
+            }
+            _ => (),
+        }
+    }
+
+    /// Change the highlighted setting, up or down if it's a number, both in effect
+    /// and in the options to be saved
+    fn change_setting(&mut self, up: bool) {
+        let setting = Setting::ALL[self.setting];
+        let mut settings = self.settings.clone();
+        setting.change(&mut settings, up);
+        setting.copy(&settings, &mut self.saved_settings);
+        self.apply_settings(settings);
+    }
+
+    /// List every kind of monster with what's known about it, up and down scrolling
+    /// and escape going back to the game
+    fn bestiary_state(&mut self, ctx: &mut BTerm) {
+        let lines = self.player.bestiary().lines(&levels::monster_names());
+        let shown = (self.window_size.1 as usize).saturating_sub(6).max(1);
+        let max_scroll = lines.len().saturating_sub(shown);
+        self.bestiary_scroll = self.bestiary_scroll.min(max_scroll);
+
+        ctx.print_color(2, 1, RGB::named(WHITE), RGB::new(), "Bestiary");
+        for (y, line) in lines
+            .iter()
+            .skip(self.bestiary_scroll)
+            .take(shown)
+            .enumerate()
+        {
+            ctx.print_color(2, 3 + y as i32, RGB::named(GRAY), RGB::new(), line);
+        }
+        ctx.print_centered(
+            self.window_size.1 as i32 - 2,
+            "Press escape to return to the game",
+        );
+
+        match ctx.key {
+            Some(VirtualKeyCode::Up) => {
+                self.bestiary_scroll = self.bestiary_scroll.saturating_sub(1)
+            }
+            Some(VirtualKeyCode::Down) => {
+                self.bestiary_scroll = (self.bestiary_scroll + 1).min(max_scroll)
+            }
+            Some(VirtualKeyCode::Escape) => self.curr_state = CurrentState::Playing,
+            _ => (),
+        }
+    }
+
+    /// Show how the run went until a key is pressed
+    fn dead_state(&mut self, ctx: &mut BTerm) {
+        self.game_events.print(ctx, self.layout.events);
+
+        let summary = self.player.stats().summary(self.player.turns());
+        let top = self.window_size.1 as i32 / 2 - summary.len() as i32 / 2 - 2;
+        ctx.print_centered(top, "You died");
+        for (y, line) in summary.iter().enumerate() {
+            ctx.print_centered(top + 2 + y as i32, line);
+        }
+        ctx.print_centered(
+            top + 3 + summary.len() as i32,
+            format!("Seed {}", self.rng.seed()),
+        );
+        ctx.print_centered(top + 5 + summary.len() as i32, "Press any key to exit");
+
+        if ctx.key.is_some() {
+            self.game_events.flush_log();
+            ctx.quit();
+        }
+    }
+
+    fn quit_state(&mut self, ctx: &mut BTerm) {
+        self.game_events.flush_log();
+        ctx.quit();
+    }
+}
+
+/// Draw `prompt` in a box in the middle of the window, over whatever is already there
+fn draw_confirm(ctx: &mut BTerm, window_size: (u32, u32), prompt: &str) {
+    let text = format!("{} (y/n)", prompt);
+    let x = window_size.0 as i32 / 2 - text.len() as i32 / 2 - 2;
+    let y = window_size.1 as i32 / 2 - 1;
+
+    ctx.draw_box(
+        x,
+        y,
+        text.len() as i32 + 3,
+        2,
+        RGB::named(WHITE),
+        RGB::new(),
+    );
+    ctx.print(x + 2, y + 1, &text);
+}
+
+/// Return the hotbar slot a number key picks, counting from 0
+fn hotbar_slot(key: VirtualKeyCode) -> Option<usize> {
+    [
+        VirtualKeyCode::Key1,
+        VirtualKeyCode::Key2,
+        VirtualKeyCode::Key3,
+        VirtualKeyCode::Key4,
+        VirtualKeyCode::Key5,
+        VirtualKeyCode::Key6,
+        VirtualKeyCode::Key7,
+        VirtualKeyCode::Key8,
+        VirtualKeyCode::Key9,
+    ]
+    .iter()
+    .position(|&number| number == key)
+}
+
+/// Rank how much a cell with `access` stands out when several share a tile
+fn interest(access: CellAccess) -> u8 {
+    match access {
+        CellAccess::Hostile => 3,
+        CellAccess::Takeable => 2,
+        CellAccess::Impassable => 1,
+        CellAccess::Static => 0,
+    }
+}
+
+impl GameState for State {
+    fn tick(&mut self, ctx: &mut BTerm) {
+        ctx.cls();
+
+        let mut input = INPUT.lock();
+
+        input.for_each_message(|event| match event {
+            BEvent::MouseClick { button, pressed } => self.mouse_click = Some((button, pressed)),
+            BEvent::MouseButtonUp { button } => self.mouse_pressed = (button, false),
+            BEvent::MouseButtonDown { button } => self.mouse_pressed = (button, true),
+            _ => (),
+        });
+
+        self.tic += 4;
+        if self.tic > 99 {
+            self.tic = 0;
+        }
+
+        self.mouse = ctx.mouse_point();
+
+        let (w, h) = ctx.get_char_size();
+        self.resize(w.saturating_sub(1), h);
+        if self.window_size.0 < MIN_WINDOW_SIZE.0 || self.window_size.1 < MIN_WINDOW_SIZE.1 {
+            ctx.print_centered(h as i32 / 2, "Window too small");
+            self.mouse_click = None;
+            return;
         }
 
-        self.mouse = ctx.mouse_point();
-
         match self.curr_state {
             CurrentState::Menu => self.menu_state(ctx),
             CurrentState::Playing => self.play_state(ctx),
+            CurrentState::Help => self.help_state(ctx),
+            CurrentState::Settings => self.settings_state(ctx),
+            CurrentState::Bestiary => self.bestiary_state(ctx),
             CurrentState::Quitting => self.quit_state(ctx),
+            CurrentState::Dead => self.dead_state(ctx),
         }
 
         self.mouse_click = None;
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Return a fresh game with the level cleared away, leaving nothing in the player's way
+    fn empty_level() -> State {
+        let mut state = State::headless(80, 25, 1);
+        state.world = World::default();
+        state.grid = SpatialGrid::new(&state.world);
+        state.curr_state = CurrentState::Playing;
+        state
+    }
+
+    #[test]
+    fn undoes_three_moves_in_a_row() {
+        let mut state = empty_level();
+        let start = state.player.point();
+
+        for _ in 0..3 {
+            state.step(Action::MoveRight);
+        }
+        assert_eq!(state.player.point(), Point::new(start.x + 3, start.y));
+
+        for back in (0..3).rev() {
+            state.step(Action::Undo);
+            assert_eq!(state.player.point(), Point::new(start.x + back, start.y));
+        }
+    }
+
+    #[test]
+    fn picks_up_what_fits_of_a_stack() {
+        let mut state = empty_level();
+        let here = state.player.point();
+        let ration = GameCell::new(
+            here,
+            CellKind::Food,
+            "ration",
+            RGB::named(GRAY),
+            CellAccess::Takeable,
+        )
+        .with_weight(2);
+        for _ in 0..30 {
+            state.world.push((ration.clone(), CellVisibility::Visible));
+        }
+        state.grid.rebuild(&state.world);
+
+        assert!(state.take_items());
+        assert_eq!(state.inventory.item_count(), 25);
+        assert!(state
+            .game_events
+            .has_posted("You pick up 25 rations (5 left behind)."));
+    }
+
+    #[test]
+    fn names_what_it_has_no_room_to_drop() {
+        let mut state = empty_level();
+        let here = state.player.point();
+        state.world.push((
+            GameCell::new(
+                here,
+                CellKind::Floor,
+                "floor",
+                RGB::named(GRAY),
+                CellAccess::Static,
+            ),
+            CellVisibility::Visible,
+        ));
+        state.grid.rebuild(&state.world);
+        state.inventory = Inventory::new();
+        let item =
+            |kind, name| GameCell::new(here, kind, name, RGB::named(GRAY), CellAccess::Takeable);
+        state
+            .inventory
+            .take(item(CellKind::EdgedWeapon, "dagger"))
+            .unwrap();
+        for _ in 0..3 {
+            state
+                .inventory
+                .take(item(CellKind::Food, "ration"))
+                .unwrap();
+        }
+
+        assert!(state.drop_all());
+        assert_eq!(state.inventory.item_count(), 1);
+        assert!(state
+            .game_events
+            .has_posted("You drop 3 things, but there is no room for: dagger."));
+    }
+
+    #[test]
+    fn centers_the_player_on_odd_and_even_canvases() {
+        let mut state = State::headless(80, 25, 1);
+        let player = state.player.point();
+        let drawn_at = |state: &State| {
+            let (x, y) = state.offset();
+            Point::new(player.x + x, player.y + y)
+        };
+
+        // An 80 by 19 canvas, under the 6 rows of messages
+        state.resize(80, 25);
+        assert_eq!(drawn_at(&state), Point::new(40, 9));
+
+        // An 81 by 20 canvas
+        state.resize(81, 26);
+        assert_eq!(drawn_at(&state), Point::new(40, 10));
+    }
+
+    /// Return the kind of the door at `point`
+    fn door_at(state: &mut State, point: Point) -> Option<CellKind> {
+        let world = &mut state.world;
+        state.grid.at(point).iter().find_map(|&entity| {
+            world.entry(entity).and_then(|entry| {
+                entry
+                    .get_component::<GameCell>()
+                    .ok()
+                    .map(|cell| cell.kind())
+            })
+        })
+    }
+
+    #[test]
+    fn only_the_matching_key_unlocks_a_door() {
+        let mut state = empty_level();
+        state.inventory = Inventory::new();
+        let door = Point::new(state.player.x() + 1, state.player.y());
+        state.world.push((
+            GameCell::new(
+                door,
+                CellKind::LockedDoor,
+                "locked door",
+                RGB::named(GRAY),
+                CellAccess::Impassable,
+            )
+            .with_lock(3),
+            CellVisibility::Visible,
+        ));
+        state.grid.rebuild(&state.world);
+        let key = |lock| {
+            GameCell::new(
+                door,
+                CellKind::Key,
+                "brass key",
+                RGB::named(GRAY),
+                CellAccess::Takeable,
+            )
+            .with_lock(lock)
+        };
+
+        assert!(!state.unlock_door(door, 3));
+        assert!(state.game_events.has_posted("The door is locked."));
+
+        state.inventory.take(key(4)).unwrap();
+        assert!(!state.unlock_door(door, 3));
+        assert_eq!(door_at(&mut state, door), Some(CellKind::LockedDoor));
+
+        state.inventory.take(key(3)).unwrap();
+        assert!(state.unlock_door(door, 3));
+        assert!(state.game_events.has_posted("You unlock the door."));
+        assert_eq!(door_at(&mut state, door), Some(CellKind::OpenedDoor));
+        assert_eq!(state.inventory.key_for(3), None);
+        assert!(state.inventory.key_for(4).is_some());
+    }
+
+    #[test]
+    fn leaves_items_lying_unless_auto_pickup_is_on() {
+        for &auto_pickup in &[false, true] {
+            let mut state = empty_level();
+            state.inventory = Inventory::new();
+            state.settings.auto_pickup = auto_pickup;
+            let next = Point::new(state.player.x() + 1, state.player.y());
+            state.world.push((
+                GameCell::new(
+                    next,
+                    CellKind::Food,
+                    "ration",
+                    RGB::named(GRAY),
+                    CellAccess::Takeable,
+                ),
+                CellVisibility::Visible,
+            ));
+            state.grid.rebuild(&state.world);
+
+            state.step(Action::MoveRight);
+            assert_eq!(state.player.point(), next);
+            assert_eq!(state.inventory.item_count(), auto_pickup as usize);
+            assert_eq!(
+                state.game_events.has_posted("You see here: a ration."),
+                !auto_pickup
+            );
+        }
+    }
+
+    #[test]
+    fn saves_only_what_was_changed_on_the_settings_screen() {
+        let mut state = empty_level();
+        // As if given on the command line
+        state.set_bell(true);
+        state.set_realtime(4.0);
+        state.setting = Setting::ALL
+            .iter()
+            .position(|&setting| setting == Setting::AutoPickup)
+            .unwrap();
+        let auto_pickup = state.settings.auto_pickup;
+
+        state.change_setting(true);
+        assert_eq!(state.settings.auto_pickup, !auto_pickup);
+        assert!(state.settings.bell);
+        assert_eq!(
+            state.saved_settings,
+            Config {
+                auto_pickup: !auto_pickup,
+                ..Config::default()
+            }
+        );
+    }
+}
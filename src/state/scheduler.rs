@@ -0,0 +1,76 @@
+use std::{collections::HashMap, hash::Hash};
+
+use legion::Entity;
+
+/// Energy an actor has to build up before it takes a turn
+const ACT_ENERGY: u32 = 6;
+
+/// Something that takes turns
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Actor {
+    Player,
+    Enemy(Entity),
+}
+
+/// Decides who acts when, from how fast each actor is.
+///
+/// Every tick each actor gains its speed in energy, and any actor that has built up
+/// `ACT_ENERGY` spends it and takes a turn. Energy left over carries into the next turn,
+/// so an actor with speed 2 acts exactly twice for every turn one with speed 1 takes,
+/// and one with speed 3 three times for every two.
+///
+/// Actors are told apart by `K`, which is anything that names them, like an entity.
+#[derive(Clone, Debug)]
+pub struct TurnScheduler<K: Eq + Hash> {
+    energy: HashMap<K, u32>,
+}
+
+impl<K: Eq + Hash> Default for TurnScheduler<K> {
+    fn default() -> Self {
+        Self {
+            energy: HashMap::new(),
+        }
+    }
+}
+
+impl<K: Copy + Eq + Hash> TurnScheduler<K> {
+    /// Let a tick pass for each of `actors`, given with their speed, returning those
+    /// that get to act in it in the order they were given.
+    /// Anyone not in `actors` is forgotten, as when an enemy dies.
+    pub fn tick(&mut self, actors: &[(K, u32)]) -> Vec<K> {
+        let mut energy = HashMap::with_capacity(actors.len());
+        let mut ready = Vec::new();
+        for &(actor, speed) in actors {
+            let mut gained = self.energy.get(&actor).copied().unwrap_or(0) + speed;
+            if gained >= ACT_ENERGY {
+                gained -= ACT_ENERGY;
+                ready.push(actor);
+            }
+            energy.insert(actor, gained);
+        }
+        self.energy = energy;
+        ready
+    }
+
+    /// Forget everyone's energy, as when the player arrives on another level
+    pub fn clear(&mut self) {
+        self.energy.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn twice_the_speed_acts_twice_as_often() {
+        let mut scheduler = TurnScheduler::default();
+        let mut turns = [0, 0];
+        for _ in 0..60 {
+            for actor in scheduler.tick(&[(0, 2), (1, 1)]) {
+                turns[actor] += 1;
+            }
+        }
+        assert_eq!(turns, [20, 10]);
+    }
+}
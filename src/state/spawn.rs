@@ -0,0 +1,102 @@
+use std::collections::HashSet;
+
+use bracket_lib::prelude::*;
+
+use legion::*;
+
+use rand::{seq::SliceRandom, Rng};
+
+use super::{difficulty::difficulty_for_depth, levels::enemy_roster};
+use crate::{
+    components::{CellVisibility, EnemyAI, GameCell},
+    spatial::SpatialGrid,
+    types::CellKind,
+};
+
+/// How many turns pass between wandering monsters
+const SPAWN_CADENCE: u32 = 50;
+/// How many more enemies than a level starts with can end up on it
+const EXTRA_POPULATION: usize = 3;
+
+/// Brings a wandering monster onto the level every so often, out of the player's sight
+#[derive(Clone, Copy, Debug)]
+pub struct SpawnController {
+    /// How many turns pass between spawns
+    cadence: u32,
+    /// How many enemies a level can hold beyond the ones it starts with
+    extra: usize,
+}
+
+impl Default for SpawnController {
+    fn default() -> Self {
+        Self {
+            cadence: SPAWN_CADENCE,
+            extra: EXTRA_POPULATION,
+        }
+    }
+}
+
+impl SpawnController {
+    /// Return how many enemies the level at `depth` can hold before no more turn up
+    pub fn cap(&self, depth: u32) -> usize {
+        difficulty_for_depth(depth).spawn_count + self.extra
+    }
+
+    /// Bring in an enemy fit for `depth` if `turn` is due one and the level isn't full,
+    /// returning whether one came.
+    ///
+    /// It only ever comes onto empty ground that isn't in view, so nothing
+    /// appears in front of the player or on top of anything else.
+    pub fn run<R: Rng>(
+        &self,
+        world: &mut World,
+        grid: &mut SpatialGrid,
+        depth: u32,
+        turn: u32,
+        player: Point,
+        rng: &mut R,
+    ) -> bool {
+        if self.cadence == 0 || turn == 0 || turn % self.cadence != 0 {
+            return false;
+        }
+
+        let mut query = <(Read<GameCell>, Read<CellVisibility>)>::query();
+
+        let mut enemies = 0;
+        let mut hidden = HashSet::new();
+        for (cell, visible) in query.iter(world) {
+            if cell.kind() == CellKind::Enemy {
+                enemies += 1;
+            } else if cell.kind().is_ground() && *visible != CellVisibility::Visible {
+                hidden.insert(cell.point());
+            }
+        }
+        hidden.remove(&player);
+        if enemies >= self.cap(depth) {
+            return false;
+        }
+
+        let enemy = match enemy_roster(depth).choose(rng) {
+            Some(enemy) => enemy.clone(),
+            None => return false,
+        };
+        // Sorted first so the same seed always spawns in the same place
+        let mut spots = hidden.iter().copied().collect::<Vec<_>>();
+        spots.sort_by_key(|spot| (spot.x, spot.y));
+        spots.shuffle(rng);
+
+        for spot in spots {
+            let enemy = enemy.clone().with_point(spot);
+            if !enemy.tiles().iter().all(|tile| hidden.contains(tile)) {
+                continue;
+            }
+            if let Some(entity) = grid.place_cell(world, enemy, CellVisibility::Unvisited) {
+                if let Some(mut entry) = world.entry(entity) {
+                    entry.add_component(EnemyAI::wandering());
+                }
+                return true;
+            }
+        }
+        false
+    }
+}
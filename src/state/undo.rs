@@ -0,0 +1,61 @@
+use std::collections::VecDeque;
+
+use legion::*;
+
+use crate::{components::GameCell, spatial::SpatialGrid, types::Player};
+
+/// Most moves that can be taken back in a row
+const UNDO_DEPTH: usize = 10;
+
+/// How things stood before a single step, enough to put them back.
+///
+/// Along with the player this is every cell the step could have changed,
+/// like enemies that moved or a door that was opened.
+pub struct Snapshot {
+    pub player: Player,
+    /// How many items the player was carrying, to tell when something was picked up
+    pub items: usize,
+    pub cells: Vec<(Entity, GameCell)>,
+}
+
+impl Snapshot {
+    /// Put every saved cell back the way it was, moving it in `grid` too,
+    /// and return the player to go back to.
+    /// Cells that have since left the world are skipped.
+    pub fn restore(self, world: &mut World, grid: &mut SpatialGrid) -> Player {
+        for (entity, saved) in self.cells {
+            if let Some(mut entry) = world.entry(entity) {
+                if let Ok(cell) = entry.get_component_mut::<GameCell>() {
+                    grid.move_tiles(entity, &cell.tiles(), &saved.tiles());
+                    *cell = saved;
+                }
+            }
+        }
+        self.player
+    }
+}
+
+/// The last few exploring steps, most recent last
+#[derive(Default)]
+pub struct UndoHistory {
+    snapshots: VecDeque<Snapshot>,
+}
+
+impl UndoHistory {
+    /// Remember `snapshot`, forgetting the oldest one once there are too many
+    pub fn push(&mut self, snapshot: Snapshot) {
+        if self.snapshots.len() == UNDO_DEPTH {
+            self.snapshots.pop_front();
+        }
+        self.snapshots.push_back(snapshot);
+    }
+
+    pub fn pop(&mut self) -> Option<Snapshot> {
+        self.snapshots.pop_back()
+    }
+
+    /// Forget every step, as when something happens that can't be taken back
+    pub fn clear(&mut self) {
+        self.snapshots.clear();
+    }
+}
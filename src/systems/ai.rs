@@ -0,0 +1,315 @@
+use std::collections::HashSet;
+
+use bracket_lib::prelude::*;
+
+use legion::*;
+
+use rand::{seq::SliceRandom, Rng};
+
+use crate::{
+    components::{AiState, EnemyAI, GameCell},
+    los,
+    scent::ScentMap,
+    spatial::SpatialGrid,
+    types::{CellAccess, Player},
+};
+
+/// How far away an enemy can spot the player when they aren't sneaking
+const ENEMY_SIGHT: i32 = 8;
+/// How many turns a chasing enemy keeps after the player's scent without seeing them
+const LOSE_TRACK_TURNS: u32 = 10;
+/// How far an enemy that spots the player can rouse the rest of its pack
+const PACK_ALERT_RADIUS: i32 = 6;
+
+pub struct AiSystem;
+
+impl AiSystem {
+    /// Let every enemy in `acting` act, in world coordinates.
+    ///
+    /// Sleeping enemies wake and start chasing once the player comes into their line of sight,
+    /// within `detection_range`.
+    /// Chasing enemies step toward the player while they can see them and follow the
+    /// strongest scent around them when they can't, until they've gone `LOSE_TRACK_TURNS`
+    /// without a sighting and take to wandering at random.
+    ///
+    /// An enemy in a pack that spots the player sets every packmate within
+    /// `PACK_ALERT_RADIUS` chasing too, and packmates in sight of the player close in on
+    /// different tiles around them rather than queueing up behind each other.
+    pub fn run<R: Rng>(
+        world: &mut World,
+        grid: &mut SpatialGrid,
+        scent: &ScentMap,
+        player: &Player,
+        acting: &HashSet<Entity>,
+        rng: &mut R,
+    ) {
+        let mut query = <(Read<GameCell>, Read<EnemyAI>)>::query();
+
+        let mut enemies = Vec::new();
+        for chunk in query.iter_chunks(world) {
+            for (entity, (cell, ai)) in chunk.into_iter_entities() {
+                if acting.contains(&entity) {
+                    enemies.push((entity, *ai, cell.point(), cell.tiles()));
+                }
+            }
+        }
+
+        let target = player.point();
+        // Tiles around the player packmates are already heading for this turn
+        let mut claimed = Vec::new();
+        // Packs, and where they were, that have a member who spotted the player this turn
+        let mut alerts = Vec::new();
+
+        for (entity, mut ai, pos, tiles) in enemies {
+            let dx = (target.x - pos.x).signum();
+            let dy = (target.y - pos.y).signum();
+            let dist = chebyshev(pos, target);
+
+            // Every tile of a large enemy has to fit, but it doesn't block itself
+            let shifted = |a: i32, b: i32| -> Vec<Point> {
+                tiles
+                    .iter()
+                    .map(|tile| Point::new(tile.x + a, tile.y + b))
+                    .collect()
+            };
+            let fits = |world: &mut World, a: i32, b: i32| {
+                shifted(a, b).iter().all(|&tile| {
+                    tile != target
+                        && (tiles.contains(&tile)
+                            || !grid.any_at(world, tile, |cell| {
+                                cell.access() == CellAccess::Impassable
+                                    || cell.access() == CellAccess::Hostile
+                            }))
+                })
+            };
+
+            let in_sight = dist <= detection_range(player)
+                && los::line(pos, target)
+                    .into_iter()
+                    .skip(1)
+                    .all(|point| !grid.any_at(world, point, |cell| cell.kind().is_opaque()));
+
+            ai.state = match (ai.state, in_sight) {
+                (_, true) => AiState::Chasing,
+                (AiState::Chasing, false) if ai.unseen >= LOSE_TRACK_TURNS => AiState::Wandering,
+                (state, false) => state,
+            };
+            ai.unseen = if in_sight { 0 } else { ai.unseen + 1 };
+            if let Some(pack) = ai.pack.filter(|_| in_sight) {
+                alerts.push((pack, pos));
+            }
+
+            let step = match ai.state {
+                AiState::Asleep => None,
+                AiState::Chasing if in_sight => {
+                    let goal = ai
+                        .pack
+                        .and_then(|_| flank(world, grid, pos, &tiles, target, &claimed));
+                    match goal {
+                        Some(goal) => {
+                            claimed.push(goal);
+                            NEIGHBORS
+                                .iter()
+                                .copied()
+                                .filter(|&(a, b)| {
+                                    let next = Point::new(pos.x + a, pos.y + b);
+                                    chebyshev(next, goal) < chebyshev(pos, goal)
+                                        && chebyshev(next, target) <= dist
+                                        && fits(world, a, b)
+                                })
+                                .min_by_key(|&(a, b)| {
+                                    chebyshev(Point::new(pos.x + a, pos.y + b), goal)
+                                })
+                        }
+                        // Alone, or with nowhere around the player left to flank from
+                        None => [(dx, dy), (dx, 0), (0, dy)]
+                            .iter()
+                            .copied()
+                            .find(|&(a, b)| {
+                                let next = Point::new(pos.x + a, pos.y + b);
+                                next != pos && chebyshev(next, target) < dist && fits(world, a, b)
+                            }),
+                    }
+                }
+                AiState::Chasing => {
+                    let mut best = (scent.at(pos), None);
+                    for &(a, b) in &NEIGHBORS {
+                        let strength = scent.at(Point::new(pos.x + a, pos.y + b));
+                        if strength > best.0 && fits(world, a, b) {
+                            best = (strength, Some((a, b)));
+                        }
+                    }
+                    best.1
+                }
+                AiState::Wandering => {
+                    let open: Vec<(i32, i32)> = NEIGHBORS
+                        .iter()
+                        .copied()
+                        .filter(|&(a, b)| fits(world, a, b))
+                        .collect();
+                    open.choose(rng).copied()
+                }
+            };
+
+            if let Some(mut entry) = world.entry(entity) {
+                if let Ok(state) = entry.get_component_mut::<EnemyAI>() {
+                    *state = ai;
+                }
+                if let Some((a, b)) = step {
+                    if let Ok(cell) = entry.get_component_mut::<GameCell>() {
+                        cell.move_pos(a, b);
+                        grid.move_tiles(entity, &tiles, &shifted(a, b));
+                    }
+                }
+            }
+        }
+
+        Self::alert_packs(world, &alerts);
+    }
+
+    /// Set every pack member within `PACK_ALERT_RADIUS` of where one of its packmates
+    /// spotted the player chasing them too
+    fn alert_packs(world: &mut World, alerts: &[(u32, Point)]) {
+        if alerts.is_empty() {
+            return;
+        }
+        let mut query = <(Read<GameCell>, Write<EnemyAI>)>::query();
+        for (cell, ai) in query.iter_mut(world) {
+            let roused = ai.pack.map_or(false, |pack| {
+                alerts.iter().any(|&(alerted, spot)| {
+                    alerted == pack && chebyshev(cell.point(), spot) <= PACK_ALERT_RADIUS
+                })
+            });
+            if roused && ai.state != AiState::Chasing {
+                ai.state = AiState::Chasing;
+                ai.unseen = 0;
+            }
+        }
+    }
+}
+
+/// Return the tile next to `target` a pack member at `pos`, covering `tiles`, should close in on:
+/// the nearest open one no packmate has claimed or stands on, or `pos` if it's already there.
+/// Returns `None` if there's no such tile left.
+fn flank(
+    world: &mut World,
+    grid: &SpatialGrid,
+    pos: Point,
+    tiles: &[Point],
+    target: Point,
+    claimed: &[Point],
+) -> Option<Point> {
+    if chebyshev(pos, target) <= 1 {
+        return Some(pos);
+    }
+    NEIGHBORS
+        .iter()
+        .map(|&(a, b)| Point::new(target.x + a, target.y + b))
+        .filter(|tile| {
+            !claimed.contains(tile)
+                && (tiles.contains(tile)
+                    || !grid.any_at(world, *tile, |cell| {
+                        cell.access() == CellAccess::Impassable
+                            || cell.access() == CellAccess::Hostile
+                    }))
+        })
+        .min_by_key(|&tile| chebyshev(pos, tile))
+}
+
+/// Return how far away enemies can spot `player`, which is half as far while sneaking
+pub fn detection_range(player: &Player) -> i32 {
+    if player.sneaking() {
+        ENEMY_SIGHT / 2
+    } else {
+        ENEMY_SIGHT
+    }
+}
+
+const NEIGHBORS: [(i32, i32); 8] = [
+    (-1, -1),
+    (0, -1),
+    (1, -1),
+    (-1, 0),
+    (1, 0),
+    (-1, 1),
+    (0, 1),
+    (1, 1),
+];
+
+fn chebyshev(a: Point, b: Point) -> i32 {
+    (a.x - b.x).abs().max((a.y - b.y).abs())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use crate::{components::CellVisibility, types::CellKind};
+
+    fn enemy(point: Point) -> GameCell {
+        GameCell::new(
+            point,
+            CellKind::Enemy,
+            "goblin",
+            RGB::named(GRAY),
+            CellAccess::Hostile,
+        )
+    }
+
+    /// Return a world with an enemy on every tile around `target` but those in `free`
+    fn surrounded(target: Point, free: &[Point]) -> World {
+        let mut world = World::default();
+        for &(a, b) in &NEIGHBORS {
+            let tile = Point::new(target.x + a, target.y + b);
+            if !free.contains(&tile) {
+                world.push((enemy(tile), CellVisibility::Visible));
+            }
+        }
+        world
+    }
+
+    #[test]
+    fn flanks_from_a_tile_no_other_enemy_stands_on() {
+        let (target, pos, free) = (Point::new(5, 5), Point::new(5, 9), Point::new(5, 6));
+        let mut world = surrounded(target, &[free]);
+        let grid = SpatialGrid::new(&world);
+
+        assert_eq!(
+            flank(&mut world, &grid, pos, &[pos], target, &[]),
+            Some(free)
+        );
+        assert_eq!(flank(&mut world, &grid, pos, &[pos], target, &[free]), None);
+    }
+
+    #[test]
+    fn a_packmate_with_nowhere_to_flank_from_closes_in_directly() {
+        let target = Point::new(5, 5);
+        let mut world = surrounded(target, &[]);
+        let chasing = EnemyAI {
+            state: AiState::Chasing,
+            ..EnemyAI::new().with_pack(1)
+        };
+        let goblin = world.push((enemy(Point::new(5, 8)), CellVisibility::Visible, chasing));
+        let mut grid = SpatialGrid::new(&world);
+        let acting = std::iter::once(goblin).collect();
+
+        AiSystem::run(
+            &mut world,
+            &mut grid,
+            &ScentMap::default(),
+            &Player::new(target),
+            &acting,
+            &mut StdRng::seed_from_u64(1),
+        );
+
+        let moved = world.entry(goblin).and_then(|entry| {
+            entry
+                .get_component::<GameCell>()
+                .ok()
+                .map(|cell| cell.point())
+        });
+        assert_eq!(moved, Some(Point::new(5, 7)));
+    }
+}
@@ -0,0 +1,799 @@
+use bracket_lib::prelude::*;
+
+use legion::*;
+
+use rand::Rng;
+
+use crate::{
+    components::{AiState, CellVisibility, EnemyAI, GameCell, Inventory},
+    spatial::SpatialGrid,
+    types::{
+        hp_bar, CellAccess, Dice, Effect, EquipSlot, EventKind, GameEvents, LootTable, Player,
+        StatusEffects, HP_BAR_WIDTH,
+    },
+};
+
+/// What a d20 plus the attacker's accuracy has to reach, before the defender's defense
+const TO_HIT: i32 = 8;
+/// How accurate every enemy is
+const ENEMY_ACCURACY: i32 = 2;
+/// How many times the damage a sneak attack on an unaware enemy does
+const BACKSTAB_MULTIPLIER: i32 = 3;
+
+pub struct CombatSystem;
+
+/// What came of a single attack roll
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AttackResult {
+    Miss,
+    Hit(i32),
+    /// A natural 20, for double damage
+    Crit(i32),
+}
+
+impl AttackResult {
+    /// Return the same result doing `multiplier` times the damage
+    fn times(self, multiplier: i32) -> Self {
+        match self {
+            AttackResult::Miss => AttackResult::Miss,
+            AttackResult::Hit(dealt) => AttackResult::Hit(dealt * multiplier),
+            AttackResult::Crit(dealt) => AttackResult::Crit(dealt * multiplier),
+        }
+    }
+}
+
+/// Roll an attack with `accuracy` and `damage` against a defender with `defense`.
+///
+/// A d20 plus `accuracy` has to reach `TO_HIT` plus `defense` to land.
+/// A natural 20 always lands as a critical hit and a natural 1 always misses.
+/// The defense also soaks up that much of the damage, but a blow that lands does at least 1.
+pub fn resolve_attack<R: Rng>(
+    accuracy: i32,
+    damage: Dice,
+    defense: i32,
+    rng: &mut R,
+) -> AttackResult {
+    let roll = rng.gen_range(1, 21);
+    if roll == 1 || (roll < 20 && roll + accuracy < TO_HIT + defense) {
+        return AttackResult::Miss;
+    }
+    let dealt = (damage.roll(rng) - defense).max(1);
+    if roll == 20 {
+        AttackResult::Crit(dealt * 2)
+    } else {
+        AttackResult::Hit(dealt)
+    }
+}
+
+/// What came of the player striking at a tile
+enum Strike {
+    /// There was nothing there to strike
+    Missed,
+    /// The enemy died, with its name and how much XP it was worth
+    Killed(String, u32),
+    /// The enemy is still standing, with its name and damage
+    Survived(String, Option<Dice>),
+    /// The blow missed the enemy, with its name and damage
+    Dodged(String, Option<Dice>),
+}
+
+impl CombatSystem {
+    /// Resolve the player attacking the hostile cell at `target` and it hitting back,
+    /// with the wielded weapon's damage and the equipped armor's defense.
+    /// Both sides roll to hit with `resolve_attack`.
+    /// Sneaking up on an enemy that isn't chasing the player backstabs it for extra damage.
+    /// The weapon wears with every blow that lands and the armor with every one taken,
+    /// and leaves its on-hit effect on an enemy that survives the blow.
+    /// A slain enemy is removed from the world before it can strike.
+    /// Returns false if the player died from the exchange.
+    pub fn resolve<R: Rng>(
+        world: &mut World,
+        grid: &mut SpatialGrid,
+        player: &mut Player,
+        inventory: &mut Inventory,
+        game_events: &mut GameEvents,
+        target: Point,
+        rng: &mut R,
+    ) -> bool {
+        let backstab = player.sneaking() && is_unaware(world, grid, target);
+        if backstab {
+            game_events.post_event(
+                String::from("You strike from the shadows!"),
+                EventKind::Critical,
+            );
+        }
+        let strike = Self::strike(
+            world,
+            grid,
+            game_events,
+            target,
+            "hit",
+            rng,
+            |defense, rng| {
+                let result = resolve_attack(accuracy(player), weapon(inventory), defense, rng);
+                if backstab {
+                    result.times(BACKSTAB_MULTIPLIER)
+                } else {
+                    result
+                }
+            },
+        );
+        if let Strike::Survived(..) | Strike::Killed(..) = strike {
+            wear(inventory, game_events, EquipSlot::Weapon);
+        }
+        if let Strike::Survived(..) = strike {
+            afflict(
+                world,
+                grid,
+                game_events,
+                target,
+                inventory.weapon_on_hit_effect(),
+            );
+        }
+        let (name, enemy_damage) = match strike {
+            Strike::Survived(name, damage) | Strike::Dodged(name, damage) => (name, damage),
+            Strike::Killed(name, xp) => {
+                award_xp(player, game_events, &name, xp);
+                return true;
+            }
+            Strike::Missed => return true,
+        };
+
+        Self::counter(player, inventory, game_events, &name, enemy_damage, rng)
+    }
+
+    /// Hurt the player with the trap they stepped on.
+    /// Returns false if the player died from it.
+    pub fn spring_trap<R: Rng>(
+        player: &mut Player,
+        trap: &GameCell,
+        game_events: &mut GameEvents,
+        rng: &mut R,
+    ) -> bool {
+        let damage = trap.damage().map_or(0, |dice| dice.roll(rng));
+        player.set_health(player.health() - damage);
+        game_events.post_event(
+            format!("You step on a {}! It deals {} damage.", trap.name(), damage),
+            EventKind::Warning,
+        );
+
+        if player.health() <= 0 {
+            game_events.post_event(String::from("You died."), EventKind::Combat);
+            player
+                .stats_mut()
+                .record_death(&format!("Impaled on a {}", trap.name()));
+            false
+        } else {
+            true
+        }
+    }
+
+    /// Shoot the hostile cell at `target` with the wielded weapon.
+    /// Nothing can hit back from across the room.
+    pub fn shoot<R: Rng>(
+        world: &mut World,
+        grid: &mut SpatialGrid,
+        player: &mut Player,
+        inventory: &mut Inventory,
+        game_events: &mut GameEvents,
+        target: Point,
+        rng: &mut R,
+    ) {
+        let strike = Self::strike(
+            world,
+            grid,
+            game_events,
+            target,
+            "shoot",
+            rng,
+            |defense, rng| resolve_attack(accuracy(player), weapon(inventory), defense, rng),
+        );
+        if let Strike::Survived(..) | Strike::Killed(..) = strike {
+            wear(inventory, game_events, EquipSlot::Weapon);
+        }
+        if let Strike::Killed(name, xp) = strike {
+            award_xp(player, game_events, &name, xp);
+        }
+    }
+
+    /// Hit the hostile cell at `target` with something thrown that does `damage`
+    pub fn hurl<R: Rng>(
+        world: &mut World,
+        grid: &mut SpatialGrid,
+        player: &mut Player,
+        game_events: &mut GameEvents,
+        target: Point,
+        damage: Dice,
+        rng: &mut R,
+    ) {
+        let strike = Self::strike(
+            world,
+            grid,
+            game_events,
+            target,
+            "hit",
+            rng,
+            |defense, rng| resolve_attack(accuracy(player), damage, defense, rng),
+        );
+        if let Strike::Killed(name, xp) = strike {
+            award_xp(player, game_events, &name, xp);
+        }
+    }
+
+    /// Swipe the wielded weapon through the three tiles in front of the player,
+    /// striking every enemy in them once. Every enemy left standing hits back.
+    /// Returns false if the player died from the exchange.
+    pub fn swipe<R: Rng>(
+        world: &mut World,
+        grid: &mut SpatialGrid,
+        player: &mut Player,
+        inventory: &mut Inventory,
+        game_events: &mut GameEvents,
+        rng: &mut R,
+    ) -> bool {
+        // A large enemy filling several tiles of the arc only gets struck once
+        let mut struck = Vec::new();
+        let mut targets = Vec::new();
+        for tile in arc(player.point(), player.facing()).iter().copied() {
+            if let Some(enemy) = enemy_at(world, grid, tile) {
+                if !struck.contains(&enemy) {
+                    struck.push(enemy);
+                    targets.push(tile);
+                }
+            }
+        }
+        if targets.is_empty() {
+            game_events.post_event(String::from("You swipe at empty air."), EventKind::Info);
+            return true;
+        }
+
+        let mut landed = false;
+        let mut survivors = Vec::new();
+        for target in targets {
+            let strike = Self::strike(
+                world,
+                grid,
+                game_events,
+                target,
+                "slash",
+                rng,
+                |defense, rng| resolve_attack(accuracy(player), weapon(inventory), defense, rng),
+            );
+            match strike {
+                Strike::Killed(name, xp) => {
+                    landed = true;
+                    award_xp(player, game_events, &name, xp);
+                }
+                Strike::Survived(name, damage) => {
+                    landed = true;
+                    afflict(
+                        world,
+                        grid,
+                        game_events,
+                        target,
+                        inventory.weapon_on_hit_effect(),
+                    );
+                    survivors.push((name, damage));
+                }
+                Strike::Dodged(name, damage) => survivors.push((name, damage)),
+                Strike::Missed => (),
+            }
+        }
+        if landed {
+            wear(inventory, game_events, EquipSlot::Weapon);
+        }
+
+        survivors
+            .into_iter()
+            .all(|(name, damage)| Self::counter(player, inventory, game_events, &name, damage, rng))
+    }
+
+    /// Hurt every enemy that's bleeding or poisoned a point, and count its effects down.
+    /// Regeneration stops an enemy's bleeding before it can hurt it.
+    /// An enemy that dies of it is worth as much XP as one the player killed.
+    pub fn run_effects<R: Rng>(
+        world: &mut World,
+        grid: &mut SpatialGrid,
+        player: &mut Player,
+        game_events: &mut GameEvents,
+        rng: &mut R,
+    ) {
+        let mut query = <(Write<GameCell>, Write<StatusEffects>)>::query();
+
+        let mut slain = Vec::new();
+        for chunk in query.iter_chunks_mut(world) {
+            for (entity, (cell, effects)) in chunk.into_iter_entities() {
+                if effects.has(Effect::Regeneration) && effects.cure(Effect::Bleed) {
+                    game_events.post_event(
+                        format!("The {}'s wounds close.", cell.name()),
+                        EventKind::Combat,
+                    );
+                }
+                for effect in effects.active() {
+                    match effect {
+                        Effect::Poison | Effect::Bleed => {
+                            let dead = cell.take_damage(1);
+                            game_events.post_event(
+                                format!(
+                                    "The {} takes 1 damage from {}.",
+                                    cell.name(),
+                                    effect.name()
+                                ),
+                                EventKind::Combat,
+                            );
+                            if dead {
+                                slain.push((entity, cell.name(), cell.tiles(), cell.health()));
+                                break;
+                            }
+                        }
+                        Effect::Regeneration | Effect::Haste => (),
+                    }
+                }
+                effects.tick();
+            }
+        }
+
+        for (enemy, name, tiles, health) in slain {
+            game_events.post_event(
+                format!("The {} succumbs to its wounds.", name),
+                EventKind::Combat,
+            );
+            slay(world, grid, game_events, enemy, &name, &tiles, rng);
+            award_xp(player, game_events, &name, health.map_or(1, |(_, max)| max));
+        }
+    }
+
+    /// Let the enemy at `from` take a free swing at the player as they step away from it.
+    /// Returns false if that killed the player.
+    pub fn opportunity_attack<R: Rng>(
+        world: &mut World,
+        grid: &SpatialGrid,
+        player: &mut Player,
+        inventory: &mut Inventory,
+        game_events: &mut GameEvents,
+        from: Point,
+        rng: &mut R,
+    ) -> bool {
+        let enemy = match enemy_at(world, grid, from) {
+            Some(enemy) => enemy,
+            None => return true,
+        };
+        let (name, damage) = match world.entry(enemy) {
+            Some(entry) => match entry.get_component::<GameCell>() {
+                Ok(cell) => (cell.name(), cell.damage()),
+                Err(_) => return true,
+            },
+            None => return true,
+        };
+        game_events.post_event(
+            format!("The {} strikes as you turn to flee!", name),
+            EventKind::Warning,
+        );
+
+        Self::counter(player, inventory, game_events, &name, damage, rng)
+    }
+
+    /// Let the enemy called `name` hit back at the player with `damage`.
+    /// Returns false if that killed the player.
+    fn counter<R: Rng>(
+        player: &mut Player,
+        inventory: &mut Inventory,
+        game_events: &mut GameEvents,
+        name: &str,
+        damage: Option<Dice>,
+        rng: &mut R,
+    ) -> bool {
+        let damage = damage.unwrap_or_else(|| Dice::new(1, 3));
+        match resolve_attack(ENEMY_ACCURACY, damage, inventory.defense(), rng) {
+            AttackResult::Miss => {
+                game_events.post_event(format!("The {} misses you.", name), EventKind::Combat);
+            }
+            AttackResult::Hit(damage) => {
+                wear(inventory, game_events, EquipSlot::Armor);
+                wear(inventory, game_events, EquipSlot::OffHand);
+                player.set_health(player.health() - damage);
+                game_events.post_event(
+                    format!("The {} hits you for {} damage.", name, damage),
+                    EventKind::Combat,
+                );
+            }
+            AttackResult::Crit(damage) => {
+                wear(inventory, game_events, EquipSlot::Armor);
+                wear(inventory, game_events, EquipSlot::OffHand);
+                player.set_health(player.health() - damage);
+                game_events.post_event(
+                    format!("The {} critically hits you for {} damage!", name, damage),
+                    EventKind::Critical,
+                );
+            }
+        }
+
+        if player.health() <= 0 {
+            game_events.post_event(String::from("You died."), EventKind::Combat);
+            player
+                .stats_mut()
+                .record_death(&format!("Killed by a {}", name));
+            false
+        } else {
+            true
+        }
+    }
+
+    /// Attack the hostile cell at `target` with the result of `roll` against its defense,
+    /// removing it if slain and leaving behind whatever it drops
+    fn strike<R: Rng, F: FnOnce(i32, &mut R) -> AttackResult>(
+        world: &mut World,
+        grid: &mut SpatialGrid,
+        game_events: &mut GameEvents,
+        target: Point,
+        verb: &str,
+        rng: &mut R,
+        roll: F,
+    ) -> Strike {
+        let enemy = match enemy_at(world, grid, target) {
+            Some(enemy) => enemy,
+            None => return Strike::Missed,
+        };
+
+        let defense = match world.entry(enemy) {
+            Some(entry) => match entry.get_component::<GameCell>() {
+                Ok(cell) => cell.defense(),
+                Err(_) => return Strike::Missed,
+            },
+            None => return Strike::Missed,
+        };
+        let result = roll(defense, rng);
+        let dealt = match result {
+            AttackResult::Miss => 0,
+            AttackResult::Hit(dealt) | AttackResult::Crit(dealt) => dealt,
+        };
+
+        let (name, enemy_damage, health, tiles, slain) = match world.entry(enemy) {
+            Some(mut entry) => match entry.get_component_mut::<GameCell>() {
+                Ok(cell) => {
+                    let slain = cell.take_damage(dealt as u32);
+                    (
+                        cell.name(),
+                        cell.damage(),
+                        cell.health(),
+                        cell.tiles(),
+                        slain,
+                    )
+                }
+                Err(_) => return Strike::Missed,
+            },
+            None => return Strike::Missed,
+        };
+
+        match result {
+            AttackResult::Miss => {
+                game_events.post_event(format!("You miss the {}.", name), EventKind::Combat);
+                return Strike::Dodged(name, enemy_damage);
+            }
+            AttackResult::Hit(_) => game_events.post_event(
+                format!("You {} the {} for {} damage.", verb, name, dealt),
+                EventKind::Combat,
+            ),
+            AttackResult::Crit(_) => game_events.post_event(
+                format!("You critically {} the {} for {} damage!", verb, name, dealt),
+                EventKind::Critical,
+            ),
+        }
+        if slain {
+            game_events.post_event(format!("You kill the {}.", name), EventKind::Combat);
+            slay(world, grid, game_events, enemy, &name, &tiles, rng);
+            return Strike::Killed(name, health.map_or(1, |(_, max)| max));
+        }
+        if let Some((cur, max)) = health {
+            game_events.post_event(
+                format!("The {} is at {}", name, hp_bar(cur, max, HP_BAR_WIDTH)),
+                EventKind::Combat,
+            );
+        }
+
+        Strike::Survived(name, enemy_damage)
+    }
+}
+
+/// Return the hostile cell at `point`, if there is one
+fn enemy_at(world: &mut World, grid: &SpatialGrid, point: Point) -> Option<Entity> {
+    grid.at(point).iter().copied().find(|&entity| {
+        world.entry(entity).map_or(false, |entry| {
+            entry
+                .get_component::<GameCell>()
+                .map_or(false, |cell| cell.access() == CellAccess::Hostile)
+        })
+    })
+}
+
+/// Take the slain enemy called `name` off its `tiles`,
+/// leaving behind whatever it drops
+fn slay<R: Rng>(
+    world: &mut World,
+    grid: &mut SpatialGrid,
+    game_events: &mut GameEvents,
+    enemy: Entity,
+    name: &str,
+    tiles: &[Point],
+    rng: &mut R,
+) {
+    world.remove(enemy);
+    for &tile in tiles {
+        grid.remove(tile, enemy);
+    }
+    if let Some(item) = LootTable::new().roll(name, rng) {
+        game_events.post_event(
+            format!("The {} dropped a {}.", name, item.name()),
+            EventKind::Loot,
+        );
+        // Drop it where the enemy fell, or beside it if something's already there
+        let spots = tiles.iter().flat_map(|&tile| {
+            (-1..=1)
+                .flat_map(move |dy| (-1..=1).map(move |dx| Point::new(tile.x + dx, tile.y + dy)))
+        });
+        let mut spots = Some(tiles[0]).into_iter().chain(spots);
+        spots.any(|spot| {
+            grid.place_cell(
+                world,
+                item.clone().with_point(spot),
+                CellVisibility::Visible,
+            )
+            .is_some()
+        });
+    }
+}
+
+/// Leave `effect` on the enemy at `target` for as many turns as it lasts,
+/// stacking onto any of it the enemy already has
+pub fn afflict(
+    world: &mut World,
+    grid: &SpatialGrid,
+    game_events: &mut GameEvents,
+    target: Point,
+    effect: Option<(Effect, u32)>,
+) {
+    let (effect, turns) = match effect {
+        Some(effect) => effect,
+        None => return,
+    };
+    let enemy = match enemy_at(world, grid, target) {
+        Some(enemy) => enemy,
+        None => return,
+    };
+    let mut entry = match world.entry(enemy) {
+        Some(entry) => entry,
+        None => return,
+    };
+    let name = match entry.get_component::<GameCell>() {
+        Ok(cell) => cell.name(),
+        Err(_) => return,
+    };
+
+    if let Ok(effects) = entry.get_component_mut::<StatusEffects>() {
+        let worse = effects.has(effect);
+        effects.stack(effect, turns);
+        if worse {
+            game_events.post_event(
+                format!("The {}'s {} worsens.", name, effect.name()),
+                EventKind::Combat,
+            );
+            return;
+        }
+    } else {
+        let mut effects = StatusEffects::default();
+        effects.stack(effect, turns);
+        entry.add_component(effects);
+    }
+    game_events.post_event(
+        format!("The {} suffers from {}.", name, effect.name()),
+        EventKind::Combat,
+    );
+}
+
+/// Return whether the enemy at `point` hasn't noticed the player
+fn is_unaware(world: &mut World, grid: &SpatialGrid, point: Point) -> bool {
+    let enemy = match enemy_at(world, grid, point) {
+        Some(enemy) => enemy,
+        None => return false,
+    };
+    world.entry(enemy).map_or(false, |entry| {
+        entry
+            .get_component::<EnemyAI>()
+            .map_or(false, |ai| ai.state != AiState::Chasing)
+    })
+}
+
+/// Return the tile `facing` points at from `origin` and the tile on either side of it,
+/// the arc a swipe sweeps through
+pub fn arc(origin: Point, facing: (i32, i32)) -> [Point; 3] {
+    let (dx, dy) = facing;
+    let left = ((dx + dy).signum(), (dy - dx).signum());
+    let right = ((dx - dy).signum(), (dx + dy).signum());
+    let step = |(x, y): (i32, i32)| Point::new(origin.x + x, origin.y + y);
+    [step(left), step(facing), step(right)]
+}
+
+/// Return the damage of the wielded weapon, or of bare fists
+fn weapon(inventory: &Inventory) -> Dice {
+    inventory.weapon_damage().unwrap_or_else(|| Dice::new(1, 2))
+}
+
+/// Return how accurate the player is, getting better with every level
+fn accuracy(player: &Player) -> i32 {
+    player.level() as i32
+}
+
+/// Wear down what's equipped in `slot`, announcing it if it breaks
+fn wear(inventory: &mut Inventory, game_events: &mut GameEvents, slot: EquipSlot) {
+    if let Some(name) = inventory.wear(slot) {
+        game_events.post_event(format!("Your {} shatters!", name), EventKind::Warning);
+    }
+}
+
+/// Give the player `xp` for a kill, announcing any levels gained.
+/// Also counts the kill, in the stats and against the monster called `name` in the bestiary.
+/// Tougher enemies are worth more.
+fn award_xp(player: &mut Player, game_events: &mut GameEvents, name: &str, xp: u32) {
+    player.stats_mut().record_kill();
+    player.bestiary_mut().record_kill(name);
+    for level in player.gain_xp(xp) {
+        game_events.post_event(format!("Welcome to level {}!", level), EventKind::Loot);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use crate::types::CellKind;
+
+    /// Return the results of `rolls` attacks rolled from a fixed seed
+    fn attacks(accuracy: i32, defense: i32, rolls: usize) -> Vec<AttackResult> {
+        let mut rng = StdRng::seed_from_u64(42);
+        (0..rolls)
+            .map(|_| resolve_attack(accuracy, Dice::new(1, 6), defense, &mut rng))
+            .collect()
+    }
+
+    #[test]
+    fn a_sure_hit_only_misses_on_a_natural_one() {
+        let results = attacks(100, 0, 1000);
+        let misses = results
+            .iter()
+            .filter(|&&result| result == AttackResult::Miss)
+            .count();
+        assert!(misses > 0 && misses < 100, "{} misses", misses);
+        for result in results {
+            match result {
+                AttackResult::Miss => (),
+                AttackResult::Hit(dealt) => assert!((1..=6).contains(&dealt)),
+                AttackResult::Crit(dealt) => assert!((2..=12).contains(&dealt)),
+            }
+        }
+    }
+
+    #[test]
+    fn a_hopeless_attack_only_lands_on_a_natural_twenty() {
+        let results = attacks(-100, 3, 1000);
+        assert!(results.contains(&AttackResult::Miss));
+        for result in results {
+            match result {
+                AttackResult::Miss => (),
+                // Defense soaks the whole blow but a crit still does at least 1, doubled
+                AttackResult::Crit(dealt) => assert!((2..=6).contains(&dealt)),
+                AttackResult::Hit(_) => panic!("a hopeless attack hit"),
+            }
+        }
+    }
+
+    #[test]
+    fn the_same_seed_rolls_the_same_attacks() {
+        assert_eq!(attacks(2, 2, 50), attacks(2, 2, 50));
+    }
+
+    #[test]
+    fn rolls_a_known_sequence_from_a_known_seed() {
+        use AttackResult::*;
+
+        // A d20 of 8 just lands against a defense of 1 and a 6 just misses,
+        // the two natural 20s double the blow, and every hit has 1 soaked off it
+        assert_eq!(
+            attacks(2, 1, 15),
+            vec![
+                Hit(2),
+                Miss,
+                Miss,
+                Hit(2),
+                Miss,
+                Hit(5),
+                Hit(1),
+                Hit(4),
+                Miss,
+                Crit(8),
+                Crit(10),
+                Hit(3),
+                Hit(4),
+                Miss,
+                Miss,
+            ]
+        );
+    }
+
+    #[test]
+    fn swipes_the_facing_tile_and_both_beside_it() {
+        let origin = Point::new(5, 5);
+        let table = [
+            ((1, 0), [(1, -1), (1, 0), (1, 1)]),
+            ((1, 1), [(1, 0), (1, 1), (0, 1)]),
+            ((0, 1), [(1, 1), (0, 1), (-1, 1)]),
+            ((-1, 1), [(0, 1), (-1, 1), (-1, 0)]),
+            ((-1, 0), [(-1, 1), (-1, 0), (-1, -1)]),
+            ((-1, -1), [(-1, 0), (-1, -1), (0, -1)]),
+            ((0, -1), [(-1, -1), (0, -1), (1, -1)]),
+            ((1, -1), [(0, -1), (1, -1), (1, 0)]),
+        ];
+        for &(facing, offsets) in table.iter() {
+            let expected = [
+                Point::new(origin.x + offsets[0].0, origin.y + offsets[0].1),
+                Point::new(origin.x + offsets[1].0, origin.y + offsets[1].1),
+                Point::new(origin.x + offsets[2].0, origin.y + offsets[2].1),
+            ];
+            assert_eq!(arc(origin, facing), expected, "facing {:?}", facing);
+        }
+    }
+
+    /// Return the player's and the enemy's health after one exchange of blows
+    /// rolled from `seed`
+    fn exchange(seed: u64) -> (i32, Option<(u32, u32)>) {
+        let target = Point::new(1, 0);
+        let mut world = World::default();
+        world.push((
+            GameCell::new(
+                target,
+                CellKind::Enemy,
+                "orc",
+                RGB::named(GRAY),
+                CellAccess::Hostile,
+            )
+            .with_health(100)
+            .with_damage(Dice::new(1, 4)),
+            CellVisibility::Visible,
+            EnemyAI {
+                state: AiState::Chasing,
+                ..EnemyAI::new()
+            },
+        ));
+        let mut grid = SpatialGrid::new(&world);
+        let mut player = Player::new(Point::new(0, 0));
+        let mut inventory = Inventory::new();
+        let mut game_events = GameEvents::new();
+
+        CombatSystem::resolve(
+            &mut world,
+            &mut grid,
+            &mut player,
+            &mut inventory,
+            &mut game_events,
+            target,
+            &mut StdRng::seed_from_u64(seed),
+        );
+        let enemy = <(Read<GameCell>,)>::query()
+            .iter(&world)
+            .next()
+            .and_then(|(cell,)| cell.health());
+        (player.health(), enemy)
+    }
+
+    #[test]
+    fn the_same_seed_deals_the_same_damage() {
+        for seed in 0..20 {
+            assert_eq!(exchange(seed), exchange(seed), "seed {}", seed);
+        }
+        // Only bare fists against a d4, so nobody dies in a single exchange
+        let (player, enemy) = exchange(0);
+        assert!(player > 0 && player <= 10);
+        assert!(enemy.map_or(false, |(cur, _)| cur >= 100 - 4));
+    }
+}
@@ -0,0 +1,13 @@
+mod ai;
+mod combat;
+mod movement;
+mod regen;
+mod search;
+mod status;
+
+pub use ai::AiSystem;
+pub use combat::{afflict, CombatSystem};
+pub use movement::{MoveOutcome, MovementSystem};
+pub use regen::RegenSystem;
+pub use search::SearchSystem;
+pub use status::StatusSystem;
@@ -0,0 +1,359 @@
+use bracket_lib::prelude::*;
+
+use legion::*;
+
+use crate::{
+    components::{AiState, EnemyAI, GameCell},
+    spatial::SpatialGrid,
+    types::{CellAccess, CellKind, EventKind, GameEvents, Player},
+};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MoveOutcome {
+    Moved,
+    Attacked(Point),
+    OpenedDoor,
+    /// Bumped into the locked door at this point, with the id of its lock
+    Locked(Point, u32),
+    /// Bumped into the closed chest at this point
+    Chest(Point),
+    Shop,
+    Blocked,
+}
+
+impl MoveOutcome {
+    /// Whether the attempted move used up the player's turn
+    pub fn took_turn(self) -> bool {
+        matches!(
+            self,
+            MoveOutcome::Moved | MoveOutcome::Attacked(_) | MoveOutcome::OpenedDoor
+        )
+    }
+}
+
+pub struct MovementSystem;
+
+impl MovementSystem {
+    /// Turn the player toward `dx` and `dy` and try to step them there in world coordinates,
+    /// handing the first cell in the way to `resolve_bump`.
+    /// With a weapon of more than 1 `reach` an enemy further along that line is attacked
+    /// instead of stepping, unless something in between blocks the way.
+    pub fn try_move(
+        world: &mut World,
+        grid: &SpatialGrid,
+        player: &mut Player,
+        game_events: &mut GameEvents,
+        dx: i32,
+        dy: i32,
+        reach: i32,
+    ) -> MoveOutcome {
+        player.face(dx, dy);
+        let target = Point::new(player.x() + dx, player.y() + dy);
+
+        for &entity in grid.at(target) {
+            let mut entry = match world.entry(entity) {
+                Some(entry) => entry,
+                None => continue,
+            };
+            let cell = match entry.get_component_mut::<GameCell>() {
+                Ok(cell) => cell,
+                Err(_) => continue,
+            };
+            if let Some(outcome) = Self::resolve_bump(cell, target, game_events) {
+                return outcome;
+            }
+        }
+
+        if let Some(target) = Self::in_reach(world, grid, target, dx, dy, reach) {
+            return MoveOutcome::Attacked(target);
+        }
+
+        player.move_pos(dx, dy);
+        MoveOutcome::Moved
+    }
+
+    /// Return a tile of every enemy that's after the player and would be left behind
+    /// by a step from `from` to `to`, once per enemy however many tiles it fills
+    pub fn opportunists(
+        world: &mut World,
+        grid: &SpatialGrid,
+        from: Point,
+        to: Point,
+    ) -> Vec<Point> {
+        let near = |a: Point, b: Point| (a.x - b.x).abs() <= 1 && (a.y - b.y).abs() <= 1;
+
+        let mut seen = Vec::new();
+        let mut opportunists = Vec::new();
+        for dy in -1..=1 {
+            for dx in -1..=1 {
+                let tile = Point::new(from.x + dx, from.y + dy);
+                for &entity in grid.at(tile) {
+                    if seen.contains(&entity) {
+                        continue;
+                    }
+                    let entry = match world.entry(entity) {
+                        Some(entry) => entry,
+                        None => continue,
+                    };
+                    let chasing = entry
+                        .get_component::<EnemyAI>()
+                        .map_or(false, |ai| ai.state == AiState::Chasing);
+                    let left = entry.get_component::<GameCell>().map_or(false, |cell| {
+                        cell.access() == CellAccess::Hostile
+                            && !cell.tiles().iter().any(|&tile| near(tile, to))
+                    });
+                    if chasing && left {
+                        seen.push(entity);
+                        opportunists.push(tile);
+                    }
+                }
+            }
+        }
+        opportunists
+    }
+
+    /// Return where the first enemy is past the clear tile at `from`, looking along
+    /// `dx` and `dy` until `reach` tiles from the player or something that stands in the way
+    pub fn in_reach(
+        world: &mut World,
+        grid: &SpatialGrid,
+        from: Point,
+        dx: i32,
+        dy: i32,
+        reach: i32,
+    ) -> Option<Point> {
+        for step in 1..reach {
+            let point = Point::new(from.x + dx * step, from.y + dy * step);
+            if grid.any_at(world, point, |cell| cell.access() == CellAccess::Hostile) {
+                return Some(point);
+            }
+            if grid.any_at(world, point, |cell| {
+                cell.access() == CellAccess::Impassable || cell.kind().is_opaque()
+            }) {
+                return None;
+            }
+        }
+        None
+    }
+
+    /// Work out what bumping into `cell` at `target` does, or `None` if the cell
+    /// doesn't stand in the way. New kinds of bump interaction get a branch here.
+    pub fn resolve_bump(
+        cell: &mut GameCell,
+        target: Point,
+        game_events: &mut GameEvents,
+    ) -> Option<MoveOutcome> {
+        match (cell.kind(), cell.access(), cell.lock()) {
+            (_, CellAccess::Hostile, _) => Some(MoveOutcome::Attacked(target)),
+            (CellKind::Shopkeeper, _, _) => Some(MoveOutcome::Shop),
+            (CellKind::LockedDoor, _, Some(lock)) => Some(MoveOutcome::Locked(target, lock)),
+            (CellKind::Chest, _, _) if !cell.contents().is_empty() => {
+                Some(MoveOutcome::Chest(target))
+            }
+            (CellKind::ClosedDoor, _, _) => Some(Self::open_door(cell, game_events)),
+            (_, CellAccess::Impassable, _) => Some(Self::block(cell, game_events)),
+            _ => None,
+        }
+    }
+
+    fn open_door(cell: &mut GameCell, game_events: &mut GameEvents) -> MoveOutcome {
+        cell.set_kind(CellKind::OpenedDoor);
+        cell.set_access(CellAccess::Static);
+        game_events.post_event(String::from("You open the door."), EventKind::Info);
+        MoveOutcome::OpenedDoor
+    }
+
+    fn block(cell: &GameCell, game_events: &mut GameEvents) -> MoveOutcome {
+        game_events.post_event(
+            format!("You ran into the {}.", cell.name()),
+            EventKind::Info,
+        );
+        MoveOutcome::Blocked
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::components::CellVisibility;
+
+    /// Return a world holding a cell of each `kind` and `access` at its point, and a grid over it
+    fn level(cells: &[(CellKind, CellAccess, Point)]) -> (World, SpatialGrid) {
+        let mut world = World::default();
+        for &(kind, access, point) in cells {
+            world.push((
+                GameCell::new(point, kind, kind.name(), RGB::named(GRAY), access),
+                CellVisibility::Visible,
+            ));
+        }
+        let grid = SpatialGrid::new(&world);
+        (world, grid)
+    }
+
+    #[test]
+    fn reaches_an_enemy_past_an_empty_tile() {
+        let enemy = Point::new(7, 5);
+        let (mut world, grid) = level(&[(CellKind::Enemy, CellAccess::Hostile, enemy)]);
+        assert_eq!(
+            MovementSystem::in_reach(&mut world, &grid, Point::new(6, 5), 1, 0, 2),
+            Some(enemy)
+        );
+    }
+
+    #[test]
+    fn reach_stops_at_a_wall() {
+        let (mut world, grid) = level(&[
+            (CellKind::Wall, CellAccess::Impassable, Point::new(7, 5)),
+            (CellKind::Enemy, CellAccess::Hostile, Point::new(8, 5)),
+        ]);
+        assert_eq!(
+            MovementSystem::in_reach(&mut world, &grid, Point::new(6, 5), 1, 0, 3),
+            None
+        );
+    }
+
+    #[test]
+    fn each_enemy_left_behind_strikes_once() {
+        let mut world = World::default();
+        let chasing = EnemyAI {
+            state: AiState::Chasing,
+            ..EnemyAI::new()
+        };
+        let enemy = |x, y| {
+            GameCell::new(
+                Point::new(x, y),
+                CellKind::Enemy,
+                "enemy",
+                RGB::named(GRAY),
+                CellAccess::Hostile,
+            )
+        };
+        // Two tiles tall, both next to the player
+        world.push((
+            enemy(6, 4).with_footprint(&[Point::new(0, 1)]),
+            CellVisibility::Visible,
+            chasing,
+        ));
+        world.push((enemy(6, 6), CellVisibility::Visible, chasing));
+        // Still next to the player after the step
+        world.push((enemy(4, 4), CellVisibility::Visible, chasing));
+        let grid = SpatialGrid::new(&world);
+
+        assert_eq!(
+            MovementSystem::opportunists(&mut world, &grid, Point::new(5, 5), Point::new(4, 5)),
+            vec![Point::new(6, 4), Point::new(6, 6)]
+        );
+    }
+
+    #[test]
+    fn bumping_routes_on_what_was_bumped() {
+        let target = Point::new(1, 0);
+        let cell = |kind: CellKind, access| {
+            GameCell::new(target, kind, kind.name(), RGB::named(GRAY), access)
+        };
+        let full_chest = cell(CellKind::Chest, CellAccess::Impassable)
+            .with_contents(vec![cell(CellKind::Gold, CellAccess::Takeable)]);
+        let table = vec![
+            (
+                cell(CellKind::Enemy, CellAccess::Hostile),
+                Some(MoveOutcome::Attacked(target)),
+                None,
+            ),
+            (
+                cell(CellKind::Shopkeeper, CellAccess::Impassable),
+                Some(MoveOutcome::Shop),
+                None,
+            ),
+            (full_chest, Some(MoveOutcome::Chest(target)), None),
+            (
+                cell(CellKind::ClosedDoor, CellAccess::Impassable),
+                Some(MoveOutcome::OpenedDoor),
+                Some("You open the door."),
+            ),
+            (
+                cell(CellKind::Wall, CellAccess::Impassable),
+                Some(MoveOutcome::Blocked),
+                Some("You ran into the wall."),
+            ),
+            (cell(CellKind::Floor, CellAccess::Static), None, None),
+        ];
+
+        for (mut bumped, outcome, message) in table {
+            let kind = bumped.kind();
+            let mut game_events = GameEvents::new();
+            assert_eq!(
+                MovementSystem::resolve_bump(&mut bumped, target, &mut game_events),
+                outcome,
+                "{:?}",
+                kind
+            );
+            if let Some(message) = message {
+                assert!(game_events.has_posted(message), "{:?}", kind);
+            }
+        }
+    }
+
+    #[test]
+    fn a_bumped_door_stays_open() {
+        let mut door = GameCell::new(
+            Point::new(1, 0),
+            CellKind::ClosedDoor,
+            "door",
+            RGB::named(GRAY),
+            CellAccess::Impassable,
+        );
+        MovementSystem::resolve_bump(&mut door, Point::new(1, 0), &mut GameEvents::new());
+        assert_eq!(door.kind(), CellKind::OpenedDoor);
+        assert_eq!(door.access(), CellAccess::Static);
+    }
+
+    #[test]
+    fn bumping_a_locked_door_asks_for_its_key() {
+        let target = Point::new(1, 0);
+        let door = GameCell::new(
+            target,
+            CellKind::LockedDoor,
+            "locked door",
+            RGB::named(GRAY),
+            CellAccess::Impassable,
+        );
+        let mut game_events = GameEvents::new();
+        assert_eq!(
+            MovementSystem::resolve_bump(&mut door.clone().with_lock(3), target, &mut game_events),
+            Some(MoveOutcome::Locked(target, 3))
+        );
+        // A locked door without a lock to fit a key in is just in the way
+        assert_eq!(
+            MovementSystem::resolve_bump(&mut door.clone(), target, &mut game_events),
+            Some(MoveOutcome::Blocked)
+        );
+    }
+
+    #[test]
+    fn steps_onto_floor_and_stops_at_walls_and_enemies() {
+        let (mut world, grid) = level(&[
+            (CellKind::Floor, CellAccess::Static, Point::new(1, 0)),
+            (CellKind::Wall, CellAccess::Impassable, Point::new(2, 0)),
+            (CellKind::Enemy, CellAccess::Hostile, Point::new(1, 1)),
+        ]);
+        let mut player = Player::new(Point::new(0, 0));
+        let mut game_events = GameEvents::new();
+        let mut step = |player: &mut Player, dx, dy| {
+            MovementSystem::try_move(&mut world, &grid, player, &mut game_events, dx, dy, 1)
+        };
+
+        assert_eq!(step(&mut player, 1, 0), MoveOutcome::Moved);
+        assert_eq!(player.point(), Point::new(1, 0));
+
+        assert_eq!(step(&mut player, 1, 0), MoveOutcome::Blocked);
+        assert_eq!(player.point(), Point::new(1, 0));
+
+        assert_eq!(
+            step(&mut player, 0, 1),
+            MoveOutcome::Attacked(Point::new(1, 1))
+        );
+        assert_eq!(player.point(), Point::new(1, 0));
+        assert_eq!(player.facing(), (0, 1));
+    }
+}
@@ -0,0 +1,44 @@
+use legion::*;
+
+use crate::{
+    components::GameCell,
+    types::{CellAccess, EventKind, GameEvents, Player},
+};
+
+/// How many turns pass between each point of HP regained
+pub const REGEN_INTERVAL: u32 = 5;
+/// How many turns the player has to go without an enemy next to them to start healing
+pub const COMBAT_COOLDOWN: u32 = 5;
+
+pub struct RegenSystem;
+
+impl RegenSystem {
+    /// Heal the player a point every `REGEN_INTERVAL` turns once they're out of combat
+    pub fn run(world: &World, player: &mut Player, game_events: &mut GameEvents) {
+        let mut query = <(Read<GameCell>,)>::query();
+
+        let threatened = query.iter(world).any(|(cell,)| {
+            cell.access() == CellAccess::Hostile
+                && cell.tiles().iter().any(|tile| {
+                    (tile.x - player.x()).abs() <= 1 && (tile.y - player.y()).abs() <= 1
+                })
+        });
+        if threatened {
+            player.enter_combat();
+            return;
+        }
+
+        let max = player.max_health() as i32;
+        if player.turns_since_combat() < COMBAT_COOLDOWN
+            || player.turns() % REGEN_INTERVAL != 0
+            || player.health() >= max
+        {
+            return;
+        }
+
+        player.set_health(player.health() + 1);
+        if player.health() == max {
+            game_events.post_event(String::from("You feel fully rested."), EventKind::Info);
+        }
+    }
+}
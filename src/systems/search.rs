@@ -0,0 +1,125 @@
+use bracket_lib::prelude::*;
+
+use legion::*;
+
+use rand::Rng;
+
+use crate::{
+    components::GameCell,
+    spatial::SpatialGrid,
+    types::{CellKind, EventKind, GameEvents, Player},
+};
+
+/// Color of a door once it's been found
+const DOOR_COLOR: (u8, u8, u8) = (150, 75, 0);
+/// Chance out of 100 of finding a hidden door with a turn of searching at level 1
+const BASE_SEARCH_CHANCE: u32 = 25;
+/// How much the chance goes up for each level past the first
+const SEARCH_CHANCE_PER_LEVEL: u32 = 5;
+/// The chance never goes above this, so finding one always takes some luck
+const MAX_SEARCH_CHANCE: u32 = 90;
+
+pub struct SearchSystem;
+
+impl SearchSystem {
+    /// Spend a turn searching the eight tiles around the player, giving every
+    /// secret door there a `search_chance` of being found.
+    /// Returns how many were found.
+    pub fn run<R: Rng>(
+        world: &mut World,
+        grid: &SpatialGrid,
+        player: &Player,
+        game_events: &mut GameEvents,
+        rng: &mut R,
+    ) -> usize {
+        let chance = search_chance(player.level());
+        let mut found = 0;
+        for dy in -1..=1 {
+            for dx in -1..=1 {
+                let tile = Point::new(player.x() + dx, player.y() + dy);
+                for &entity in grid.at(tile) {
+                    let mut entry = match world.entry(entity) {
+                        Some(entry) => entry,
+                        None => continue,
+                    };
+                    let cell = match entry.get_component_mut::<GameCell>() {
+                        Ok(cell) => cell,
+                        Err(_) => continue,
+                    };
+                    if cell.kind() == CellKind::SecretDoor
+                        && rng.gen_range(0, 100) < chance
+                        && reveal(cell)
+                    {
+                        found += 1;
+                        game_events
+                            .post_event(String::from("You found a hidden door!"), EventKind::Loot);
+                    }
+                }
+            }
+        }
+        found
+    }
+}
+
+/// Return the chance out of 100 a turn of searching finds each hidden door
+/// next to a player at `level`
+pub fn search_chance(level: u32) -> u32 {
+    (BASE_SEARCH_CHANCE + SEARCH_CHANCE_PER_LEVEL * level.saturating_sub(1)).min(MAX_SEARCH_CHANCE)
+}
+
+/// Turn `cell` from a secret door into an ordinary closed door,
+/// returning whether it was a secret door
+pub fn reveal(cell: &mut GameCell) -> bool {
+    if cell.kind() != CellKind::SecretDoor {
+        return false;
+    }
+    cell.set_kind(CellKind::ClosedDoor);
+    cell.set_name("door");
+    cell.set_color(RGB::named(DOOR_COLOR));
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::types::CellAccess;
+
+    fn cell(kind: CellKind) -> GameCell {
+        GameCell::new(
+            Point::new(0, 0),
+            kind,
+            "wall",
+            RGB::from_u8(150, 150, 150),
+            CellAccess::Impassable,
+        )
+    }
+
+    #[test]
+    fn reveal_turns_a_secret_door_into_a_door() {
+        let mut door = cell(CellKind::SecretDoor);
+        assert!(reveal(&mut door));
+        assert_eq!(door.kind(), CellKind::ClosedDoor);
+        assert_eq!(door.name(), "door");
+        assert_eq!(door.color(), RGB::named(DOOR_COLOR));
+        assert!(!reveal(&mut door));
+    }
+
+    #[test]
+    fn reveal_leaves_walls_alone() {
+        let mut wall = cell(CellKind::Wall);
+        assert!(!reveal(&mut wall));
+        assert_eq!(wall.kind(), CellKind::Wall);
+        assert_eq!(wall.name(), "wall");
+    }
+
+    #[test]
+    fn search_chance_grows_with_level_up_to_a_cap() {
+        assert_eq!(search_chance(1), BASE_SEARCH_CHANCE);
+        assert_eq!(
+            search_chance(2),
+            BASE_SEARCH_CHANCE + SEARCH_CHANCE_PER_LEVEL
+        );
+        assert_eq!(search_chance(100), MAX_SEARCH_CHANCE);
+    }
+}
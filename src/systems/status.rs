@@ -0,0 +1,24 @@
+use crate::types::{Effect, EventKind, GameEvents, Player};
+
+pub struct StatusSystem;
+
+impl StatusSystem {
+    /// Apply a turn of every effect on the player, then count them down
+    /// and announce the ones that wore off
+    pub fn run(player: &mut Player, game_events: &mut GameEvents) {
+        if player.effects().has(Effect::Regeneration) && player.effects_mut().cure(Effect::Bleed) {
+            game_events.post_event(String::from("Your bleeding stops."), EventKind::Info);
+        }
+        for effect in player.effects().active() {
+            match effect {
+                Effect::Poison | Effect::Bleed => player.set_health(player.health() - 1),
+                Effect::Regeneration => player.set_health(player.health() + 1),
+                Effect::Haste => (),
+            }
+        }
+
+        for effect in player.effects_mut().tick() {
+            game_events.post_event(format!("The {} wears off.", effect.name()), EventKind::Info);
+        }
+    }
+}
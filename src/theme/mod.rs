@@ -0,0 +1,234 @@
+use std::{collections::HashMap, fs};
+
+use bracket_lib::prelude::*;
+
+use serde::Deserialize;
+
+use crate::{components::GameCell, types::CellKind};
+
+#[derive(Deserialize)]
+struct ThemeEntry {
+    symbol: Option<char>,
+    color: Option<(u8, u8, u8)>,
+}
+
+/// How each kind of cell is drawn.
+///
+/// Kinds without a color of their own are drawn in the color of each cell.
+#[derive(Clone, Debug, Default)]
+pub struct Theme {
+    /// Symbols and colors set in the theme file, drawn over the built-in ones
+    glyphs: HashMap<CellKind, (Option<char>, Option<RGB>)>,
+    /// Whether weapons and armor are drawn with the high-contrast set
+    high_contrast: bool,
+    /// Whether items are drawn as a letter naming what they are
+    letters: bool,
+}
+
+impl Theme {
+    /// Load a theme from the TOML file at `path`, falling back to the defaults.
+    ///
+    /// The file has a table per kind of cell, for example
+    /// `[wall]` followed by `symbol = "#"` and `color = [0, 0, 255]`.
+    /// Either key can be left out to keep its default.
+    /// Problems with the file are returned as warnings rather than failing.
+    pub fn load(path: &str) -> (Self, Vec<String>) {
+        let mut theme = Self::default();
+        let mut warnings = Vec::new();
+
+        let contents = match fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(_) => return (theme, warnings),
+        };
+        let table: HashMap<String, ThemeEntry> = match toml::from_str(&contents) {
+            Ok(table) => table,
+            Err(e) => {
+                warnings.push(format!("Could not read {}: {}", path, e));
+                return (theme, warnings);
+            }
+        };
+
+        for (name, entry) in table {
+            let kind = match CellKind::from_name(&name) {
+                Some(kind) => kind,
+                None => {
+                    warnings.push(format!("Unknown cell kind \"{}\" in {}.", name, path));
+                    continue;
+                }
+            };
+            theme.glyphs.insert(
+                kind,
+                (
+                    entry.symbol,
+                    entry.color.map(|(r, g, b)| RGB::from_u8(r, g, b)),
+                ),
+            );
+        }
+
+        (theme, warnings)
+    }
+
+    /// Draw weapons and armor with symbols and colors that are easier to tell apart
+    pub fn set_high_contrast(&mut self, high_contrast: bool) {
+        self.high_contrast = high_contrast;
+    }
+
+    /// Draw items as a letter, like `w` for a weapon, in place of any symbol
+    pub fn set_letters(&mut self, letters: bool) {
+        self.letters = letters;
+    }
+
+    pub fn symbol(&self, kind: CellKind) -> char {
+        if let Some(letter) = letter(kind).filter(|_| self.letters) {
+            return letter;
+        }
+        self.glyphs
+            .get(&kind)
+            .and_then(|glyph| glyph.0)
+            .or_else(|| {
+                high_contrast(kind)
+                    .filter(|_| self.high_contrast)
+                    .map(|glyph| glyph.0)
+            })
+            .unwrap_or_else(|| kind.symbol())
+    }
+
+    /// Return the symbol for a wall at `point`, joined up to the walls beside it
+    /// that `joins` knows of, unless the theme file gives walls a symbol of its own
+    pub fn wall_symbol<F: Fn(Point) -> bool>(&self, point: Point, joins: F) -> char {
+        self.glyphs
+            .get(&CellKind::Wall)
+            .and_then(|glyph| glyph.0)
+            .unwrap_or_else(|| wall_glyph(point, joins))
+    }
+
+    /// Return the color to draw `cell` in.
+    /// Anything that passes for a wall takes the color walls are given.
+    pub fn color(&self, cell: &GameCell) -> RGB {
+        let kind = if cell.kind().looks_like_wall() {
+            CellKind::Wall
+        } else {
+            cell.kind()
+        };
+        self.glyphs
+            .get(&kind)
+            .and_then(|glyph| glyph.1)
+            .or_else(|| {
+                high_contrast(kind)
+                    .filter(|_| self.high_contrast)
+                    .map(|(_, (r, g, b))| RGB::from_u8(r, g, b))
+            })
+            .unwrap_or_else(|| cell.color())
+    }
+
+    /// Return a brightened version of the color to draw `cell` in
+    pub fn color_bright(&self, cell: &GameCell) -> RGB {
+        let color = self.color(cell);
+        RGB::from_f32(color.r * 1.5, color.g * 1.5, color.b * 1.5)
+    }
+}
+
+/// Return the box-drawing character for a wall at `point` that joins up with each
+/// of the four tiles beside it that `joins` says is wall too, like `┌` for a corner.
+/// A wall with nothing known beside it to join up with is drawn as `#`.
+fn wall_glyph<F: Fn(Point) -> bool>(point: Point, joins: F) -> char {
+    let up = joins(Point::new(point.x, point.y - 1));
+    let down = joins(Point::new(point.x, point.y + 1));
+    let left = joins(Point::new(point.x - 1, point.y));
+    let right = joins(Point::new(point.x + 1, point.y));
+
+    match (up, down, left, right) {
+        (false, false, false, false) => '#',
+        (_, _, false, false) => '│',
+        (false, false, _, _) => '─',
+        (false, true, false, true) => '┌',
+        (false, true, true, false) => '┐',
+        (true, false, false, true) => '└',
+        (true, false, true, false) => '┘',
+        (true, true, false, true) => '├',
+        (true, true, true, false) => '┤',
+        (false, true, true, true) => '┬',
+        (true, false, true, true) => '┴',
+        (true, true, true, true) => '┼',
+    }
+}
+
+/// Return the high-contrast symbol and color for `kind`, if it has one.
+///
+/// Every weapon and armor gets its own shape as well as a color from a palette
+/// that stays distinct under the common kinds of color blindness.
+fn high_contrast(kind: CellKind) -> Option<(char, (u8, u8, u8))> {
+    match kind {
+        CellKind::BluntWeapon => Some(('♣', (230, 159, 0))),
+        CellKind::EdgedWeapon => Some(('♠', (86, 180, 233))),
+        CellKind::PointedWeapon => Some(('↑', (240, 228, 66))),
+        CellKind::RangedWeapon => Some(('Φ', (204, 121, 167))),
+        CellKind::SoftArmor => Some(('○', (0, 158, 115))),
+        CellKind::HardArmor => Some(('■', (255, 255, 255))),
+        CellKind::Shield => Some(('◘', (213, 94, 0))),
+        _ => None,
+    }
+}
+
+/// Return the letter `kind` is drawn as when items are drawn as letters
+fn letter(kind: CellKind) -> Option<char> {
+    match kind {
+        CellKind::BluntWeapon
+        | CellKind::EdgedWeapon
+        | CellKind::PointedWeapon
+        | CellKind::RangedWeapon => Some('w'),
+        CellKind::SoftArmor | CellKind::HardArmor | CellKind::Shield => Some('a'),
+        CellKind::Food => Some('f'),
+        CellKind::Potion => Some('p'),
+        CellKind::Torch => Some('t'),
+        CellKind::Key => Some('k'),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::types::CellAccess;
+
+    #[test]
+    fn secret_doors_take_the_wall_color() {
+        let mut theme = Theme::default();
+        let blue = RGB::from_u8(0, 0, 255);
+        theme.glyphs.insert(CellKind::Wall, (None, Some(blue)));
+
+        let door = GameCell::new(
+            Point::new(0, 0),
+            CellKind::SecretDoor,
+            "wall",
+            RGB::from_u8(150, 150, 150),
+            CellAccess::Impassable,
+        );
+        assert_eq!(theme.color(&door), blue);
+    }
+
+    /// Return the glyph for a wall at the origin joined to the walls at `offsets` from it
+    fn glyph(offsets: &[(i32, i32)]) -> char {
+        wall_glyph(Point::new(0, 0), |point| {
+            offsets.contains(&(point.x, point.y))
+        })
+    }
+
+    #[test]
+    fn walls_join_their_neighbours() {
+        assert_eq!(glyph(&[]), '#');
+        assert_eq!(glyph(&[(0, -1), (0, 1)]), '│');
+        assert_eq!(glyph(&[(0, 1)]), '│');
+        assert_eq!(glyph(&[(-1, 0), (1, 0)]), '─');
+        assert_eq!(glyph(&[(0, 1), (1, 0)]), '┌');
+        assert_eq!(glyph(&[(0, -1), (0, 1), (-1, 0), (1, 0)]), '┼');
+    }
+
+    #[test]
+    fn a_themed_wall_symbol_wins() {
+        let mut theme = Theme::default();
+        theme.glyphs.insert(CellKind::Wall, (Some('X'), None));
+        assert_eq!(theme.wall_symbol(Point::new(0, 0), |_| true), 'X');
+    }
+}
@@ -0,0 +1,60 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{components::GameCell, types::Dice};
+
+/// What the player has learned about one kind of monster
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+struct Entry {
+    /// Max health, damage and defense of the first one seen, if one has been seen
+    stats: Option<(u32, Option<Dice>, i32)>,
+    kills: u32,
+}
+
+/// Every kind of monster the player has come across, by name, kept with the save
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Bestiary {
+    entries: HashMap<String, Entry>,
+}
+
+impl Bestiary {
+    /// Note down `enemy` as seen, returning whether it's the first of its kind
+    pub fn record_sighting(&mut self, enemy: &GameCell) -> bool {
+        let entry = self.entries.entry(enemy.name()).or_default();
+        if entry.stats.is_some() {
+            return false;
+        }
+        let health = enemy.health().map_or(0, |(_, max)| max);
+        entry.stats = Some((health, enemy.damage(), enemy.defense()));
+        true
+    }
+
+    /// Count a kill of the monster called `name`
+    pub fn record_kill(&mut self, name: &str) {
+        self.entries.entry(name.to_owned()).or_default().kills += 1;
+    }
+
+    /// Return a line for each monster in `names`, in order, with what's known about it.
+    /// Monsters never come across show as "???".
+    pub fn lines(&self, names: &[String]) -> Vec<String> {
+        names
+            .iter()
+            .map(|name| match self.entries.get(name) {
+                None => String::from("???"),
+                Some(entry) => {
+                    let stats = match entry.stats {
+                        Some((health, damage, defense)) => format!(
+                            "HP {:<3} damage {:<6} defense {:<2}",
+                            health,
+                            damage.map_or(String::from("-"), |damage| damage.to_string()),
+                            defense
+                        ),
+                        None => String::from("never seen clearly"),
+                    };
+                    format!("{:<10} {} kills {}", name, stats, entry.kills)
+                }
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,39 @@
+use std::fmt;
+
+use rand::Rng;
+
+use serde::{Deserialize, Serialize};
+
+/// A roll of `count` dice with `sides` sides each, like 2d6
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Dice {
+    count: u32,
+    sides: u32,
+}
+
+impl Dice {
+    pub fn new(count: u32, sides: u32) -> Self {
+        Self { count, sides }
+    }
+
+    /// Return the same roll with `extra` more dice
+    pub fn with_extra_dice(self, extra: u32) -> Self {
+        Self::new(self.count + extra, self.sides)
+    }
+
+    pub fn roll<R: Rng>(self, rng: &mut R) -> i32 {
+        (0..self.count)
+            .map(|_| rng.gen_range(1, self.sides as i32 + 1))
+            .sum()
+    }
+}
+
+impl fmt::Display for Dice {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.count == 1 {
+            write!(f, "d{}", self.sides)
+        } else {
+            write!(f, "{}d{}", self.count, self.sides)
+        }
+    }
+}
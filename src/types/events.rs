@@ -1,35 +1,308 @@
-use std::collections::VecDeque;
+use std::{
+    collections::VecDeque,
+    fs::{File, OpenOptions},
+    io::{BufWriter, Write},
+    sync::{Arc, Mutex},
+};
 
 use bracket_lib::prelude::*;
 
-#[derive(Clone, Default, Debug)]
+use serde::{Deserialize, Serialize};
+
+const HISTORY: usize = 100;
+const SHOWN: usize = 5;
+/// Color of the line separating each turn's events
+const SEPARATOR: (u8, u8, u8) = (80, 80, 80);
+
+/// Number of marks in a health bar
+pub const HP_BAR_WIDTH: usize = 5;
+
+/// Draw `cur` out of `max` health as a bar `width` marks wide, like "[###--]".
+/// Anything still alive keeps at least one mark.
+pub fn hp_bar(cur: u32, max: u32, width: usize) -> String {
+    let filled = if max == 0 {
+        0
+    } else {
+        let (cur, max) = (cur.min(max) as usize, max as usize);
+        let filled = (cur * width * 2 + max) / (max * 2);
+        if cur > 0 {
+            filled.max(1).min(width)
+        } else {
+            filled
+        }
+    };
+    format!("[{}{}]", "#".repeat(filled), "-".repeat(width - filled))
+}
+
+/// Break `text` into lines no longer than `width`, between words.
+/// A word too long for a line of its own is split across as many as it needs.
+pub fn wrap(text: &str, width: usize) -> Vec<String> {
+    let width = width.max(1);
+    let mut lines: Vec<String> = Vec::new();
+    for word in text.split_whitespace() {
+        let mut word = word.chars().collect::<Vec<_>>();
+        match lines.last_mut() {
+            Some(line) if line.chars().count() + 1 + word.len() <= width => {
+                line.push(' ');
+                line.extend(word);
+                continue;
+            }
+            _ => (),
+        }
+        while word.len() > width {
+            lines.push(word.drain(..width).collect());
+        }
+        lines.push(word.into_iter().collect());
+    }
+    lines
+}
+
+/// Name `count` of the item called `name`, like "50 arrows", or just the name for one.
+/// Only the noun the name is built on goes plural, as in "3 potions of healing".
+pub fn counted(name: &str, count: usize) -> String {
+    if count == 1 {
+        return name.to_owned();
+    }
+    let (head, rest) = name.split_at(name.find(" of ").unwrap_or(name.len()));
+    let plural = if head.ends_with('s')
+        || head.ends_with('x')
+        || head.ends_with("ch")
+        || head.ends_with("sh")
+    {
+        format!("{}es", head)
+    } else {
+        format!("{}s", head)
+    };
+    format!("{} {}{}", count, plural, rest)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum EventKind {
+    Combat,
+    /// A critical hit, drawn brighter than the rest of combat
+    Critical,
+    Info,
+    Warning,
+    Loot,
+}
+
+impl EventKind {
+    pub fn color(self) -> RGB {
+        match self {
+            EventKind::Combat => RGB::from_u8(170, 0, 0),
+            EventKind::Critical => RGB::from_u8(255, 60, 60),
+            EventKind::Info => RGB::from_u8(255, 255, 255),
+            EventKind::Warning => RGB::from_u8(255, 170, 0),
+            EventKind::Loot => RGB::from_u8(0, 170, 0),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct Event {
+    content: String,
+    kind: EventKind,
+    repeats: u32,
+    /// Turn the event happened on
+    #[serde(default)]
+    turn: u32,
+}
+
+impl Event {
+    fn text(&self) -> String {
+        if self.repeats > 1 {
+            format!("{} (x{})", self.content, self.repeats)
+        } else {
+            self.content.clone()
+        }
+    }
+}
+
+#[derive(Clone, Default, Debug, Serialize, Deserialize)]
 pub struct GameEvents {
-    events: VecDeque<(String, RGB)>,
+    events: VecDeque<Event>,
+    scroll: usize,
+    /// File every event is also written to, if any
+    #[serde(skip)]
+    log: Option<Arc<Mutex<BufWriter<File>>>>,
+    #[serde(skip)]
+    turn: u32,
 }
 
 impl GameEvents {
     pub fn new() -> Self {
-        let mut events = VecDeque::with_capacity(5);
-        for _ in 0..5 {
-            events.push_back((String::from("\n"), RGB::new()));
+        Self {
+            events: VecDeque::with_capacity(HISTORY),
+            scroll: 0,
+            log: None,
+            turn: 0,
+        }
+    }
+
+    /// Append every event from now on to the file at `path`.
+    /// If it can't be opened the events only stay in memory, with a warning saying so.
+    pub fn open_log(&mut self, path: &str) {
+        match OpenOptions::new().create(true).append(true).open(path) {
+            Ok(file) => self.log = Some(Arc::new(Mutex::new(BufWriter::new(file)))),
+            Err(e) => self.post_event(
+                format!("Could not open log {}: {}", path, e),
+                EventKind::Warning,
+            ),
         }
-        Self { events }
     }
 
-    pub fn post_event(&mut self, content: String, color: RGB) {
-        self.events.pop_front();
-        self.events.push_back((content, color));
+    /// Bring back the history from a `saved` game, keeping the log file
+    /// and any events posted since after it
+    pub fn restore(&mut self, saved: GameEvents) {
+        let since = std::mem::replace(&mut self.events, saved.events);
+        for event in since {
+            if self.events.len() == HISTORY {
+                self.events.pop_front();
+            }
+            self.events.push_back(event);
+        }
+        self.scroll = 0;
+    }
+
+    /// Set the turn number written in front of logged events
+    pub fn set_turn(&mut self, turn: u32) {
+        self.turn = turn;
     }
 
-    pub fn print(&self, ctx: &mut BTerm, window_size: (u32, u32)) {
-        for (y, s) in self.events.iter().enumerate() {
-            ctx.print_color(
-                1,
-                window_size.1 as i32 - 6 + y as i32,
-                s.1,
-                RGB::new(),
-                &s.0,
-            );
+    /// Write out anything still buffered for the log file
+    pub fn flush_log(&self) {
+        if let Some(log) = &self.log {
+            if let Ok(mut log) = log.lock() {
+                let _ = log.flush();
+            }
         }
     }
+
+    /// Return whether an event reading `content` is still in the history
+    #[cfg(test)]
+    pub fn has_posted(&self, content: &str) -> bool {
+        self.events.iter().any(|event| event.content == content)
+    }
+
+    /// Add an event to the log, dropping the oldest once the history is full.
+    /// Repeating the latest event bumps its count instead of adding a line.
+    /// If the log is scrolled up the view stays on the same events.
+    pub fn post_event(&mut self, content: String, kind: EventKind) {
+        if let Some(log) = &self.log {
+            if let Ok(mut log) = log.lock() {
+                let _ = writeln!(log, "[{}] {}", self.turn, content);
+            }
+        }
+
+        let lines = self.lines().len();
+        match self.events.back_mut() {
+            Some(last) if last.content == content && last.kind == kind => {
+                last.repeats += 1;
+                last.turn = self.turn;
+            }
+            _ => {
+                if self.events.len() == HISTORY {
+                    self.events.pop_front();
+                }
+                self.events.push_back(Event {
+                    content,
+                    kind,
+                    repeats: 1,
+                    turn: self.turn,
+                });
+            }
+        }
+        if self.scroll > 0 {
+            let added = self.lines().len().saturating_sub(lines);
+            self.scroll = (self.scroll + added).min(self.max_scroll());
+        }
+    }
+
+    /// Return a line per event with its color, with a separator
+    /// naming the turn before each turn's events
+    fn lines(&self) -> Vec<(String, RGB)> {
+        let mut lines = Vec::with_capacity(self.events.len() * 2);
+        let mut turn = None;
+        for event in &self.events {
+            if turn != Some(event.turn) {
+                turn = Some(event.turn);
+                lines.push((format!("-- turn {} --", event.turn), RGB::named(SEPARATOR)));
+            }
+            lines.push((event.text(), event.kind.color()));
+        }
+        lines
+    }
+
+    pub fn scroll_up(&mut self) {
+        self.scroll = (self.scroll + 1).min(self.max_scroll());
+    }
+
+    pub fn scroll_down(&mut self) {
+        self.scroll = self.scroll.saturating_sub(1);
+    }
+
+    fn max_scroll(&self) -> usize {
+        self.lines().len().saturating_sub(SHOWN)
+    }
+
+    /// Draw the latest events, or those scrolled back to, at the bottom of `area`,
+    /// grouped by the turn they happened on.
+    /// Events too long for `area` carry on over as many rows as they need.
+    pub fn print(&self, ctx: &mut BTerm, area: Rect) {
+        let lines = self.lines();
+        let shown = SHOWN.min(area.height().max(0) as usize);
+        let end = lines.len().saturating_sub(self.scroll);
+
+        let mut rows = Vec::new();
+        for (text, color) in lines[..end].iter().rev() {
+            if rows.len() >= shown {
+                break;
+            }
+            for row in wrap(text, area.width().max(1) as usize).into_iter().rev() {
+                rows.push((row, *color));
+            }
+        }
+        rows.truncate(shown);
+        let top = area.y2 - rows.len() as i32;
+
+        for (y, (text, color)) in rows.iter().rev().enumerate() {
+            ctx.print_color(area.x1, top + y as i32, *color, RGB::new(), text);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_items_by_their_plural() {
+        assert_eq!(counted("arrow", 1), "arrow");
+        assert_eq!(counted("arrow", 50), "50 arrows");
+        assert_eq!(counted("torch", 2), "2 torches");
+        assert_eq!(counted("potion of healing", 3), "3 potions of healing");
+    }
+
+    #[test]
+    fn splits_a_word_longer_than_the_line() {
+        assert_eq!(wrap("a abcdefghij b", 4), vec!["a", "abcd", "efgh", "ij b"]);
+        assert_eq!(wrap("you hit it", 20), vec!["you hit it"]);
+    }
+
+    #[test]
+    fn empty_and_full_health_bars() {
+        assert_eq!(hp_bar(0, 10, 5), "[-----]");
+        assert_eq!(hp_bar(0, 0, 5), "[-----]");
+        assert_eq!(hp_bar(10, 10, 5), "[#####]");
+        assert_eq!(hp_bar(12, 10, 5), "[#####]");
+    }
+
+    #[test]
+    fn health_bars_round_to_the_nearest_mark() {
+        assert_eq!(hp_bar(5, 10, 5), "[###--]");
+        assert_eq!(hp_bar(3, 10, 5), "[##---]");
+        assert_eq!(hp_bar(2, 10, 5), "[#----]");
+        // Still alive, however little is left
+        assert_eq!(hp_bar(1, 100, 5), "[#----]");
+    }
 }
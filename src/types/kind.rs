@@ -1,11 +1,26 @@
-#[derive(Clone, Copy, Debug, PartialEq)]
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
 pub enum CellAccess {
     Takeable,
     Static,
     Impassable,
+    Hostile,
 }
 
-#[derive(Clone, Copy, Debug, PartialEq)]
+/// What a cell is drawn over or under when several share a tile, lowest first
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum RenderLayer {
+    Floor,
+    /// Anything that can be picked up
+    Item,
+    /// Walls, doors, stairs and traps
+    Feature,
+    /// Enemies and shopkeepers
+    Actor,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum CellKind {
     SoftArmor,
     HardArmor,
@@ -18,9 +33,192 @@ pub enum CellKind {
     Wall,
     Tunnel,
     Floor,
+    Enemy,
+    Gold,
+    Shopkeeper,
+    StairsDown,
+    StairsUp,
+    Food,
+    Trap,
+    Potion,
+    Torch,
+    LockedDoor,
+    Key,
+    Chest,
+    Shield,
+    /// A door that passes for a wall until it's found by searching
+    SecretDoor,
 }
 
 impl CellKind {
+    pub const ALL: [CellKind; 25] = [
+        CellKind::SoftArmor,
+        CellKind::HardArmor,
+        CellKind::BluntWeapon,
+        CellKind::EdgedWeapon,
+        CellKind::PointedWeapon,
+        CellKind::RangedWeapon,
+        CellKind::ClosedDoor,
+        CellKind::OpenedDoor,
+        CellKind::Wall,
+        CellKind::Tunnel,
+        CellKind::Floor,
+        CellKind::Enemy,
+        CellKind::Gold,
+        CellKind::Shopkeeper,
+        CellKind::StairsDown,
+        CellKind::StairsUp,
+        CellKind::Food,
+        CellKind::Trap,
+        CellKind::Potion,
+        CellKind::Torch,
+        CellKind::LockedDoor,
+        CellKind::Key,
+        CellKind::Chest,
+        CellKind::Shield,
+        CellKind::SecretDoor,
+    ];
+
+    /// Return the name the kind goes by in the theme file
+    pub fn name(self) -> &'static str {
+        match self {
+            CellKind::SoftArmor => "soft_armor",
+            CellKind::HardArmor => "hard_armor",
+            CellKind::BluntWeapon => "blunt_weapon",
+            CellKind::EdgedWeapon => "edged_weapon",
+            CellKind::PointedWeapon => "pointed_weapon",
+            CellKind::RangedWeapon => "ranged_weapon",
+            CellKind::ClosedDoor => "closed_door",
+            CellKind::OpenedDoor => "opened_door",
+            CellKind::Wall => "wall",
+            CellKind::Tunnel => "tunnel",
+            CellKind::Floor => "floor",
+            CellKind::Enemy => "enemy",
+            CellKind::Gold => "gold",
+            CellKind::Shopkeeper => "shopkeeper",
+            CellKind::StairsDown => "stairs_down",
+            CellKind::StairsUp => "stairs_up",
+            CellKind::Food => "food",
+            CellKind::Trap => "trap",
+            CellKind::Potion => "potion",
+            CellKind::Torch => "torch",
+            CellKind::LockedDoor => "locked_door",
+            CellKind::Key => "key",
+            CellKind::Chest => "chest",
+            CellKind::Shield => "shield",
+            CellKind::SecretDoor => "secret_door",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.iter().copied().find(|kind| kind.name() == name)
+    }
+
+    /// Return whether the kind of cell blocks the player's line of sight
+    pub fn is_opaque(self) -> bool {
+        matches!(
+            self,
+            CellKind::Wall | CellKind::ClosedDoor | CellKind::LockedDoor | CellKind::SecretDoor
+        )
+    }
+
+    /// Return whether the kind of cell is drawn as a wall
+    pub fn looks_like_wall(self) -> bool {
+        matches!(self, CellKind::Wall | CellKind::SecretDoor)
+    }
+
+    /// Return whether a wall beside this kind of cell is drawn joined up with it,
+    /// as walls are with each other and with the doors set in them
+    pub fn joins_walls(self) -> bool {
+        matches!(
+            self,
+            CellKind::Wall
+                | CellKind::SecretDoor
+                | CellKind::ClosedDoor
+                | CellKind::OpenedDoor
+                | CellKind::LockedDoor
+        )
+    }
+
+    /// Return whether the kind of cell is bare ground that things can be put down on
+    pub fn is_ground(self) -> bool {
+        matches!(
+            self,
+            CellKind::Floor | CellKind::Tunnel | CellKind::OpenedDoor
+        )
+    }
+
+    /// Return the layer the kind of cell is drawn in,
+    /// so the ground never covers what's lying or standing on it
+    pub fn render_layer(self) -> RenderLayer {
+        match self {
+            CellKind::Floor | CellKind::Tunnel | CellKind::OpenedDoor => RenderLayer::Floor,
+            CellKind::SoftArmor
+            | CellKind::HardArmor
+            | CellKind::Shield
+            | CellKind::BluntWeapon
+            | CellKind::EdgedWeapon
+            | CellKind::PointedWeapon
+            | CellKind::RangedWeapon
+            | CellKind::Gold
+            | CellKind::Food
+            | CellKind::Potion
+            | CellKind::Torch
+            | CellKind::Key => RenderLayer::Item,
+            CellKind::ClosedDoor
+            | CellKind::LockedDoor
+            | CellKind::Wall
+            | CellKind::SecretDoor
+            | CellKind::StairsDown
+            | CellKind::StairsUp
+            | CellKind::Trap
+            | CellKind::Chest => RenderLayer::Feature,
+            CellKind::Enemy | CellKind::Shopkeeper => RenderLayer::Actor,
+        }
+    }
+
+    /// Return the equipment slot this kind of item goes in, if any
+    pub fn equip_slot(self) -> Option<EquipSlot> {
+        match self {
+            CellKind::BluntWeapon
+            | CellKind::EdgedWeapon
+            | CellKind::PointedWeapon
+            | CellKind::RangedWeapon => Some(EquipSlot::Weapon),
+            CellKind::SoftArmor | CellKind::HardArmor => Some(EquipSlot::Armor),
+            CellKind::Shield => Some(EquipSlot::OffHand),
+            _ => None,
+        }
+    }
+
+    /// Return a line of flavor text for describing this kind of cell
+    pub fn flavor(self) -> &'static str {
+        match self {
+            CellKind::SoftArmor => "Supple, and better than nothing.",
+            CellKind::HardArmor => "Heavy, but it turns most blades.",
+            CellKind::BluntWeapon => "Good for cracking skulls.",
+            CellKind::EdgedWeapon => "Keep the edge away from yourself.",
+            CellKind::PointedWeapon => "The pointy end goes in the enemy.",
+            CellKind::RangedWeapon => "Strikes from across the room.",
+            CellKind::ClosedDoor | CellKind::OpenedDoor => "An old wooden door.",
+            CellKind::Wall | CellKind::SecretDoor => "Cold, damp stone.",
+            CellKind::Tunnel => "A narrow passage through the rock.",
+            CellKind::Floor => "Flagstones worn smooth.",
+            CellKind::Enemy => "It doesn't look friendly.",
+            CellKind::Gold => "Shiny.",
+            CellKind::Shopkeeper => "Always happy to take your gold.",
+            CellKind::StairsDown => "They lead further down.",
+            CellKind::StairsUp => "They lead back up.",
+            CellKind::Food => "It will keep hunger at bay for a while.",
+            CellKind::Trap => "Best not stepped on.",
+            CellKind::Potion => "Who knows what it does until it's drunk.",
+            CellKind::Torch => "It keeps the dark back while it burns.",
+            CellKind::LockedDoor => "A heavy door with a brass lock.",
+            CellKind::Key => "It must open something around here.",
+            CellKind::Chest => "A sturdy wooden chest bound in iron.",
+            CellKind::Shield => "Something to hide behind, if there's a hand free.",
+        }
+    }
+
     pub fn symbol(self) -> char {
         match self {
             CellKind::SoftArmor => '(',
@@ -34,6 +232,109 @@ impl CellKind {
             CellKind::Wall => '#',
             CellKind::Tunnel => '░',
             CellKind::Floor => '.',
+            CellKind::Enemy => 'e',
+            CellKind::Gold => '$',
+            CellKind::Shopkeeper => '&',
+            CellKind::StairsDown => '>',
+            CellKind::StairsUp => '<',
+            CellKind::Food => '%',
+            CellKind::Trap => '^',
+            CellKind::Potion => '!',
+            CellKind::Torch => '~',
+            CellKind::LockedDoor => '=',
+            CellKind::Key => '¬',
+            CellKind::Chest => '⌂',
+            CellKind::Shield => '0',
+            CellKind::SecretDoor => '#',
+        }
+    }
+}
+
+/// A group of items the inventory can be narrowed down to
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ItemCategory {
+    All,
+    Weapons,
+    Armor,
+    Other,
+}
+
+impl Default for ItemCategory {
+    fn default() -> Self {
+        ItemCategory::All
+    }
+}
+
+impl ItemCategory {
+    /// Return the category after this one, wrapping back around to `All`
+    pub fn next(self) -> Self {
+        match self {
+            ItemCategory::All => ItemCategory::Weapons,
+            ItemCategory::Weapons => ItemCategory::Armor,
+            ItemCategory::Armor => ItemCategory::Other,
+            ItemCategory::Other => ItemCategory::All,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ItemCategory::All => "All",
+            ItemCategory::Weapons => "Weapons",
+            ItemCategory::Armor => "Armor",
+            ItemCategory::Other => "Other",
+        }
+    }
+
+    /// Return whether items of `kind` fall in this category
+    pub fn contains(self, kind: CellKind) -> bool {
+        match self {
+            ItemCategory::All => true,
+            ItemCategory::Weapons => kind.equip_slot() == Some(EquipSlot::Weapon),
+            ItemCategory::Armor => matches!(
+                kind.equip_slot(),
+                Some(EquipSlot::Armor) | Some(EquipSlot::OffHand)
+            ),
+            ItemCategory::Other => kind.equip_slot().is_none(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum EquipSlot {
+    Weapon,
+    Armor,
+    /// The hand a one-handed weapon leaves free, for a shield
+    OffHand,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn layers_draw_floor_then_items_then_features_then_actors() {
+        assert!(CellKind::Floor.render_layer() < CellKind::Potion.render_layer());
+        assert!(CellKind::Potion.render_layer() < CellKind::StairsDown.render_layer());
+        assert!(CellKind::StairsDown.render_layer() < CellKind::Enemy.render_layer());
+    }
+
+    #[test]
+    fn nothing_put_down_is_drawn_under_the_ground() {
+        for kind in CellKind::ALL.iter().filter(|kind| kind.is_ground()) {
+            assert_eq!(kind.render_layer(), RenderLayer::Floor, "{:?}", kind);
+        }
+    }
+
+    #[test]
+    fn everything_worn_or_wielded_has_its_own_symbol() {
+        let gear = CellKind::ALL
+            .iter()
+            .filter(|kind| kind.equip_slot().is_some())
+            .collect::<Vec<_>>();
+        for (i, a) in gear.iter().enumerate() {
+            for b in &gear[i + 1..] {
+                assert_ne!(a.symbol(), b.symbol(), "{:?} and {:?}", a, b);
+            }
         }
     }
 }
@@ -0,0 +1,124 @@
+use bracket_lib::prelude::*;
+
+use crate::{
+    components::{GameCell, Inventory},
+    types::{CellAccess, CellKind, Dice, POTION_COLORS},
+};
+
+/// The kit a new game starts with
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Loadout {
+    /// Empty-handed, the way the game has always started
+    Wanderer,
+    /// A sword in hand and leather armor on
+    Warrior,
+    /// A potion of every color, none of them known yet
+    Mage,
+    /// A dagger in hand and a torch to see by
+    Rogue,
+}
+
+impl Loadout {
+    pub const ALL: [Loadout; 4] = [
+        Loadout::Wanderer,
+        Loadout::Warrior,
+        Loadout::Mage,
+        Loadout::Rogue,
+    ];
+
+    /// Return the name the loadout is picked by on the command line
+    pub fn name(self) -> &'static str {
+        match self {
+            Loadout::Wanderer => "wanderer",
+            Loadout::Warrior => "warrior",
+            Loadout::Mage => "mage",
+            Loadout::Rogue => "rogue",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|loadout| loadout.name() == name)
+    }
+
+    /// Return every item in the kit, each with whether it starts out equipped
+    fn items(self) -> Vec<(GameCell, bool)> {
+        match self {
+            Loadout::Wanderer => Vec::new(),
+            Loadout::Warrior => vec![
+                (
+                    GameCell::weapon(
+                        CellKind::EdgedWeapon,
+                        "sword",
+                        RGB::from_u8(0, 0, 255),
+                        Dice::new(1, 8),
+                    )
+                    .with_weight(8),
+                    true,
+                ),
+                (
+                    GameCell::armor(
+                        CellKind::SoftArmor,
+                        "leather armor",
+                        RGB::from_u8(150, 75, 0),
+                        1,
+                    )
+                    .with_weight(10),
+                    true,
+                ),
+            ],
+            Loadout::Mage => POTION_COLORS
+                .iter()
+                .map(|&(color, (r, g, b))| {
+                    (
+                        GameCell::new(
+                            Point::new(0, 0),
+                            CellKind::Potion,
+                            &format!("{} potion", color),
+                            RGB::from_u8(r, g, b),
+                            CellAccess::Takeable,
+                        )
+                        .with_weight(1),
+                        false,
+                    )
+                })
+                .collect(),
+            Loadout::Rogue => vec![
+                (
+                    GameCell::weapon(
+                        CellKind::EdgedWeapon,
+                        "dagger",
+                        RGB::from_u8(150, 150, 200),
+                        Dice::new(1, 4),
+                    )
+                    .with_speed(3)
+                    .with_weight(3),
+                    true,
+                ),
+                (
+                    GameCell::new(
+                        Point::new(0, 0),
+                        CellKind::Torch,
+                        "torch",
+                        RGB::from_u8(255, 150, 0),
+                        CellAccess::Takeable,
+                    )
+                    .with_weight(2)
+                    .with_light(3, 300),
+                    false,
+                ),
+            ],
+        }
+    }
+
+    /// Pack the kit into `inventory`, putting what's meant to be worn in its slot
+    pub fn equip(self, inventory: &mut Inventory) {
+        for (item, equipped) in self.items() {
+            if inventory.take(item).is_ok() && equipped {
+                let _ = inventory.equip(inventory.len() - 1);
+            }
+        }
+    }
+}
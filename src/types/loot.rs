@@ -0,0 +1,141 @@
+use std::collections::HashMap;
+
+use bracket_lib::prelude::*;
+
+use rand::Rng;
+
+use crate::{
+    components::GameCell,
+    types::{CellAccess, CellKind, Dice},
+};
+
+/// What an enemy might leave behind
+struct Drops {
+    /// Chance out of 100 of dropping anything at all
+    chance: u32,
+    /// Items to pick from, each with how likely it is relative to the others
+    items: Vec<(u32, GameCell)>,
+}
+
+/// What each kind of enemy can drop when it dies, by name
+pub struct LootTable {
+    drops: HashMap<String, Drops>,
+}
+
+impl LootTable {
+    pub fn new() -> Self {
+        let mut table = Self {
+            drops: HashMap::new(),
+        };
+
+        let dagger = GameCell::weapon(
+            CellKind::EdgedWeapon,
+            "dagger",
+            RGB::from_u8(150, 150, 200),
+            Dice::new(1, 4),
+        )
+        .with_speed(3)
+        .with_weight(3);
+        let club = GameCell::weapon(
+            CellKind::BluntWeapon,
+            "club",
+            RGB::from_u8(120, 80, 40),
+            Dice::new(1, 6),
+        )
+        .with_weight(10);
+        let ration = GameCell::new(
+            Point::new(0, 0),
+            CellKind::Food,
+            "ration",
+            RGB::from_u8(200, 150, 100),
+            CellAccess::Takeable,
+        )
+        .with_weight(1);
+
+        let gold = GameCell::new(
+            Point::new(0, 0),
+            CellKind::Gold,
+            "gold",
+            RGB::from_u8(255, 215, 0),
+            CellAccess::Takeable,
+        )
+        .with_value(20);
+
+        table.add("goblin", 30, vec![(2, dagger.clone()), (1, ration.clone())]);
+        table.add("rat", 10, vec![(1, ration.clone())]);
+        table.add("ogre", 60, vec![(1, club.clone()), (2, ration.clone())]);
+        table.add(
+            "chest",
+            75,
+            vec![(2, dagger), (1, club), (3, ration), (4, gold)],
+        );
+        table
+    }
+
+    /// Let the enemy called `name` drop one of `items` `chance` times out of 100
+    pub fn add(&mut self, name: &str, chance: u32, items: Vec<(u32, GameCell)>) {
+        self.drops.insert(name.to_owned(), Drops { chance, items });
+    }
+
+    /// Roll for what the enemy called `name` drops, if anything
+    pub fn roll<R: Rng>(&self, name: &str, rng: &mut R) -> Option<GameCell> {
+        let drops = self.drops.get(name)?;
+        let total: u32 = drops.items.iter().map(|(weight, _)| weight).sum();
+        if total == 0 || rng.gen_range(0, 100) >= drops.chance {
+            return None;
+        }
+
+        let mut pick = rng.gen_range(0, total);
+        for (weight, item) in &drops.items {
+            if pick < *weight {
+                return Some(item.clone());
+            }
+            pick -= weight;
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use rand::{rngs::StdRng, SeedableRng};
+
+    fn ration() -> GameCell {
+        GameCell::new(
+            Point::new(0, 0),
+            CellKind::Food,
+            "ration",
+            RGB::named(GRAY),
+            CellAccess::Takeable,
+        )
+    }
+
+    #[test]
+    fn a_zero_chance_never_drops() {
+        let mut table = LootTable::new();
+        table.add("ghost", 0, vec![(1, ration())]);
+        let mut rng = StdRng::seed_from_u64(1);
+        assert!((0..1000).all(|_| table.roll("ghost", &mut rng).is_none()));
+    }
+
+    #[test]
+    fn a_sure_chance_at_one_item_always_drops_it() {
+        let mut table = LootTable::new();
+        table.add("mule", 100, vec![(1, ration())]);
+        let mut rng = StdRng::seed_from_u64(1);
+        for _ in 0..1000 {
+            assert_eq!(
+                table.roll("mule", &mut rng).map(|item| item.name()),
+                Some(String::from("ration"))
+            );
+        }
+    }
+
+    #[test]
+    fn an_unknown_enemy_drops_nothing() {
+        let mut rng = StdRng::seed_from_u64(1);
+        assert!(LootTable::new().roll("nobody", &mut rng).is_none());
+    }
+}
@@ -1,7 +1,25 @@
+mod bestiary;
+mod dice;
 mod events;
 mod kind;
+mod loadout;
+mod loot;
 mod player;
+mod potion;
+mod rng;
+mod shop;
+mod stats;
+mod status;
 
-pub use events::GameEvents;
-pub use kind::{CellAccess, CellKind};
-pub use player::Player;
+pub use bestiary::Bestiary;
+pub use dice::Dice;
+pub use events::{counted, hp_bar, wrap, EventKind, GameEvents, HP_BAR_WIDTH};
+pub use kind::{CellAccess, CellKind, EquipSlot, ItemCategory};
+pub use loadout::Loadout;
+pub use loot::LootTable;
+pub use player::{Player, SIGHT_RADIUS};
+pub use potion::{PotionEffect, Potions, POTION_COLORS};
+pub use rng::{todays_seed, GameRng};
+pub use shop::Shop;
+pub use stats::Stats;
+pub use status::{Effect, StatusEffects};
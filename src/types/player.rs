@@ -1,11 +1,58 @@
 use bracket_lib::prelude::*;
 
+use serde::{Deserialize, Serialize};
+
+use super::{hp_bar, Bestiary, Stats, StatusEffects, HP_BAR_WIDTH};
+
+pub const SIGHT_RADIUS: i32 = 6;
+const MAX_HUNGER: u32 = 100;
+/// How many turns pass between each point of hunger lost
+const HUNGER_INTERVAL: u32 = 10;
+/// How much max HP each level gained adds
+const LEVEL_HEALTH: u32 = 3;
+const MAX_STAMINA: u32 = 10;
+/// How many turns pass between each point of stamina recovered
+const STAMINA_INTERVAL: u32 = 3;
+
+fn full_stamina() -> (u32, u32) {
+    (MAX_STAMINA, MAX_STAMINA)
+}
+
+fn facing_up() -> (i32, i32) {
+    (0, -1)
+}
+
+/// Return the total XP needed to reach level `n`
+pub fn xp_for_level(n: u32) -> u32 {
+    5 * n * n.saturating_sub(1)
+}
+
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Player {
     point: Point,
     lvl: u32,
     hp: (i32, u32),
-    xp: (i32, u32),
-    sight: (i32, i32, i32, i32),
+    xp: u32,
+    sight: i32,
+    gold: u32,
+    turns: u32,
+    hunger: u32,
+    last_combat: u32,
+    #[serde(default)]
+    effects: StatusEffects,
+    #[serde(default)]
+    stats: Stats,
+    /// Every kind of monster come across so far
+    #[serde(default)]
+    bestiary: Bestiary,
+    #[serde(default = "full_stamina")]
+    stamina: (u32, u32),
+    /// Direction the player last moved or attacked in
+    #[serde(default = "facing_up")]
+    facing: (i32, i32),
+    /// Whether the player is creeping along, harder to spot but slower
+    #[serde(default)]
+    sneaking: bool,
 }
 
 impl Player {
@@ -14,36 +61,96 @@ impl Player {
             point,
             lvl: 1,
             hp: (10, 10),
-            xp: (0, 10),
-            sight: (4, 4, 4, 4),
+            xp: 0,
+            sight: SIGHT_RADIUS,
+            gold: 0,
+            turns: 0,
+            hunger: MAX_HUNGER,
+            last_combat: 0,
+            effects: StatusEffects::default(),
+            stats: Stats::default(),
+            bestiary: Bestiary::default(),
+            stamina: full_stamina(),
+            facing: facing_up(),
+            sneaking: false,
         }
     }
 
-    pub fn print_info(&self, ctx: &mut BTerm, window_size: (u32, u32)) {
+    /// Draw the player's status in the column `area`
+    pub fn print_info(&self, ctx: &mut BTerm, area: Rect, items: usize) {
         ctx.print(
-            window_size.0 as i32 - 15,
-            window_size.1 as i32 - 4,
-            format!("Level: {}", self.lvl),
+            area.x1,
+            area.y1,
+            format!(
+                "SP: {}",
+                hp_bar(self.stamina.0, self.stamina.1, HP_BAR_WIDTH)
+            ),
         );
         ctx.print(
-            window_size.0 as i32 - 15,
-            window_size.1 as i32 - 3,
+            area.x1,
+            area.y1 + 1,
+            format!("Status: {}", self.effects.abbreviations()),
+        );
+        ctx.print(
+            area.x1,
+            area.y1 + 2,
+            format!("Hunger: {} / {}", self.hunger, MAX_HUNGER),
+        );
+        ctx.print(area.x1, area.y1 + 3, format!("Turn: {}", self.turns));
+        ctx.print(area.x1, area.y1 + 4, format!("Gold: {}", self.gold));
+        ctx.print(
+            area.x1,
+            area.y1 + 5,
+            format!("Pos: {}, {}", self.point.x, self.point.y),
+        );
+        ctx.print(area.x1, area.y1 + 6, format!("Items: {}", items));
+        ctx.print(area.x1, area.y1 + 7, format!("Level: {}", self.lvl));
+        ctx.print(
+            area.x1,
+            area.y1 + 8,
             format!("HP: {} / {}", self.hp.0, self.hp.1),
         );
         ctx.print(
-            window_size.0 as i32 - 15,
-            window_size.1 as i32 - 2,
-            format!("XP: {} / {}", self.xp.0, self.xp.1),
+            area.x1,
+            area.y1 + 9,
+            format!("XP: {} / {}", self.xp, xp_for_level(self.lvl + 1)),
         );
     }
 
+    pub fn set_point(&mut self, point: Point) {
+        self.point = point;
+    }
+
     pub fn move_pos(&mut self, a: i32, b: i32) {
         self.point.x += a;
         self.point.y += b;
     }
 
-    pub fn default_sight(&mut self) {
-        self.sight = (4, 4, 4, 4);
+    /// Return the direction the player is facing, as a step of at most one tile each way
+    pub fn facing(&self) -> (i32, i32) {
+        self.facing
+    }
+
+    /// Turn the player toward `dx` and `dy`
+    pub fn face(&mut self, dx: i32, dy: i32) {
+        if dx != 0 || dy != 0 {
+            self.facing = (dx.signum(), dy.signum());
+        }
+    }
+
+    pub fn sneaking(&self) -> bool {
+        self.sneaking
+    }
+
+    /// Start sneaking if the player wasn't, or stop if they were, returning whether they now are
+    pub fn toggle_sneak(&mut self) -> bool {
+        self.sneaking = !self.sneaking;
+        self.sneaking
+    }
+
+    /// Let the player see `radius` tiles away
+    pub fn set_sight(&mut self, radius: i32) {
+        self.sight = radius;
     }
 
     pub fn x(&self) -> i32 {
@@ -52,7 +159,147 @@ impl Player {
     pub fn y(&self) -> i32 {
         self.point.y
     }
-    pub fn sight(&self) -> (i32, i32, i32, i32) {
+    pub fn point(&self) -> Point {
+        self.point
+    }
+    /// Return how many tiles away the player can see
+    pub fn sight(&self) -> i32 {
         self.sight
     }
+    pub fn turns(&self) -> u32 {
+        self.turns
+    }
+    pub fn pass_turn(&mut self) {
+        self.turns += 1;
+    }
+    /// Remember that the player was fighting this turn
+    pub fn enter_combat(&mut self) {
+        self.last_combat = self.turns;
+    }
+    pub fn turns_since_combat(&self) -> u32 {
+        self.turns - self.last_combat
+    }
+    pub fn effects(&self) -> &StatusEffects {
+        &self.effects
+    }
+    pub fn effects_mut(&mut self) -> &mut StatusEffects {
+        &mut self.effects
+    }
+    pub fn stats(&self) -> &Stats {
+        &self.stats
+    }
+    pub fn stats_mut(&mut self) -> &mut Stats {
+        &mut self.stats
+    }
+    pub fn bestiary(&self) -> &Bestiary {
+        &self.bestiary
+    }
+    pub fn bestiary_mut(&mut self) -> &mut Bestiary {
+        &mut self.bestiary
+    }
+    pub fn hunger(&self) -> u32 {
+        self.hunger
+    }
+    /// Get hungrier as turns pass, or lose HP once there's nothing left.
+    /// Returns whether the player lost HP to starvation.
+    pub fn digest(&mut self) -> bool {
+        if self.turns % HUNGER_INTERVAL != 0 {
+            return false;
+        }
+        if self.hunger > 0 {
+            self.hunger -= 1;
+            false
+        } else {
+            self.hp.0 -= 1;
+            true
+        }
+    }
+    pub fn stamina(&self) -> u32 {
+        self.stamina.0
+    }
+    /// Spend `amount` stamina if the player has that much, returning whether they did
+    pub fn spend_stamina(&mut self, amount: u32) -> bool {
+        if self.stamina.0 < amount {
+            return false;
+        }
+        self.stamina.0 -= amount;
+        true
+    }
+    /// Get a point of stamina back every few turns
+    pub fn recover_stamina(&mut self) {
+        if self.turns % STAMINA_INTERVAL == 0 {
+            self.stamina.0 = (self.stamina.0 + 1).min(self.stamina.1);
+        }
+    }
+    pub fn eat(&mut self, nutrition: u32) {
+        self.hunger = (self.hunger + nutrition).min(MAX_HUNGER);
+    }
+    pub fn level(&self) -> u32 {
+        self.lvl
+    }
+    pub fn xp(&self) -> u32 {
+        self.xp
+    }
+    /// Gain `amount` XP, returning every level reached by it
+    pub fn gain_xp(&mut self, amount: u32) -> Vec<u32> {
+        self.xp += amount;
+
+        let mut reached = Vec::new();
+        while self.xp >= xp_for_level(self.lvl + 1) {
+            self.lvl += 1;
+            self.raise_max_health(LEVEL_HEALTH);
+            reached.push(self.lvl);
+        }
+        reached
+    }
+    pub fn gold(&self) -> u32 {
+        self.gold
+    }
+    pub fn add_gold(&mut self, amount: u32) {
+        self.gold += amount;
+        self.stats.record_gold(amount);
+    }
+    /// Spend `amount` gold if the player has that much, returning whether they did
+    pub fn spend_gold(&mut self, amount: u32) -> bool {
+        if self.gold < amount {
+            return false;
+        }
+        self.gold -= amount;
+        true
+    }
+    pub fn health(&self) -> i32 {
+        self.hp.0
+    }
+    pub fn max_health(&self) -> u32 {
+        self.hp.1
+    }
+    /// Raise the player's max HP by `amount`, healing them by as much
+    pub fn raise_max_health(&mut self, amount: u32) {
+        self.hp.1 += amount;
+        self.hp.0 += amount as i32;
+    }
+    /// Set the player's current HP, capped at their max HP
+    pub fn set_health(&mut self, hp: i32) {
+        self.hp.0 = hp.min(self.hp.1 as i32);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn each_level_takes_more_xp_than_the_last() {
+        assert_eq!(xp_for_level(0), 0);
+        assert_eq!(xp_for_level(1), 0);
+        assert_eq!(xp_for_level(2), 10);
+        assert_eq!(xp_for_level(3), 30);
+        for n in 2..50 {
+            assert!(
+                xp_for_level(n + 1) - xp_for_level(n) > xp_for_level(n) - xp_for_level(n - 1),
+                "level {}",
+                n
+            );
+        }
+    }
 }
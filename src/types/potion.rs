@@ -0,0 +1,117 @@
+use bracket_lib::prelude::*;
+
+use rand::{seq::SliceRandom, Rng};
+
+use serde::{Deserialize, Serialize};
+
+/// The color name and color of every kind of potion
+pub const POTION_COLORS: [(&str, (u8, u8, u8)); 3] = [
+    ("blue", (0, 100, 255)),
+    ("green", (0, 200, 100)),
+    ("murky", (120, 100, 60)),
+];
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum PotionEffect {
+    Heal,
+    Poison,
+    Strength,
+}
+
+impl PotionEffect {
+    const ALL: [PotionEffect; 3] = [
+        PotionEffect::Heal,
+        PotionEffect::Poison,
+        PotionEffect::Strength,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            PotionEffect::Heal => "potion of healing",
+            PotionEffect::Poison => "potion of poison",
+            PotionEffect::Strength => "potion of strength",
+        }
+    }
+}
+
+/// Which potion color does what this run, and which the player has figured out.
+///
+/// Potions are told apart by the color of their cell.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Potions {
+    kinds: Vec<(RGB, PotionEffect, bool)>,
+}
+
+impl Potions {
+    /// Shuffle the effects between the potion colors
+    pub fn new<R: Rng>(rng: &mut R) -> Self {
+        let mut effects = PotionEffect::ALL.to_vec();
+        effects.shuffle(rng);
+
+        let kinds = POTION_COLORS
+            .iter()
+            .zip(effects)
+            .map(|(&(_, (r, g, b)), effect)| (RGB::from_u8(r, g, b), effect, false))
+            .collect();
+        Self { kinds }
+    }
+
+    /// Return what potions of `color` do
+    pub fn effect(&self, color: RGB) -> Option<PotionEffect> {
+        self.kinds
+            .iter()
+            .find(|(c, _, _)| *c == color)
+            .map(|&(_, effect, _)| effect)
+    }
+
+    pub fn is_identified(&self, color: RGB) -> bool {
+        self.kinds
+            .iter()
+            .any(|&(c, _, identified)| c == color && identified)
+    }
+
+    /// Remember what potions of `color` do from now on
+    pub fn identify(&mut self, color: RGB) {
+        for (c, _, identified) in self.kinds.iter_mut() {
+            if *c == color {
+                *identified = true;
+            }
+        }
+    }
+
+    /// Return what the player would call a potion of `color` named `unknown`
+    pub fn name(&self, color: RGB, unknown: &str) -> String {
+        match self.effect(color) {
+            Some(effect) if self.is_identified(color) => String::from(effect.name()),
+            _ => String::from(unknown),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use rand::{rngs::StdRng, SeedableRng};
+
+    fn color(index: usize) -> RGB {
+        let (_, (r, g, b)) = POTION_COLORS[index];
+        RGB::from_u8(r, g, b)
+    }
+
+    #[test]
+    fn identifying_a_potion_names_every_one_of_its_color() {
+        let mut potions = Potions::new(&mut StdRng::seed_from_u64(3));
+        let (blue, green) = (color(0), color(1));
+        let effect = potions.effect(blue).unwrap();
+        assert_eq!(potions.name(blue, "blue potion"), "blue potion");
+
+        potions.identify(blue);
+        // Another blue potion found later, and the same after saving and loading
+        assert_eq!(potions.name(blue, "blue potion"), effect.name());
+        let loaded: Potions =
+            serde_json::from_str(&serde_json::to_string(&potions).unwrap()).unwrap();
+        assert_eq!(loaded.name(blue, "blue potion"), effect.name());
+        assert_eq!(loaded.name(green, "green potion"), "green potion");
+    }
+}
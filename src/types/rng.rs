@@ -0,0 +1,106 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use rand::{rngs::StdRng, Error, RngCore, SeedableRng};
+
+/// The one source of randomness for everything that happens during play,
+/// so replaying a seed replays the same game.
+///
+/// Levels are generated from the seed and their depth rather than from this,
+/// so they come out the same no matter what happened before reaching them.
+pub struct GameRng {
+    seed: u64,
+    rng: StdRng,
+}
+
+impl GameRng {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+
+    /// Seed from the system clock
+    pub fn from_clock() -> Self {
+        let seed = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |time| time.as_nanos() as u64);
+        Self::new(seed)
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+}
+
+/// Return the seed for the daily challenge on the UTC date `year`-`month`-`day`,
+/// which reads as the date itself, like 20240131
+pub fn daily_seed(year: u32, month: u32, day: u32) -> u64 {
+    u64::from(year) * 10_000 + u64::from(month) * 100 + u64::from(day)
+}
+
+/// Return the seed for today's daily challenge, going by the system clock in UTC
+pub fn todays_seed() -> u64 {
+    let days = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |time| time.as_secs() / 86_400);
+    let (year, month, day) = civil_date(days);
+    daily_seed(year, month, day)
+}
+
+/// Return the year, month and day `days` days after 1970-01-01
+fn civil_date(days: u64) -> (u32, u32, u32) {
+    // Counted in 400 year eras starting from March, so leap days fall at the end of a year
+    let days = days + 719_468;
+    let era = days / 146_097;
+    let day_of_era = days % 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_from_march = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_from_march + 2) / 5 + 1;
+    let month = if month_from_march < 10 {
+        month_from_march + 3
+    } else {
+        month_from_march - 9
+    };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year as u32, month as u32, day as u32)
+}
+
+impl RngCore for GameRng {
+    fn next_u32(&mut self) -> u32 {
+        self.rng.next_u32()
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.rng.next_u64()
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        self.rng.fill_bytes(dest)
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), Error> {
+        self.rng.try_fill_bytes(dest)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_daily_seed_reads_as_the_date() {
+        assert_eq!(daily_seed(2024, 1, 31), 20_240_131);
+        assert_eq!(daily_seed(2024, 1, 31), daily_seed(2024, 1, 31));
+        assert_ne!(daily_seed(2024, 1, 31), daily_seed(2024, 2, 1));
+    }
+
+    #[test]
+    fn counts_days_into_dates() {
+        assert_eq!(civil_date(0), (1970, 1, 1));
+        assert_eq!(civil_date(11_016), (2000, 2, 29));
+        assert_eq!(civil_date(19_753), (2024, 1, 31));
+    }
+}
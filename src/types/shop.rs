@@ -0,0 +1,200 @@
+use bracket_lib::prelude::*;
+
+use crate::{
+    components::{GameCell, Inventory},
+    types::{CellAccess, CellKind, Dice, Effect, Player},
+};
+
+pub struct Shop {
+    stock: Vec<GameCell>,
+}
+
+impl Shop {
+    pub fn new() -> Self {
+        Self {
+            stock: vec![
+                GameCell::weapon(
+                    CellKind::EdgedWeapon,
+                    "short sword",
+                    RGB::from_u8(0, 0, 255),
+                    Dice::new(1, 6),
+                )
+                .with_weight(6)
+                .with_value(30),
+                GameCell::weapon(
+                    CellKind::BluntWeapon,
+                    "mace",
+                    RGB::from_u8(100, 100, 150),
+                    Dice::new(1, 4),
+                )
+                .with_weight(8)
+                .with_value(25),
+                GameCell::weapon(
+                    CellKind::PointedWeapon,
+                    "spear",
+                    RGB::from_u8(160, 130, 90),
+                    Dice::new(1, 6),
+                )
+                .with_reach(2)
+                .with_weight(7)
+                .with_value(45),
+                GameCell::weapon(
+                    CellKind::EdgedWeapon,
+                    "greatsword",
+                    RGB::from_u8(200, 200, 230),
+                    Dice::new(2, 6),
+                )
+                .with_hands(2)
+                .with_weight(14)
+                .with_value(70),
+                GameCell::weapon(
+                    CellKind::EdgedWeapon,
+                    "serrated blade",
+                    RGB::from_u8(180, 60, 60),
+                    Dice::new(1, 4),
+                )
+                .with_on_hit_effect(Effect::Bleed, 3)
+                .with_weight(5)
+                .with_value(50),
+                GameCell::weapon(
+                    CellKind::RangedWeapon,
+                    "short bow",
+                    RGB::from_u8(150, 100, 50),
+                    Dice::new(1, 6),
+                )
+                .with_weight(4)
+                .with_value(40),
+                GameCell::armor(
+                    CellKind::HardArmor,
+                    "chain mail",
+                    RGB::from_u8(170, 170, 170),
+                    3,
+                )
+                .with_weight(20)
+                .with_value(50),
+                GameCell::armor(CellKind::Shield, "buckler", RGB::from_u8(140, 100, 60), 1)
+                    .with_weight(5)
+                    .with_value(20),
+                GameCell::new(
+                    Point::new(0, 0),
+                    CellKind::Food,
+                    "ration",
+                    RGB::from_u8(200, 150, 100),
+                    CellAccess::Takeable,
+                )
+                .with_weight(1)
+                .with_value(5),
+            ],
+        }
+    }
+
+    pub fn print(&self, ctx: &mut BTerm, window_size: (u32, u32), gold: u32) {
+        ctx.draw_box(
+            window_size.0 as i32 / 4,
+            window_size.1 as i32 / 4,
+            window_size.0 as i32 / 2,
+            self.stock.len() as i32 + 3,
+            RGB::from_u8(0, 170, 0),
+            RGB::from_u8(100, 100, 100),
+        );
+        ctx.print(
+            window_size.0 as i32 / 4 + 1,
+            window_size.1 as i32 / 4,
+            format!("Buy what? You have {} gold.", gold),
+        );
+        for (y, item) in self.stock.iter().enumerate() {
+            ctx.print(
+                window_size.0 as i32 / 4 + 1,
+                window_size.1 as i32 / 4 + 2 + y as i32,
+                format!(
+                    "{}) {} - {} gold",
+                    (b'a' + y as u8) as char,
+                    item.label(),
+                    item.value()
+                ),
+            );
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.stock.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.stock.is_empty()
+    }
+
+    /// Sell the player the item at `index`, returning a message describing how it went
+    pub fn buy(
+        &self,
+        index: usize,
+        player: &mut Player,
+        inventory: &mut Inventory,
+    ) -> Result<String, String> {
+        let item = match self.stock.get(index) {
+            Some(item) => item,
+            None => return Err(String::from("That isn't for sale.")),
+        };
+        if !inventory.can_carry(item) {
+            return Err(String::from("You are carrying too much."));
+        }
+        if !player.spend_gold(item.value()) {
+            return Err(format!(
+                "You can't afford the {}. It costs {} gold.",
+                item.name(),
+                item.value()
+            ));
+        }
+        inventory.take(item.clone())?;
+        Ok(format!(
+            "You buy the {} for {} gold.",
+            item.name(),
+            item.value()
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn buying_pays_the_price_in_gold() {
+        let shop = Shop::new();
+        let mut player = Player::new(Point::new(0, 0));
+        let mut inventory = Inventory::new();
+        player.add_gold(35);
+
+        assert_eq!(
+            shop.buy(0, &mut player, &mut inventory),
+            Ok(String::from("You buy the short sword for 30 gold."))
+        );
+        assert_eq!(player.gold(), 5);
+        assert_eq!(inventory.item_count(), 1);
+
+        let ration = shop.len() - 1;
+        assert!(shop.buy(ration, &mut player, &mut inventory).is_ok());
+        assert_eq!(player.gold(), 0);
+    }
+
+    #[test]
+    fn turns_away_a_player_short_of_gold() {
+        let shop = Shop::new();
+        let mut player = Player::new(Point::new(0, 0));
+        let mut inventory = Inventory::new();
+        player.add_gold(29);
+
+        assert_eq!(
+            shop.buy(0, &mut player, &mut inventory),
+            Err(String::from(
+                "You can't afford the short sword. It costs 30 gold."
+            ))
+        );
+        assert_eq!(player.gold(), 29);
+        assert!(inventory.is_empty());
+        assert_eq!(
+            shop.buy(shop.len(), &mut player, &mut inventory),
+            Err(String::from("That isn't for sale."))
+        );
+    }
+}
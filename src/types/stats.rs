@@ -0,0 +1,42 @@
+use serde::{Deserialize, Serialize};
+
+/// A tally of the run, shown when the player dies
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Stats {
+    kills: u32,
+    gold_collected: u32,
+    deepest: u32,
+    cause_of_death: Option<String>,
+}
+
+impl Stats {
+    pub fn record_kill(&mut self) {
+        self.kills += 1;
+    }
+    pub fn record_gold(&mut self, amount: u32) {
+        self.gold_collected += amount;
+    }
+    pub fn record_depth(&mut self, depth: u32) {
+        self.deepest = self.deepest.max(depth);
+    }
+    /// Remember what killed the player, keeping the first cause if several land at once
+    pub fn record_death(&mut self, cause: &str) {
+        if self.cause_of_death.is_none() {
+            self.cause_of_death = Some(cause.to_owned());
+        }
+    }
+
+    /// Return a line per stat for a player who lasted `turns` turns
+    pub fn summary(&self, turns: u32) -> Vec<String> {
+        vec![
+            format!("Turns survived: {}", turns),
+            format!("Monsters killed: {}", self.kills),
+            format!("Gold collected: {}", self.gold_collected),
+            format!("Deepest level: {}", self.deepest),
+            format!(
+                "Cause of death: {}",
+                self.cause_of_death.as_deref().unwrap_or("unknown")
+            ),
+        ]
+    }
+}
@@ -0,0 +1,100 @@
+use serde::{Deserialize, Serialize};
+
+/// Most turns an effect can build up to by stacking
+const MAX_STACKED_TURNS: u32 = 12;
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Effect {
+    Poison,
+    Regeneration,
+    Haste,
+    /// Left by serrated blades, and stopped by regeneration
+    Bleed,
+}
+
+impl Effect {
+    pub fn name(self) -> &'static str {
+        match self {
+            Effect::Poison => "poison",
+            Effect::Regeneration => "regeneration",
+            Effect::Haste => "haste",
+            Effect::Bleed => "bleeding",
+        }
+    }
+
+    /// Return the short name shown in the player panel
+    pub fn abbreviation(self) -> &'static str {
+        match self {
+            Effect::Poison => "Psn",
+            Effect::Regeneration => "Rgn",
+            Effect::Haste => "Hst",
+            Effect::Bleed => "Bld",
+        }
+    }
+}
+
+/// Effects on the player or an enemy along with how many turns each has left
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct StatusEffects {
+    effects: Vec<(Effect, u32)>,
+}
+
+impl StatusEffects {
+    /// Start `effect` for `turns` turns.
+    /// An effect that's already going is refreshed rather than stacked.
+    pub fn apply(&mut self, effect: Effect, turns: u32) {
+        match self.effects.iter_mut().find(|(e, _)| *e == effect) {
+            Some((_, left)) => *left = (*left).max(turns),
+            None => self.effects.push((effect, turns)),
+        }
+    }
+
+    /// Add `turns` more turns of `effect` onto any it already has,
+    /// up to `MAX_STACKED_TURNS`
+    pub fn stack(&mut self, effect: Effect, turns: u32) {
+        match self.effects.iter_mut().find(|(e, _)| *e == effect) {
+            Some((_, left)) => *left = (*left + turns).min(MAX_STACKED_TURNS),
+            None => self.effects.push((effect, turns.min(MAX_STACKED_TURNS))),
+        }
+    }
+
+    /// End `effect` early, returning whether it was going
+    pub fn cure(&mut self, effect: Effect) -> bool {
+        let had = self.has(effect);
+        self.effects.retain(|(e, _)| *e != effect);
+        had
+    }
+
+    pub fn has(&self, effect: Effect) -> bool {
+        self.effects.iter().any(|(e, _)| *e == effect)
+    }
+
+    /// Return every active effect
+    pub fn active(&self) -> Vec<Effect> {
+        self.effects.iter().map(|&(effect, _)| effect).collect()
+    }
+
+    /// Count every effect down a turn, returning the ones that wore off
+    pub fn tick(&mut self) -> Vec<Effect> {
+        for (_, left) in self.effects.iter_mut() {
+            *left = left.saturating_sub(1);
+        }
+        let expired = self
+            .effects
+            .iter()
+            .filter(|(_, left)| *left == 0)
+            .map(|&(effect, _)| effect)
+            .collect();
+        self.effects.retain(|(_, left)| *left > 0);
+        expired
+    }
+
+    /// Return the abbreviations of every active effect, like "Psn Hst"
+    pub fn abbreviations(&self) -> String {
+        self.effects
+            .iter()
+            .map(|(effect, _)| effect.abbreviation())
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
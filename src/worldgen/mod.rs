@@ -0,0 +1,308 @@
+use bracket_lib::prelude::*;
+
+use legion::*;
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use crate::{
+    components::{CellVisibility, GameCell},
+    types::{CellAccess, CellKind},
+};
+
+const GRAY: (u8, u8, u8) = (150, 150, 150);
+const BROWN: (u8, u8, u8) = (150, 75, 0);
+
+const MAX_ROOMS: usize = 12;
+const MIN_ROOM_SIZE: i32 = 4;
+const MAX_ROOM_SIZE: i32 = 10;
+/// Chance of each room past the second also getting a hidden passage
+/// to the room carved two before it
+const SECRET_PASSAGE_CHANCE: f64 = 0.25;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Tile {
+    Rock,
+    Room,
+    Corridor,
+    Door,
+    /// A corridor the rooms it joins keep hidden behind secret doors
+    Passage,
+    SecretDoor,
+}
+
+struct Grid {
+    width: i32,
+    height: i32,
+    tiles: Vec<Tile>,
+}
+
+impl Grid {
+    fn new(width: i32, height: i32) -> Self {
+        Self {
+            width,
+            height,
+            tiles: vec![Tile::Rock; (width * height) as usize],
+        }
+    }
+
+    fn in_bounds(&self, x: i32, y: i32) -> bool {
+        x >= 0 && x < self.width && y >= 0 && y < self.height
+    }
+
+    fn get(&self, x: i32, y: i32) -> Tile {
+        if self.in_bounds(x, y) {
+            self.tiles[(y * self.width + x) as usize]
+        } else {
+            Tile::Rock
+        }
+    }
+
+    fn set(&mut self, x: i32, y: i32, tile: Tile) {
+        if self.in_bounds(x, y) {
+            self.tiles[(y * self.width + x) as usize] = tile;
+        }
+    }
+
+    fn carve_room(&mut self, room: &Rect) {
+        for y in room.y1..room.y2 {
+            for x in room.x1..room.x2 {
+                self.set(x, y, Tile::Room);
+            }
+        }
+    }
+
+    /// Dig `tile`, a corridor or passage, through the rock at `x`, `y`
+    fn carve_corridor(&mut self, x: i32, y: i32, tile: Tile) {
+        if self.get(x, y) == Tile::Rock {
+            self.set(x, y, tile);
+        }
+    }
+
+    /// Dig an L-shaped corridor, or passage if `tile` says so, between two points
+    fn connect(&mut self, from: Point, to: Point, horizontal_first: bool, tile: Tile) {
+        let corner = if horizontal_first {
+            Point::new(to.x, from.y)
+        } else {
+            Point::new(from.x, to.y)
+        };
+        for x in from.x.min(corner.x)..=from.x.max(corner.x) {
+            self.carve_corridor(x, from.y, tile);
+        }
+        for y in from.y.min(corner.y)..=from.y.max(corner.y) {
+            self.carve_corridor(from.x, y, tile);
+        }
+        for x in corner.x.min(to.x)..=corner.x.max(to.x) {
+            self.carve_corridor(x, to.y, tile);
+        }
+        for y in corner.y.min(to.y)..=corner.y.max(to.y) {
+            self.carve_corridor(to.x, y, tile);
+        }
+    }
+
+    /// Turn corridor tiles into doors where they pierce a room's wall,
+    /// and passage tiles into secret doors
+    fn place_doors(&mut self, rooms: &[Rect]) {
+        for room in rooms {
+            for y in room.y1..room.y2 {
+                self.place_door(room.x1 - 1, y, (0, 1));
+                self.place_door(room.x2, y, (0, 1));
+            }
+            for x in room.x1..room.x2 {
+                self.place_door(x, room.y1 - 1, (1, 0));
+                self.place_door(x, room.y2, (1, 0));
+            }
+        }
+    }
+
+    /// `along` is the direction the wall runs in at this tile
+    fn place_door(&mut self, x: i32, y: i32, along: (i32, i32)) {
+        let door = match self.get(x, y) {
+            Tile::Corridor => Tile::Door,
+            Tile::Passage => Tile::SecretDoor,
+            _ => return,
+        };
+        if self.get(x + along.0, y + along.1) == Tile::Rock
+            && self.get(x - along.0, y - along.1) == Tile::Rock
+        {
+            self.set(x, y, door);
+        }
+    }
+
+    fn is_open(&self, x: i32, y: i32) -> bool {
+        self.get(x, y) != Tile::Rock
+    }
+
+    fn touches_open(&self, x: i32, y: i32) -> bool {
+        (-1..=1).any(|dy| (-1..=1).any(|dx| self.is_open(x + dx, y + dy)))
+    }
+}
+
+/// Carve a dungeon of rooms joined by corridors into `world` and return the rooms.
+///
+/// The same seed always produces the same layout. Every room is joined to the
+/// one carved before it, so the whole map is reachable from the first room,
+/// which is where the player should start. Some rooms also get a hidden passage
+/// to the room two before them, a shortcut that's never needed to get anywhere.
+pub fn generate_rooms(world: &mut World, width: i32, height: i32, seed: u64) -> Vec<Rect> {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut grid = Grid::new(width, height);
+
+    let mut rooms: Vec<Rect> = Vec::with_capacity(MAX_ROOMS);
+    for _ in 0..MAX_ROOMS * 4 {
+        if rooms.len() == MAX_ROOMS {
+            break;
+        }
+        let w = rng.gen_range(MIN_ROOM_SIZE, MAX_ROOM_SIZE + 1);
+        let h = rng.gen_range(MIN_ROOM_SIZE, MAX_ROOM_SIZE + 1);
+        let x = rng.gen_range(1, width - w - 1);
+        let y = rng.gen_range(1, height - h - 1);
+        let room = Rect::with_size(x, y, w, h);
+
+        // Keep at least two tiles of rock between rooms so their walls never merge
+        let padded = Rect::with_size(x - 2, y - 2, w + 4, h + 4);
+        if rooms.iter().any(|other| padded.intersect(other)) {
+            continue;
+        }
+
+        grid.carve_room(&room);
+        if let Some(prev) = rooms.last() {
+            grid.connect(prev.center(), room.center(), rng.gen(), Tile::Corridor);
+        }
+        rooms.push(room);
+    }
+    // Dug after every corridor, so the rooms' layout doesn't change with them
+    for i in 2..rooms.len() {
+        if rng.gen_bool(SECRET_PASSAGE_CHANCE) {
+            let (from, to) = (rooms[i - 2].center(), rooms[i].center());
+            grid.connect(from, to, rng.gen(), Tile::Passage);
+        }
+    }
+    grid.place_doors(&rooms);
+
+    let mut positions = Vec::with_capacity((width * height) as usize);
+    for y in 0..height {
+        for x in 0..width {
+            let (kind, name, color, access) = match grid.get(x, y) {
+                Tile::Room => (CellKind::Floor, "floor", GRAY, CellAccess::Static),
+                Tile::Corridor | Tile::Passage => {
+                    (CellKind::Tunnel, "tunnel", GRAY, CellAccess::Static)
+                }
+                Tile::Door => (CellKind::ClosedDoor, "door", BROWN, CellAccess::Impassable),
+                Tile::SecretDoor => (CellKind::SecretDoor, "wall", GRAY, CellAccess::Impassable),
+                Tile::Rock if grid.touches_open(x, y) => {
+                    (CellKind::Wall, "wall", GRAY, CellAccess::Impassable)
+                }
+                Tile::Rock => continue,
+            };
+            positions.push((
+                GameCell::new(Point::new(x, y), kind, name, RGB::named(color), access),
+                CellVisibility::Unvisited,
+            ));
+        }
+    }
+    world.extend(positions);
+
+    rooms
+}
+
+/// Return a hash of where every cell in `world` is and what it is,
+/// the same for the same map whatever order legion keeps its entities in.
+///
+/// The cells are sorted before hashing and hashed with FNV-1a rather than the
+/// standard hasher, so the result also holds across runs and Rust versions.
+pub fn world_fingerprint(world: &World) -> u64 {
+    let mut query = <(Read<GameCell>,)>::query();
+
+    let mut cells = query
+        .iter(world)
+        .flat_map(|(cell,)| {
+            let (kind, access) = (cell.kind(), cell.access());
+            cell.tiles()
+                .into_iter()
+                .map(move |tile| (tile.x, tile.y, kind.name(), access as u8))
+        })
+        .collect::<Vec<_>>();
+    cells.sort_unstable();
+
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    let mut feed = |bytes: &[u8]| {
+        for &byte in bytes {
+            hash ^= u64::from(byte);
+            hash = hash.wrapping_mul(0x0100_0000_01b3);
+        }
+    };
+    for (x, y, kind, access) in cells {
+        feed(&x.to_le_bytes());
+        feed(&y.to_le_bytes());
+        feed(kind.as_bytes());
+        feed(&[0, access]);
+    }
+    hash
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn level(seed: u64) -> World {
+        let mut world = World::default();
+        generate_rooms(&mut world, 80, 50, seed);
+        world
+    }
+
+    #[test]
+    fn the_same_seed_leaves_the_same_fingerprint() {
+        assert_eq!(world_fingerprint(&level(7)), world_fingerprint(&level(7)));
+    }
+
+    #[test]
+    fn another_seed_leaves_another_fingerprint() {
+        assert_ne!(world_fingerprint(&level(7)), world_fingerprint(&level(8)));
+    }
+
+    #[test]
+    fn every_room_can_be_walked_to_from_the_first() {
+        for seed in 0..20 {
+            let mut world = World::default();
+            let rooms = generate_rooms(&mut world, 80, 50, seed);
+
+            // Doors open when bumped, but secret doors have to be found first
+            let mut open = std::collections::HashSet::new();
+            let mut floor = Vec::new();
+            for (cell,) in <(Read<GameCell>,)>::query().iter(&world) {
+                match cell.kind() {
+                    CellKind::Floor => {
+                        floor.push(cell.point());
+                        open.insert(cell.point());
+                    }
+                    CellKind::Tunnel | CellKind::ClosedDoor => {
+                        open.insert(cell.point());
+                    }
+                    _ => (),
+                }
+            }
+
+            let mut reached = std::collections::HashSet::new();
+            let mut frontier = vec![rooms[0].center()];
+            while let Some(point) = frontier.pop() {
+                if !open.contains(&point) || !reached.insert(point) {
+                    continue;
+                }
+                for dy in -1..=1 {
+                    for dx in -1..=1 {
+                        frontier.push(Point::new(point.x + dx, point.y + dy));
+                    }
+                }
+            }
+
+            for point in floor {
+                assert!(
+                    reached.contains(&point),
+                    "seed {} left {:?} cut off",
+                    seed,
+                    point
+                );
+            }
+        }
+    }
+}